@@ -0,0 +1,1248 @@
+use crate::error::{CommandError, ErrorKind};
+use crate::rdb::RdbFile;
+use crate::safety::{self, CommandInfo, Confirmations};
+use crate::ssh_tunnel::{SshConfig, SshTunnel};
+use redis::aio::{ConnectionLike, MultiplexedConnection};
+use redis::cluster::{ClusterClient, ClusterClientBuilder};
+use redis::cluster_async::ClusterConnection;
+use redis::{Cmd, Pipeline, RedisFuture, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// How long a Sentinel lookup is trusted before asking again. Bounds how
+/// long we keep talking to a demoted master after a failover.
+const SENTINEL_RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+const SENTINEL_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionMode {
+    #[default]
+    Standalone,
+    Cluster,
+    Sentinel,
+}
+
+/// Sentinel settings. `host`/`port` of the profile are ignored in Sentinel
+/// mode, the data node is resolved through these sentinels instead.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SentinelConfig {
+    /// `host:port` of each sentinel, tried in order
+    pub addresses: Vec<String>,
+    pub master_name: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Browse a replica of the master instead of the master itself
+    #[serde(default)]
+    pub use_replica: bool,
+}
+
+/// TLS settings, only used when `enable_ssl` is on. Certificate files are PEM.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsConfig {
+    /// Trust this CA bundle instead of the system roots
+    pub ca_cert_path: Option<String>,
+    /// Client certificate and key for mTLS
+    pub client_cert_path: Option<String>,
+    pub client_key_path: Option<String>,
+    /// Name used for SNI and certificate verification when it differs from `host`
+    pub server_name: Option<String>,
+    /// Skip certificate verification, for self-signed dev servers
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct RedisConfig {
+    pub host: String,
+    pub port: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(rename = "enableSSL")]
+    pub enable_ssl: bool,
+    #[serde(default)]
+    pub mode: ConnectionMode,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub sentinel: Option<SentinelConfig>,
+    /// Reach the server through a bastion host
+    #[serde(default)]
+    pub ssh: Option<SshConfig>,
+    /// Refuse every mutating command, enforced here rather than in the UI
+    #[serde(default, rename = "readOnly")]
+    pub read_only: bool,
+    /// Commands that only run with a confirmation token (FLUSHDB, KEYS,
+    /// DEL of many keys, ...)
+    #[serde(default, rename = "confirmCommands")]
+    pub confirm_commands: Vec<String>,
+    /// How long to wait for a reply; defaults to `DEFAULT_RESPONSE_TIMEOUT`
+    #[serde(default, rename = "responseTimeoutSecs")]
+    pub response_timeout_secs: Option<u64>,
+}
+
+/// redis-rs gives up after 500ms by default, too short for whole-value
+/// reads, DUMP of a big key or a long MEMORY USAGE pipeline.
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+impl RedisConfig {
+    /// Read-only by choice, or because the session browses a replica,
+    /// which refuses writes anyway.
+    fn is_read_only(&self) -> bool {
+        self.read_only
+            || (self.mode == ConnectionMode::Sentinel
+                && self.sentinel.as_ref().is_some_and(|s| s.use_replica))
+    }
+
+    fn response_timeout(&self) -> Duration {
+        self.response_timeout_secs
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RESPONSE_TIMEOUT)
+    }
+
+    fn to_url(&self) -> String {
+        self.node_url(&self.host, &self.port)
+    }
+
+    /// URL for another node of the same deployment (cluster masters),
+    /// reusing the profile's credentials and TLS setting.
+    fn node_url(&self, host: &str, port: &str) -> String {
+        let auth = url_auth(&self.username, &self.password);
+        format!(
+            "{}://{}{}:{}{}",
+            self.scheme(),
+            auth,
+            host,
+            port,
+            self.url_fragment()
+        )
+    }
+
+    /// Sentinels have their own credentials, separate from the data nodes.
+    fn sentinel_url(&self, sentinel: &SentinelConfig, address: &str) -> String {
+        let auth = url_auth(&sentinel.username, &sentinel.password);
+        format!(
+            "{}://{}{}{}",
+            self.scheme(),
+            auth,
+            address,
+            self.url_fragment()
+        )
+    }
+
+    fn scheme(&self) -> &'static str {
+        if self.enable_ssl {
+            "rediss"
+        } else {
+            "redis"
+        }
+    }
+
+    // redis-rs reads certificate verification off the URL fragment
+    fn url_fragment(&self) -> &'static str {
+        match &self.tls {
+            Some(tls) if self.enable_ssl && tls.insecure => "#insecure",
+            _ => "",
+        }
+    }
+
+    fn active_tls(&self) -> Option<&TlsConfig> {
+        self.tls.as_ref().filter(|_| self.enable_ssl)
+    }
+
+    /// Custom CA / client certificate, `None` when the defaults apply.
+    fn tls_certificates(&self) -> Result<Option<redis::TlsCertificates>, CommandError> {
+        let Some(tls) = self.active_tls() else {
+            return Ok(None);
+        };
+
+        let read = |path: &str| {
+            std::fs::read(path)
+                .map_err(|e| CommandError::invalid(format!("Failed to read {}: {}", path, e)))
+        };
+
+        let root_cert = match tls.ca_cert_path.as_deref().filter(|p| !p.is_empty()) {
+            Some(path) => Some(read(path)?),
+            None => None,
+        };
+        let client_tls = match (
+            tls.client_cert_path.as_deref().filter(|p| !p.is_empty()),
+            tls.client_key_path.as_deref().filter(|p| !p.is_empty()),
+        ) {
+            (Some(cert), Some(key)) => Some(redis::ClientTlsConfig {
+                client_cert: read(cert)?,
+                client_key: read(key)?,
+            }),
+            (None, None) => None,
+            _ => {
+                return Err(CommandError::invalid(
+                    "Client certificate and key must be set together",
+                ))
+            }
+        };
+
+        if root_cert.is_none() && client_tls.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(redis::TlsCertificates {
+            client_tls,
+            root_cert,
+        }))
+    }
+
+    fn open_client(&self, url: String) -> Result<redis::Client, CommandError> {
+        let client = match self.tls_certificates()? {
+            Some(certs) => redis::Client::build_with_tls(url, certs),
+            None => redis::Client::open(url),
+        };
+        client.map_err(|e| CommandError::redis("Failed to create Redis client", e))
+    }
+
+    /// Builder of a client for the whole cluster, seeded with the profile's
+    /// node.
+    fn cluster_builder(&self) -> Result<ClusterClientBuilder, CommandError> {
+        let mut builder =
+            ClusterClient::builder(vec![self.to_url()]).response_timeout(self.response_timeout());
+        if let Some(certs) = self.tls_certificates()? {
+            builder = builder.certs(certs);
+        }
+        Ok(builder)
+    }
+
+    /// Client for one node. `host` is the node's real name; with TLS the
+    /// handshake uses `server_name` (or `host`) while the TCP connection
+    /// may go elsewhere, e.g. through a local SSH tunnel port. That other
+    /// address is returned alongside, for a `FixedResolver`.
+    fn node_client(
+        &self,
+        host: &str,
+        port: &str,
+        connect_to: Option<(&str, u16)>,
+    ) -> Result<(redis::Client, Option<(String, u16)>), CommandError> {
+        let port_num: u16 = port
+            .parse()
+            .map_err(|_| CommandError::invalid(format!("Invalid port: {}", port)))?;
+        let tls_name = self
+            .active_tls()
+            .and_then(|tls| tls.server_name.as_deref())
+            .filter(|name| !name.is_empty());
+
+        let (url, target) = match (self.enable_ssl, connect_to) {
+            // No TLS: nothing depends on the name, dial the target directly
+            (false, Some((to_host, to_port))) => {
+                (self.node_url(to_host, &to_port.to_string()), None)
+            }
+            (false, None) => (self.node_url(host, port), None),
+            (true, to) => {
+                let name = tls_name.unwrap_or(host);
+                let target = match to {
+                    Some((to_host, to_port)) => Some((to_host.to_string(), to_port)),
+                    None if name != host => Some((host.to_string(), port_num)),
+                    None => None,
+                };
+                (self.node_url(name, port), target)
+            }
+        };
+
+        Ok((self.open_client(url)?, target))
+    }
+
+    /// Opens a connection to one node, see `node_client`.
+    async fn connect_node(
+        &self,
+        host: &str,
+        port: &str,
+        connect_to: Option<(&str, u16)>,
+    ) -> Result<MultiplexedConnection, CommandError> {
+        let (client, target) = self.node_client(host, port, connect_to)?;
+        let mut settings =
+            redis::AsyncConnectionConfig::new().set_response_timeout(Some(self.response_timeout()));
+        if let Some((to_host, to_port)) = target {
+            settings = settings.set_dns_resolver(FixedResolver {
+                host: to_host,
+                port: to_port,
+            });
+        }
+
+        client
+            .get_multiplexed_async_connection_with_config(&settings)
+            .await
+            .map_err(|e| CommandError::redis(&format!("Connection to {}:{} failed", host, port), e))
+    }
+}
+
+/// Resolves every name to one fixed address. Lets the TLS layer verify the
+/// certificate against one name while the socket connects somewhere else.
+struct FixedResolver {
+    host: String,
+    port: u16,
+}
+
+impl redis::io::AsyncDNSResolver for FixedResolver {
+    fn resolve<'a, 'b: 'a>(
+        &'a self,
+        _host: &'b str,
+        _port: u16,
+    ) -> RedisFuture<'a, Box<dyn Iterator<Item = SocketAddr> + Send + 'a>> {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((self.host.as_str(), self.port))
+                .await?
+                .collect();
+            Ok(Box::new(addrs.into_iter()) as Box<dyn Iterator<Item = SocketAddr> + Send>)
+        })
+    }
+}
+
+fn url_auth(username: &Option<String>, password: &Option<String>) -> String {
+    match (username, password) {
+        (Some(u), Some(p)) if !u.is_empty() && !p.is_empty() => format!("{}:{}@", u, p),
+        (None, Some(p)) if !p.is_empty() => format!(":{}@", p),
+        (Some(u), None) if !u.is_empty() => format!("{}@", u),
+        _ => "".to_string(),
+    }
+}
+
+/// Which node a Sentinel profile is currently using.
+#[derive(Serialize, Clone)]
+pub struct SentinelStatus {
+    /// Current master as reported by Sentinel, `host:port`
+    pub master: String,
+    /// Node the pooled connections talk to (the master or a replica)
+    pub node: String,
+    pub role: String,
+}
+
+/// An open session as listed to the UI. Carries no credentials.
+#[derive(Serialize, Clone)]
+pub struct ConnectionInfo {
+    pub id: String,
+    pub host: String,
+    pub port: String,
+    pub mode: ConnectionMode,
+    pub tunneled: bool,
+}
+
+/// The node a session's commands go to, see `ConnectionManager::same_server`.
+struct NodeAddress {
+    host: String,
+    port: String,
+    /// The SSH bastion in front of it, which resolves `host` on its side
+    via: Option<String>,
+}
+
+impl NodeAddress {
+    /// Whether both name the same server. Different names are compared by
+    /// what they resolve to, except behind a bastion.
+    async fn same_node(&self, other: &NodeAddress) -> bool {
+        if self.port != other.port || self.via != other.via {
+            return false;
+        }
+        if self.host.eq_ignore_ascii_case(&other.host) {
+            return true;
+        }
+        if self.via.is_some() {
+            return false;
+        }
+        let Ok(port) = self.port.parse::<u16>() else {
+            return false;
+        };
+        let (Ok(mine), Ok(theirs)) = (
+            tokio::net::lookup_host((self.host.as_str(), port)).await,
+            tokio::net::lookup_host((other.host.as_str(), port)).await,
+        ) else {
+            return false;
+        };
+        let theirs: Vec<SocketAddr> = theirs.collect();
+        mine.into_iter().any(|addr| theirs.contains(&addr))
+    }
+}
+
+struct SentinelResolution {
+    status: SentinelStatus,
+    host: String,
+    port: String,
+    resolved_at: Instant,
+}
+
+/// A pooled connection, either to a single node or to a whole cluster.
+/// Cluster connections follow MOVED/ASK redirects on their own.
+#[derive(Clone)]
+pub enum RedisConnection {
+    Single(MultiplexedConnection),
+    Cluster(ClusterConnection),
+}
+
+impl RedisConnection {
+    pub fn is_cluster(&self) -> bool {
+        matches!(self, RedisConnection::Cluster(_))
+    }
+
+    /// Runs a pipeline whose commands may touch keys in different hash slots.
+    /// Cluster connections reject cross-slot pipelines, so there every command
+    /// is sent on its own (concurrently) and the replies are put back in order.
+    pub async fn query_pipeline<T: redis::FromRedisValue>(
+        &mut self,
+        pipe: &Pipeline,
+    ) -> redis::RedisResult<T> {
+        match self {
+            RedisConnection::Single(con) => pipe.query_async(con).await,
+            RedisConnection::Cluster(con) => {
+                let mut tasks = tokio::task::JoinSet::new();
+                for (idx, cmd) in pipe.cmd_iter().enumerate() {
+                    let cmd = cmd.clone();
+                    let mut con = con.clone();
+                    tasks.spawn(async move { (idx, cmd.query_async::<Value>(&mut con).await) });
+                }
+
+                let mut values = vec![Value::Nil; pipe.len()];
+                while let Some(joined) = tasks.join_next().await {
+                    let (idx, result) = joined.map_err(|e| {
+                        redis::RedisError::from((
+                            redis::ErrorKind::Client,
+                            "Pipeline task failed",
+                            e.to_string(),
+                        ))
+                    })?;
+                    values[idx] = result?;
+                }
+
+                Ok(redis::from_redis_value(Value::Array(values))?)
+            }
+        }
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            RedisConnection::Single(con) => con.req_packed_command(cmd),
+            RedisConnection::Cluster(con) => con.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        pipeline: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            RedisConnection::Single(con) => con.req_packed_commands(pipeline, offset, count),
+            RedisConnection::Cluster(con) => con.req_packed_commands(pipeline, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            RedisConnection::Single(con) => con.get_db(),
+            RedisConnection::Cluster(con) => con.get_db(),
+        }
+    }
+}
+
+/// Registry of open sessions keyed by connection id. Each session has its
+/// own lock, so a slow server never blocks commands against another one.
+#[derive(Default)]
+pub struct ConnectionManager {
+    sessions: Mutex<HashMap<String, Arc<Mutex<ConnectionState>>>>,
+    /// Opened RDB files, browsed read-only under the same kind of id
+    snapshots: Mutex<HashMap<String, Arc<RdbFile>>>,
+    next_id: AtomicU64,
+}
+
+/// What UrDis's own connections call themselves in CLIENT LIST.
+pub const DEFAULT_CLIENT_NAME: &str = "urdis";
+
+struct ConnectionState {
+    config: RedisConfig,
+    connections: HashMap<i64, RedisConnection>,
+    /// CLIENT SETNAME of every pooled connection
+    client_name: String,
+    /// CLIENT NO-EVICT of every pooled connection (Redis 7.0+)
+    no_evict: bool,
+    /// Direct connections to every cluster master, sorted by address.
+    /// Used to fan SCAN out across shards.
+    cluster_masters: Vec<(String, MultiplexedConnection)>,
+    sentinel: Option<SentinelResolution>,
+    tunnel: Option<SshTunnel>,
+    confirmations: Confirmations,
+    /// COMMAND INFO answers by command name, see `is_mutating_command` and
+    /// `first_key_index`
+    command_info: HashMap<String, Option<CommandInfo>>,
+}
+
+impl ConnectionState {
+    /// CLIENT commands every new pooled connection runs.
+    fn client_setup(&self) -> Vec<Cmd> {
+        let mut cmds = vec![redis::cmd("CLIENT")
+            .arg("SETNAME")
+            .arg(&self.client_name)
+            .clone()];
+        if self.no_evict {
+            cmds.push(redis::cmd("CLIENT").arg("NO-EVICT").arg("ON").clone());
+        }
+        cmds
+    }
+
+    /// Runs `cmd` on every pooled connection.
+    async fn apply_to_pool(&self, cmd: &Cmd, context: &str) -> Result<(), CommandError> {
+        let mut connections: Vec<RedisConnection> = self.connections.values().cloned().collect();
+        connections.extend(
+            self.cluster_masters
+                .iter()
+                .map(|(_, conn)| RedisConnection::Single(conn.clone())),
+        );
+        for mut con in connections {
+            cmd.exec_async(&mut con)
+                .await
+                .map_err(|e| CommandError::redis(context, e))?;
+        }
+        Ok(())
+    }
+
+    fn new(config: RedisConfig) -> Self {
+        ConnectionState {
+            config,
+            connections: HashMap::new(),
+            client_name: DEFAULT_CLIENT_NAME.to_string(),
+            no_evict: false,
+            cluster_masters: Vec::new(),
+            sentinel: None,
+            tunnel: None,
+            confirmations: Confirmations::default(),
+            command_info: HashMap::new(),
+        }
+    }
+}
+
+impl ConnectionManager {
+    /// Connects with `config` and registers the session. The returned id is
+    /// what every other command takes, so the config (and its password)
+    /// only crosses the IPC boundary once.
+    pub async fn open(&self, config: RedisConfig) -> Result<String, CommandError> {
+        let mut state = ConnectionState::new(config);
+        Self::connection_locked(&mut state, 0).await?;
+
+        let id = format!("conn-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        self.sessions
+            .lock()
+            .await
+            .insert(id.clone(), Arc::new(Mutex::new(state)));
+        Ok(id)
+    }
+
+    /// Registers an indexed RDB file and returns its id.
+    pub async fn open_rdb(&self, rdb: Arc<RdbFile>) -> String {
+        let id = format!("rdb-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        self.snapshots.lock().await.insert(id.clone(), rdb);
+        id
+    }
+
+    /// The RDB file behind `id`, if it is one rather than a server session.
+    pub async fn rdb_file(&self, id: &str) -> Option<Arc<RdbFile>> {
+        self.snapshots.lock().await.get(id).cloned()
+    }
+
+    /// Drops the session's pooled connections and closes its SSH tunnel.
+    /// An RDB file is simply forgotten.
+    pub async fn close(&self, id: &str) -> Result<(), CommandError> {
+        if self.snapshots.lock().await.remove(id).is_some() {
+            return Ok(());
+        }
+        self.sessions
+            .lock()
+            .await
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| {
+                CommandError::new(
+                    ErrorKind::UnknownConnection,
+                    format!("Unknown connection: {}", id),
+                )
+            })
+    }
+
+    pub async fn list(&self) -> Vec<ConnectionInfo> {
+        // Waiting on a session that is still connecting must not hold the
+        // map, which every other command needs
+        let sessions: Vec<(String, Arc<Mutex<ConnectionState>>)> = self
+            .sessions
+            .lock()
+            .await
+            .iter()
+            .map(|(id, session)| (id.clone(), session.clone()))
+            .collect();
+        let mut infos = Vec::with_capacity(sessions.len());
+        for (id, session) in sessions {
+            let state = session.lock().await;
+            infos.push(ConnectionInfo {
+                id,
+                host: state.config.host.clone(),
+                port: state.config.port.clone(),
+                mode: state.config.mode,
+                tunneled: state.config.ssh.is_some(),
+            });
+        }
+        infos.sort_by(|a, b| a.id.cmp(&b.id));
+        infos
+    }
+
+    async fn session(&self, id: &str) -> Result<Arc<Mutex<ConnectionState>>, CommandError> {
+        if let Some(session) = self.sessions.lock().await.get(id).cloned() {
+            return Ok(session);
+        }
+        if self.snapshots.lock().await.contains_key(id) {
+            return Err(CommandError::invalid(
+                "Not available for an RDB file, it is not a running server",
+            ));
+        }
+        Err(CommandError::new(
+            ErrorKind::UnknownConnection,
+            format!("Unknown connection: {}", id),
+        ))
+    }
+
+    /// Fails with a `read_only` error if the profile is read-only or the
+    /// session is on a replica. `name` is the operation shown in the message.
+    pub async fn ensure_writable(&self, id: &str, name: &str) -> Result<(), CommandError> {
+        if self.snapshots.lock().await.contains_key(id) {
+            return Err(safety::read_only_error(name));
+        }
+        let session = self.session(id).await?;
+        let state = session.lock().await;
+        if state.config.is_read_only() {
+            return Err(safety::read_only_error(name));
+        }
+        Ok(())
+    }
+
+    /// Lets `argv` through unless the profile lists it as dangerous, in which
+    /// case a valid `token` from an earlier attempt is required.
+    pub async fn confirm(
+        &self,
+        id: &str,
+        argv: &[Vec<u8>],
+        token: Option<&str>,
+    ) -> Result<(), CommandError> {
+        let session = self.session(id).await?;
+        let mut state = session.lock().await;
+        let Some(reason) = safety::confirmation_reason(&state.config.confirm_commands, argv) else {
+            return Ok(());
+        };
+        state.confirmations.check(&reason, argv, token)
+    }
+
+    /// `confirm` for deleting every key matching `pattern`.
+    pub async fn confirm_pattern_delete(
+        &self,
+        id: &str,
+        pattern: &str,
+        token: Option<&str>,
+    ) -> Result<(), CommandError> {
+        let session = self.session(id).await?;
+        let mut state = session.lock().await;
+        let Some(reason) = safety::pattern_delete_reason(&state.config.confirm_commands, pattern)
+        else {
+            return Ok(());
+        };
+        let argv = [b"UNLINK".to_vec(), pattern.as_bytes().to_vec()];
+        state.confirmations.check(&reason, &argv, token)
+    }
+
+    /// Whether `argv` changes data or server state, from its COMMAND INFO
+    /// flags (cached per session) and `safety::is_denied_admin_command`.
+    /// Subcommands are looked up on their own when the server knows them
+    /// (`config|set`), otherwise the container command's flags apply.
+    pub async fn is_mutating_command(
+        &self,
+        id: &str,
+        con: &mut RedisConnection,
+        argv: &[Vec<u8>],
+    ) -> Result<bool, CommandError> {
+        let Some((name, sub)) = safety::command_names(argv) else {
+            return Ok(false);
+        };
+        if safety::is_denied_admin_command(&name, sub.as_deref()) {
+            return Ok(true);
+        }
+        let session = self.session(id).await?;
+        let Some(info) = Self::cached_command_info(&session, con, name).await? else {
+            // Unknown to the server: it will reject it anyway
+            return Ok(false);
+        };
+        match sub {
+            Some(sub) if info.has_subcommands => Ok(Self::cached_command_info(&session, con, sub)
+                .await?
+                .map_or(info.mutating, |sub| sub.mutating)),
+            _ => Ok(info.mutating),
+        }
+    }
+
+    /// Where the first key of `argv` is, from its COMMAND INFO (cached per
+    /// session, a subcommand's own info when the server knows it). `None`
+    /// for keyless commands and commands the server does not know.
+    pub async fn first_key_index(
+        &self,
+        id: &str,
+        con: &mut RedisConnection,
+        argv: &[Vec<u8>],
+    ) -> Result<Option<usize>, CommandError> {
+        let Some((name, sub)) = safety::command_names(argv) else {
+            return Ok(None);
+        };
+        let session = self.session(id).await?;
+        let Some(info) = Self::cached_command_info(&session, con, name).await? else {
+            return Ok(None);
+        };
+        let info = match sub {
+            Some(sub) if info.has_subcommands => Self::cached_command_info(&session, con, sub)
+                .await?
+                .unwrap_or(info),
+            _ => info,
+        };
+        Ok((info.first_key > 0).then_some(info.first_key))
+    }
+
+    /// `safety::command_info` through the session's cache. The session is
+    /// not locked while COMMAND INFO runs.
+    async fn cached_command_info(
+        session: &Mutex<ConnectionState>,
+        con: &mut RedisConnection,
+        name: String,
+    ) -> Result<Option<CommandInfo>, CommandError> {
+        if let Some(info) = session.lock().await.command_info.get(&name) {
+            return Ok(*info);
+        }
+        let info = safety::command_info(con, &name).await?;
+        session.lock().await.command_info.insert(name, info);
+        Ok(info)
+    }
+
+    pub async fn is_read_only(&self, id: &str) -> Result<bool, CommandError> {
+        if self.snapshots.lock().await.contains_key(id) {
+            return Ok(true);
+        }
+        let session = self.session(id).await?;
+        let state = session.lock().await;
+        Ok(state.config.is_read_only())
+    }
+
+    pub async fn get_connection(&self, id: &str, db: i64) -> Result<RedisConnection, CommandError> {
+        let session = self.session(id).await?;
+        let mut state = session.lock().await;
+        Self::connection_locked(&mut state, db).await
+    }
+
+    /// Client for the node behind a session, for connections that leave the
+    /// pool because they stop answering normal commands (MONITOR, ...).
+    pub async fn dedicated_client(
+        &self,
+        id: &str,
+        purpose: &str,
+    ) -> Result<redis::Client, CommandError> {
+        let session = self.session(id).await?;
+        let mut state = session.lock().await;
+        // Resolves the Sentinel master and starts the SSH tunnel if needed
+        Self::connection_locked(&mut state, 0).await?;
+
+        let config = &state.config;
+        if config.mode == ConnectionMode::Cluster {
+            return Err(CommandError::invalid(format!(
+                "{} works on a single node, connect to one cluster node directly",
+                purpose
+            )));
+        }
+        let (host, port, connect_to) = match (&state.sentinel, &state.tunnel) {
+            (Some(resolved), _) => (resolved.host.clone(), resolved.port.clone(), None),
+            (None, Some(tunnel)) => (
+                config.host.clone(),
+                config.port.clone(),
+                Some(("127.0.0.1", tunnel.local_port)),
+            ),
+            (None, None) => (config.host.clone(), config.port.clone(), None),
+        };
+
+        let (client, target) = config.node_client(&host, &port, connect_to)?;
+        // Dedicated connections cannot take a custom resolver
+        if target.is_some() {
+            return Err(CommandError::invalid(format!(
+                "{} is not supported with a TLS server name or over SSH with TLS",
+                purpose
+            )));
+        }
+        Ok(client)
+    }
+
+    /// The cluster counterpart of `dedicated_client`, `None` when the
+    /// session is not in cluster mode. A builder, so that the caller can
+    /// still set up push messages.
+    pub async fn dedicated_cluster_builder(
+        &self,
+        id: &str,
+    ) -> Result<Option<ClusterClientBuilder>, CommandError> {
+        let session = self.session(id).await?;
+        let state = session.lock().await;
+        if state.config.mode != ConnectionMode::Cluster {
+            return Ok(None);
+        }
+        state.config.cluster_builder().map(Some)
+    }
+
+    /// Connections a keyspace SCAN has to visit: the DB connection for a
+    /// single node, or one connection per master in cluster mode. A scan
+    /// starting over (`refresh`) re-reads the cluster topology, later pages
+    /// keep the master list their cursor indexes into.
+    pub async fn get_scan_connections(
+        &self,
+        id: &str,
+        db: i64,
+        refresh: bool,
+    ) -> Result<Vec<RedisConnection>, CommandError> {
+        let nodes = self.node_connections(id, db, refresh).await?;
+        Ok(nodes.into_iter().map(|(_, con)| con).collect())
+    }
+
+    /// Like `get_scan_connections`, with each master's address and the
+    /// topology always re-read. For per-node state that cluster routing
+    /// would send to a random node (CLIENT LIST, CLIENT KILL, ...). The
+    /// address is `None` outside cluster mode.
+    pub async fn get_node_connections(
+        &self,
+        id: &str,
+        db: i64,
+    ) -> Result<Vec<(Option<String>, RedisConnection)>, CommandError> {
+        self.node_connections(id, db, true).await
+    }
+
+    async fn node_connections(
+        &self,
+        id: &str,
+        db: i64,
+        refresh: bool,
+    ) -> Result<Vec<(Option<String>, RedisConnection)>, CommandError> {
+        let session = self.session(id).await?;
+        let mut state = session.lock().await;
+        let con = Self::connection_locked(&mut state, db).await?;
+
+        let RedisConnection::Cluster(mut cluster) = con else {
+            return Ok(vec![(None, con)]);
+        };
+
+        // Failovers, resharding and added masters change the master set.
+        // Connections to masters that are still there are kept.
+        if refresh || state.cluster_masters.is_empty() {
+            let nodes: String = redis::cmd("CLUSTER")
+                .arg("NODES")
+                .query_async(&mut cluster)
+                .await
+                .map_err(|e| CommandError::redis("CLUSTER NODES error", e))?;
+
+            let mut known: HashMap<String, MultiplexedConnection> =
+                std::mem::take(&mut state.cluster_masters)
+                    .into_iter()
+                    .collect();
+            let mut masters = Vec::new();
+            for (host, port) in parse_cluster_masters(&nodes) {
+                let addr = format!("{}:{}", host, port);
+                let conn = match known.remove(&addr) {
+                    Some(conn) => conn,
+                    None => {
+                        let mut conn = state.config.connect_node(&host, &port, None).await?;
+                        for cmd in state.client_setup() {
+                            let _ = cmd.exec_async(&mut conn).await;
+                        }
+                        conn
+                    }
+                };
+                masters.push((addr, conn));
+            }
+            masters.sort_by(|a, b| a.0.cmp(&b.0));
+            state.cluster_masters = masters;
+        }
+
+        Ok(state
+            .cluster_masters
+            .iter()
+            .map(|(addr, conn)| (Some(addr.clone()), RedisConnection::Single(conn.clone())))
+            .collect())
+    }
+
+    /// Renames every pooled connection of a session, and the ones it opens
+    /// from now on.
+    pub async fn set_client_name(&self, id: &str, name: &str) -> Result<(), CommandError> {
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace()) {
+            return Err(CommandError::invalid(
+                "Client names cannot be empty or contain spaces",
+            ));
+        }
+        let session = self.session(id).await?;
+        let mut state = session.lock().await;
+        let cmd = redis::cmd("CLIENT").arg("SETNAME").arg(name).clone();
+        state.apply_to_pool(&cmd, "CLIENT SETNAME error").await?;
+        state.client_name = name.to_string();
+        Ok(())
+    }
+
+    /// CLIENT NO-EVICT for every pooled connection of a session, and the
+    /// ones it opens from now on, so UrDis stays usable under memory
+    /// pressure.
+    pub async fn set_no_evict(&self, id: &str, enabled: bool) -> Result<(), CommandError> {
+        let session = self.session(id).await?;
+        let mut state = session.lock().await;
+        let cmd = redis::cmd("CLIENT")
+            .arg("NO-EVICT")
+            .arg(if enabled { "ON" } else { "OFF" })
+            .clone();
+        state.apply_to_pool(&cmd, "CLIENT NO-EVICT error").await?;
+        state.no_evict = enabled;
+        Ok(())
+    }
+
+    pub async fn client_name(&self, id: &str) -> Result<String, CommandError> {
+        let session = self.session(id).await?;
+        let state = session.lock().await;
+        Ok(state.client_name.clone())
+    }
+
+    async fn connection_locked(
+        state: &mut ConnectionState,
+        db: i64,
+    ) -> Result<RedisConnection, CommandError> {
+        let config = state.config.clone();
+
+        if config.ssh.is_some() && config.mode != ConnectionMode::Standalone {
+            return Err(CommandError::invalid(
+                "SSH tunnels are only supported for standalone connections",
+            ));
+        }
+
+        if config.mode == ConnectionMode::Sentinel {
+            Self::refresh_sentinel(state, &config).await?;
+        }
+
+        // The bastion dropped: the pooled connections went through the
+        // tunnel, so reconnect all of them over a new one
+        if state.tunnel.as_ref().is_some_and(|t| !t.is_alive()) {
+            state.tunnel = None;
+            state.connections.clear();
+        }
+
+        if config.mode == ConnectionMode::Cluster && db != 0 {
+            return Err(CommandError::invalid(format!(
+                "Cluster mode only supports database 0 (requested DB {})",
+                db
+            )));
+        }
+
+        if let Some(conn) = state.connections.get(&db) {
+            return Ok(conn.clone());
+        }
+
+        let mut conn = match config.mode {
+            ConnectionMode::Standalone | ConnectionMode::Sentinel => {
+                // Create new connection for this DB
+                let mut conn = match (&state.sentinel, &config.ssh) {
+                    (Some(resolved), _) => {
+                        let (host, port) = (resolved.host.clone(), resolved.port.clone());
+                        config.connect_node(&host, &port, None).await?
+                    }
+                    (None, Some(ssh)) => {
+                        let local_port = Self::ensure_tunnel(state, ssh, &config).await?;
+                        config
+                            .connect_node(
+                                &config.host,
+                                &config.port,
+                                Some(("127.0.0.1", local_port)),
+                            )
+                            .await?
+                    }
+                    (None, None) => {
+                        config
+                            .connect_node(&config.host, &config.port, None)
+                            .await?
+                    }
+                };
+
+                // SELECT the DB immediately for this connection
+                let _: () = redis::cmd("SELECT")
+                    .arg(db)
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(|e| CommandError::redis(&format!("Failed to select DB {}", db), e))?;
+
+                RedisConnection::Single(conn)
+            }
+            ConnectionMode::Cluster => {
+                let client = config
+                    .cluster_builder()?
+                    .build()
+                    .map_err(|e| CommandError::redis("Failed to create cluster client", e))?;
+
+                let conn = client
+                    .get_async_connection()
+                    .await
+                    .map_err(|e| CommandError::redis("Cluster connection failed", e))?;
+
+                RedisConnection::Cluster(conn)
+            }
+        };
+
+        // Best effort, ACLs may deny CLIENT
+        for cmd in state.client_setup() {
+            let _ = cmd.exec_async(&mut conn).await;
+        }
+
+        state.connections.insert(db, conn.clone());
+        Ok(conn)
+    }
+
+    /// Starts the SSH port forward to the profile's host on first use and
+    /// returns its local port.
+    async fn ensure_tunnel(
+        state: &mut ConnectionState,
+        ssh: &SshConfig,
+        config: &RedisConfig,
+    ) -> Result<u16, CommandError> {
+        if let Some(tunnel) = &state.tunnel {
+            return Ok(tunnel.local_port);
+        }
+
+        let remote_port: u16 = config
+            .port
+            .parse()
+            .map_err(|_| CommandError::invalid(format!("Invalid port: {}", config.port)))?;
+        let ssh = ssh.clone();
+        let remote_host = config.host.clone();
+        let tunnel =
+            tokio::task::spawn_blocking(move || SshTunnel::open(&ssh, &remote_host, remote_port))
+                .await
+                .map_err(|e| {
+                    CommandError::new(
+                        ErrorKind::Internal,
+                        format!("SSH tunnel task failed: {}", e),
+                    )
+                })?
+                .map_err(|e| CommandError::new(ErrorKind::Ssh, e))?;
+
+        let local_port = tunnel.local_port;
+        state.tunnel = Some(tunnel);
+        Ok(local_port)
+    }
+
+    /// The single node behind a session, `None` in cluster mode.
+    async fn node_address(&self, id: &str) -> Result<Option<NodeAddress>, CommandError> {
+        let session = self.session(id).await?;
+        let mut state = session.lock().await;
+        // Resolves the Sentinel node
+        Self::connection_locked(&mut state, 0).await?;
+
+        let config = &state.config;
+        if config.mode == ConnectionMode::Cluster {
+            return Ok(None);
+        }
+        let (host, port) = match &state.sentinel {
+            Some(resolved) => (resolved.host.clone(), resolved.port.clone()),
+            None => (config.host.clone(), config.port.clone()),
+        };
+        Ok(Some(NodeAddress {
+            host,
+            port,
+            via: config
+                .ssh
+                .as_ref()
+                .map(|ssh| format!("{}@{}:{}", ssh.username, ssh.host, ssh.port)),
+        }))
+    }
+
+    /// Whether two sessions talk to the same server, so that COPY ... DB
+    /// and MOVE can take keys from one to the other. When in doubt they
+    /// count as different servers.
+    pub async fn same_server(&self, a: &str, b: &str) -> Result<bool, CommandError> {
+        if a == b {
+            return Ok(true);
+        }
+        match (self.node_address(a).await?, self.node_address(b).await?) {
+            (Some(a), Some(b)) => Ok(a.same_node(&b).await),
+            _ => Ok(false),
+        }
+    }
+
+    /// Reports the node a Sentinel profile resolves to right now.
+    pub async fn sentinel_status(&self, id: &str) -> Result<SentinelStatus, CommandError> {
+        let session = self.session(id).await?;
+        let mut state = session.lock().await;
+        Self::connection_locked(&mut state, 0).await?;
+
+        state
+            .sentinel
+            .as_ref()
+            .map(|resolved| resolved.status.clone())
+            .ok_or_else(|| CommandError::invalid("Connection is not in Sentinel mode"))
+    }
+
+    /// Asks Sentinel for the current master (or a replica) once the previous
+    /// answer is older than `SENTINEL_RECHECK_INTERVAL`. After a failover the
+    /// node changes and every pooled connection is dropped, so the next
+    /// command reconnects to the new master.
+    async fn refresh_sentinel(
+        state: &mut ConnectionState,
+        config: &RedisConfig,
+    ) -> Result<(), CommandError> {
+        if let Some(resolved) = &state.sentinel {
+            if resolved.resolved_at.elapsed() < SENTINEL_RECHECK_INTERVAL {
+                return Ok(());
+            }
+        }
+
+        let sentinel = config
+            .sentinel
+            .as_ref()
+            .ok_or_else(|| CommandError::invalid("Sentinel mode requires sentinel settings"))?;
+
+        let mut last_error = CommandError::invalid("No sentinel addresses configured");
+        for address in &sentinel.addresses {
+            match query_sentinel(config, sentinel, address).await {
+                Ok(resolution) => {
+                    let node_changed = state
+                        .sentinel
+                        .as_ref()
+                        .is_none_or(|old| old.status.node != resolution.status.node);
+                    if node_changed {
+                        state.connections.clear();
+                    }
+                    state.sentinel = Some(resolution);
+                    return Ok(());
+                }
+                Err(e) => last_error = e,
+            }
+        }
+
+        Err(last_error.context("Failed to resolve master via Sentinel"))
+    }
+}
+
+async fn query_sentinel(
+    config: &RedisConfig,
+    sentinel: &SentinelConfig,
+    address: &str,
+) -> Result<SentinelResolution, CommandError> {
+    let client = config
+        .open_client(config.sentinel_url(sentinel, address))
+        .map_err(|e| e.context(address))?;
+    let mut con = tokio::time::timeout(
+        SENTINEL_CONNECT_TIMEOUT,
+        client.get_multiplexed_async_connection(),
+    )
+    .await
+    .map_err(|_| {
+        CommandError::new(
+            ErrorKind::Timeout,
+            format!("{}: connection timed out", address),
+        )
+    })?
+    .map_err(|e| CommandError::redis(address, e))?;
+
+    let master: Option<(String, String)> = redis::cmd("SENTINEL")
+        .arg("GET-MASTER-ADDR-BY-NAME")
+        .arg(&sentinel.master_name)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis(address, e))?;
+
+    let (master_host, master_port) = master.ok_or_else(|| {
+        CommandError::invalid(format!(
+            "{}: unknown master name '{}'",
+            address, sentinel.master_name
+        ))
+    })?;
+    let master = format!("{}:{}", master_host, master_port);
+
+    if !sentinel.use_replica {
+        return Ok(SentinelResolution {
+            status: SentinelStatus {
+                node: master.clone(),
+                master,
+                role: "master".to_string(),
+            },
+            host: master_host,
+            port: master_port,
+            resolved_at: Instant::now(),
+        });
+    }
+
+    // SENTINEL REPLICAS needs Redis 5+, older sentinels only know SLAVES
+    let replicas: Vec<HashMap<String, String>> = redis::cmd("SENTINEL")
+        .arg("SLAVES")
+        .arg(&sentinel.master_name)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis(address, e))?;
+
+    let replica = replicas
+        .iter()
+        .find(|r| {
+            r.get("flags").is_some_and(|flags| {
+                !flags
+                    .split(',')
+                    .any(|f| matches!(f, "s_down" | "o_down" | "disconnected"))
+            })
+        })
+        .and_then(|r| Some((r.get("ip")?.clone(), r.get("port")?.clone())))
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorKind::Connection,
+                format!(
+                    "{}: no healthy replica for '{}'",
+                    address, sentinel.master_name
+                ),
+            )
+        })?;
+
+    Ok(SentinelResolution {
+        status: SentinelStatus {
+            master,
+            node: format!("{}:{}", replica.0, replica.1),
+            role: "replica".to_string(),
+        },
+        host: replica.0,
+        port: replica.1,
+        resolved_at: Instant::now(),
+    })
+}
+
+/// Extracts reachable master addresses from CLUSTER NODES output.
+/// Line format: `<id> <ip:port@cport[,hostname]> <flags> <master> ...`
+fn parse_cluster_masters(nodes: &str) -> Vec<(String, String)> {
+    let mut masters = Vec::new();
+    for line in nodes.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 3 {
+            continue;
+        }
+
+        let flags: Vec<&str> = parts[2].split(',').collect();
+        if !flags.contains(&"master")
+            || flags
+                .iter()
+                .any(|f| matches!(*f, "fail" | "noaddr" | "handshake"))
+        {
+            continue;
+        }
+
+        let addr = parts[1].split('@').next().unwrap_or("");
+        if let Some((host, port)) = addr.rsplit_once(':') {
+            if !host.is_empty() && port != "0" {
+                masters.push((host.to_string(), port.to_string()));
+            }
+        }
+    }
+    masters
+}
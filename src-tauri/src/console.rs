@@ -0,0 +1,404 @@
+use crate::error::CommandError;
+use base64::{engine::general_purpose, Engine as _};
+use redis::Value;
+use serde::Serialize;
+
+/// Commands that would change the state of a pooled connection shared with
+/// the rest of the app, or never return on it.
+const UNSUPPORTED_COMMANDS: &[&str] = &[
+    "SELECT",
+    "MULTI",
+    "EXEC",
+    "DISCARD",
+    "WATCH",
+    "UNWATCH",
+    "MONITOR",
+    "SUBSCRIBE",
+    "PSUBSCRIBE",
+    "SSUBSCRIBE",
+    "UNSUBSCRIBE",
+    "PUNSUBSCRIBE",
+    "SUNSUBSCRIBE",
+    "QUIT",
+    "RESET",
+    "HELLO",
+    "AUTH",
+];
+
+/// Commands that block until data arrives. On the shared multiplexed
+/// connection they either stall every other request or hit the response
+/// timeout.
+const BLOCKING_COMMANDS: &[&str] = &[
+    "BLPOP",
+    "BRPOP",
+    "BRPOPLPUSH",
+    "BLMOVE",
+    "BLMPOP",
+    "BZPOPMIN",
+    "BZPOPMAX",
+    "BZMPOP",
+    "WAIT",
+    "WAITAOF",
+];
+
+/// CLIENT subcommands that change the calling connection.
+const CONNECTION_STATE_SUBCOMMANDS: &[&str] = &[
+    "REPLY", "SETNAME", "SETINFO", "TRACKING", "CACHING", "NO-EVICT", "NO-TOUCH",
+];
+
+/// A reply as shown in the console, mirroring the RESP2/RESP3 types.
+#[derive(Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum Reply {
+    Nil,
+    Int(i64),
+    /// Bulk string that is valid UTF-8
+    String(String),
+    /// Bulk string with arbitrary bytes, Base64 encoded
+    Binary(String),
+    Status(String),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    Verbatim {
+        format: String,
+        text: String,
+    },
+    Array(Vec<Reply>),
+    Set(Vec<Reply>),
+    Map(Vec<(Reply, Reply)>),
+    Attribute {
+        data: Box<Reply>,
+        attributes: Vec<(Reply, Reply)>,
+    },
+    Push {
+        kind: String,
+        data: Vec<Reply>,
+    },
+    Error {
+        code: String,
+        message: String,
+    },
+}
+
+impl From<Value> for Reply {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Nil => Reply::Nil,
+            Value::Int(n) => Reply::Int(n),
+            Value::BulkString(bytes) => match String::from_utf8(bytes) {
+                Ok(s) => Reply::String(s),
+                Err(e) => Reply::Binary(general_purpose::STANDARD.encode(e.into_bytes())),
+            },
+            Value::SimpleString(s) => Reply::Status(s),
+            Value::Okay => Reply::Status("OK".to_string()),
+            Value::Double(d) => Reply::Double(d),
+            Value::Boolean(b) => Reply::Boolean(b),
+            Value::BigNumber(n) => Reply::BigNumber(n.to_string()),
+            Value::VerbatimString { format, text } => Reply::Verbatim {
+                format: format.to_string(),
+                text,
+            },
+            Value::Array(items) => Reply::Array(items.into_iter().map(Reply::from).collect()),
+            Value::Set(items) => Reply::Set(items.into_iter().map(Reply::from).collect()),
+            Value::Map(pairs) => Reply::Map(reply_pairs(pairs)),
+            Value::Attribute { data, attributes } => Reply::Attribute {
+                data: Box::new(Reply::from(*data)),
+                attributes: reply_pairs(attributes),
+            },
+            Value::Push { kind, data } => Reply::Push {
+                kind: kind.to_string(),
+                data: data.into_iter().map(Reply::from).collect(),
+            },
+            Value::ServerError(err) => Reply::Error {
+                code: err.code().to_string(),
+                message: err.details().unwrap_or_default().to_string(),
+            },
+            other => Reply::Status(format!("{:?}", other)),
+        }
+    }
+}
+
+fn reply_pairs(pairs: Vec<(Value, Value)>) -> Vec<(Reply, Reply)> {
+    pairs
+        .into_iter()
+        .map(|(k, v)| (Reply::from(k), Reply::from(v)))
+        .collect()
+}
+
+/// Error replies are results in the console, not failures of the command.
+/// Anything without a server error code (I/O, timeouts) is passed on.
+pub fn error_reply(err: redis::RedisError) -> Result<Reply, CommandError> {
+    match err.code() {
+        Some(code) => Ok(Reply::Error {
+            code: code.to_string(),
+            message: err.detail().unwrap_or_default().to_string(),
+        }),
+        None => Err(CommandError::from(err)),
+    }
+}
+
+/// Rejects commands that cannot run on the pooled connection the console
+/// shares with the browser, exports and the INFO sampler.
+pub fn check_supported(args: &[Vec<u8>]) -> Result<(), CommandError> {
+    let Some((name, rest)) = args.split_first() else {
+        return Ok(());
+    };
+    let upper = String::from_utf8_lossy(name).to_ascii_uppercase();
+    let unsupported = |what: String| {
+        Err(CommandError::invalid(format!(
+            "{} is not supported in the console",
+            what
+        )))
+    };
+
+    if UNSUPPORTED_COMMANDS.contains(&upper.as_str()) || BLOCKING_COMMANDS.contains(&upper.as_str())
+    {
+        return unsupported(upper);
+    }
+    // Options come before STREAMS, keys named "block" after it
+    if (upper == "XREAD" || upper == "XREADGROUP")
+        && rest
+            .iter()
+            .take_while(|arg| !arg.eq_ignore_ascii_case(b"STREAMS"))
+            .any(|arg| arg.eq_ignore_ascii_case(b"BLOCK"))
+    {
+        return unsupported(format!("{} with BLOCK", upper));
+    }
+    if upper == "CLIENT" {
+        if let Some(sub) = rest.first() {
+            let sub = String::from_utf8_lossy(sub).to_ascii_uppercase();
+            if CONNECTION_STATE_SUBCOMMANDS.contains(&sub.as_str()) {
+                return unsupported(format!("CLIENT {}", sub));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits a line the way redis-cli does: whitespace separated arguments,
+/// double quotes with C-style escapes (`\n`, `\xff`, ...) and single quotes
+/// where only `\'` is special. A closing quote must end the argument.
+pub fn split_args(bytes: &[u8]) -> Result<Vec<Vec<u8>>, CommandError> {
+    let mut args = Vec::new();
+    let mut i = 0;
+
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            return Ok(args);
+        }
+
+        let mut current = Vec::new();
+        let mut in_double = false;
+        let mut in_single = false;
+
+        loop {
+            let Some(&c) = bytes.get(i) else {
+                if in_double || in_single {
+                    return Err(CommandError::invalid("Unbalanced quotes in command"));
+                }
+                break;
+            };
+
+            if in_double {
+                if c == b'\\' && i + 3 < bytes.len() && bytes[i + 1] == b'x' {
+                    if let Some(byte) = hex_byte(bytes[i + 2], bytes[i + 3]) {
+                        current.push(byte);
+                        i += 4;
+                        continue;
+                    }
+                }
+                if c == b'\\' && i + 1 < bytes.len() {
+                    current.push(match bytes[i + 1] {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 0x08,
+                        b'a' => 0x07,
+                        other => other,
+                    });
+                    i += 2;
+                    continue;
+                }
+                if c == b'"' {
+                    if bytes.get(i + 1).is_some_and(|n| !n.is_ascii_whitespace()) {
+                        return Err(CommandError::invalid(
+                            "Closing quote must be followed by a space",
+                        ));
+                    }
+                    i += 1;
+                    break;
+                }
+                current.push(c);
+            } else if in_single {
+                if c == b'\\' && bytes.get(i + 1) == Some(&b'\'') {
+                    current.push(b'\'');
+                    i += 2;
+                    continue;
+                }
+                if c == b'\'' {
+                    if bytes.get(i + 1).is_some_and(|n| !n.is_ascii_whitespace()) {
+                        return Err(CommandError::invalid(
+                            "Closing quote must be followed by a space",
+                        ));
+                    }
+                    i += 1;
+                    break;
+                }
+                current.push(c);
+            } else {
+                match c {
+                    b' ' | b'\n' | b'\r' | b'\t' => break,
+                    b'"' => in_double = true,
+                    b'\'' => in_single = true,
+                    _ => current.push(c),
+                }
+            }
+            i += 1;
+        }
+
+        args.push(current);
+    }
+}
+
+fn hex_byte(hi: u8, lo: u8) -> Option<u8> {
+    let digit = |c: u8| (c as char).to_digit(16);
+    Some((digit(hi)? * 16 + digit(lo)?) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::script_arg;
+    use crate::RedisBytes;
+
+    fn check(line: &str) -> Result<(), CommandError> {
+        check_supported(&split_args(line.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn rejects_blocking_commands() {
+        for line in [
+            "BLPOP list 0",
+            "brpop list 0",
+            "BRPOPLPUSH src dst 0",
+            "BLMOVE src dst LEFT RIGHT 0",
+            "BLMPOP 0 1 list LEFT",
+            "BZPOPMIN zset 0",
+            "BZPOPMAX zset 0",
+            "BZMPOP 0 1 zset MIN",
+            "WAIT 1 0",
+            "WAITAOF 1 0 0",
+        ] {
+            assert!(check(line).is_err(), "{} should be rejected", line);
+        }
+    }
+
+    #[test]
+    fn rejects_blocking_stream_reads() {
+        assert!(check("XREAD BLOCK 0 STREAMS s $").is_err());
+        assert!(check("XREAD COUNT 1 block 100 STREAMS s $").is_err());
+        assert!(check("XREADGROUP GROUP g c BLOCK 0 STREAMS s >").is_err());
+    }
+
+    #[test]
+    fn allows_non_blocking_stream_reads() {
+        assert!(check("XREAD COUNT 10 STREAMS s 0").is_ok());
+        assert!(check("XREADGROUP GROUP g c STREAMS s >").is_ok());
+        // A stream named "block"
+        assert!(check("XREAD STREAMS block 0").is_ok());
+    }
+
+    #[test]
+    fn rejects_connection_state_commands() {
+        for line in [
+            "CLIENT REPLY OFF",
+            "CLIENT REPLY SKIP",
+            "client setname me",
+            "CLIENT SETINFO LIB-NAME x",
+            "CLIENT TRACKING ON",
+            "CLIENT CACHING YES",
+            "CLIENT NO-EVICT ON",
+            "CLIENT NO-TOUCH ON",
+            "RESET",
+            "HELLO 3",
+            "SELECT 1",
+            "MULTI",
+            "SUBSCRIBE ch",
+            "MONITOR",
+        ] {
+            assert!(check(line).is_err(), "{} should be rejected", line);
+        }
+    }
+
+    #[test]
+    fn allows_other_commands() {
+        for line in [
+            "GET k",
+            "CLIENT LIST",
+            "CLIENT KILL ID 3",
+            "LPOP list",
+            "ZPOPMIN z",
+        ] {
+            assert!(check(line).is_ok(), "{} should be allowed", line);
+        }
+    }
+
+    fn split(line: &str) -> Vec<Vec<u8>> {
+        split_args(line.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn splits_plain_and_quoted_arguments() {
+        assert_eq!(split("  SET  k\tv \n"), [&b"SET"[..], b"k", b"v"]);
+        assert_eq!(
+            split(r#"SET "a key" 'a value'"#),
+            [&b"SET"[..], b"a key", b"a value"]
+        );
+        assert_eq!(split(r#"SET k """#), [&b"SET"[..], b"k", b""]);
+        assert_eq!(split("a\"b c\" d"), [&b"ab c"[..], b"d"]);
+        assert!(split("   ").is_empty());
+    }
+
+    #[test]
+    fn unescapes_double_quotes_only() {
+        assert_eq!(
+            split(r#""\x00\xFFz" "l1\nl2\t\"q\"\\""#),
+            [&b"\x00\xffz"[..], b"l1\nl2\t\"q\"\\"]
+        );
+        // An incomplete \x escape keeps the x
+        assert_eq!(split(r#""\x4""#), [&b"x4"[..]]);
+        assert_eq!(split(r"'it\'s' 'a\nb'"), [&b"it's"[..], b"a\\nb"]);
+    }
+
+    #[test]
+    fn rejects_malformed_quotes() {
+        for line in [
+            r#"SET k "open"#,
+            "SET k 'open",
+            r#"SET k "a\""#,
+            r#"SET k "a"b"#,
+            "SET k 'a'b",
+        ] {
+            assert!(split_args(line.as_bytes()).is_err(), "{} should fail", line);
+        }
+    }
+
+    #[test]
+    fn reads_back_export_script_arguments() {
+        let values = [
+            RedisBytes::Utf8("plain".to_string()),
+            RedisBytes::Utf8("quotes \" and \\ and 'single'".to_string()),
+            RedisBytes::Utf8("line\nbreak\r\ttab \u{1} ünïcode".to_string()),
+            RedisBytes::Utf8(String::new()),
+            RedisBytes::from(vec![0, 0xff, b'"', b'\\', b' ', b'x', 0x80]),
+        ];
+        let line: Vec<String> = values.iter().map(script_arg).collect();
+        let args = split(&line.join(" "));
+        let expected: Vec<Vec<u8>> = values.iter().map(|v| v.to_bytes().unwrap()).collect();
+        assert_eq!(args, expected);
+    }
+}
@@ -0,0 +1,312 @@
+//! Copying (or moving) keys to another database or server.
+//!
+//! Within one server COPY / MOVE do the work without the data leaving it.
+//! Otherwise keys travel as DUMP payloads; when the destination rejects
+//! them (an older server cannot read a newer RDB version) the copy falls
+//! back to reading every value and rebuilding it type by type.
+
+use crate::connection::RedisConnection;
+use crate::error::{is_unknown_command, CommandError};
+use crate::jobs::JobGuard;
+use crate::keyspace::{fetch_records, restore_commands, run_key_commands, KeyBatches};
+use futures_util::future::join_all;
+use redis::RedisResult;
+use serde::Serialize;
+use tauri::ipc::Channel;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyMethod {
+    Copy,
+    Move,
+    DumpRestore,
+    Rebuild,
+}
+
+pub struct CopyOptions {
+    /// Overwrite existing destination keys instead of skipping them
+    pub replace: bool,
+    /// Carry the remaining TTL over, otherwise copies are persistent
+    pub keep_ttl: bool,
+    /// Delete the source keys once copied (a migration)
+    pub delete_source: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CopyProgress {
+    pub copied: u64,
+    /// Keys that exist in the destination (without `replace`) or vanished
+    pub skipped: u64,
+    pub total: Option<u64>,
+    pub method: CopyMethod,
+}
+
+#[derive(Serialize)]
+pub struct CopySummary {
+    pub copied: u64,
+    pub skipped: u64,
+    pub cancelled: bool,
+    /// How the last batch was copied, after any fallback
+    pub method: CopyMethod,
+}
+
+/// RESTORE failures that mean the destination cannot read the payload.
+fn is_incompatible_payload(e: &redis::RedisError) -> bool {
+    let message = e.to_string();
+    message.contains("payload version") || message.contains("Bad data format")
+}
+
+/// Keys of `keys` that do not exist on `dst` yet.
+async fn missing_keys(dst: &mut RedisConnection, keys: Vec<Vec<u8>>) -> RedisResult<Vec<Vec<u8>>> {
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.cmd("EXISTS").arg(key);
+    }
+    let exists: Vec<bool> = dst.query_pipeline(&pipe).await?;
+    Ok(keys
+        .into_iter()
+        .zip(exists)
+        .filter_map(|(key, exists)| (!exists).then_some(key))
+        .collect())
+}
+
+/// Drops the TTL of freshly copied keys.
+async fn persist(dst: &mut RedisConnection, keys: &[Vec<u8>]) -> RedisResult<()> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+    let mut pipe = redis::pipe();
+    for key in keys {
+        pipe.cmd("PERSIST").arg(key).ignore();
+    }
+    dst.query_pipeline(&pipe).await
+}
+
+/// COPY or MOVE within one server. Returns the keys that were copied.
+async fn same_server_batch(
+    src: &mut RedisConnection,
+    dst: &mut RedisConnection,
+    dst_db: i64,
+    keys: Vec<Vec<u8>>,
+    method: CopyMethod,
+    options: &CopyOptions,
+) -> RedisResult<Vec<Vec<u8>>> {
+    let mut pipe = redis::pipe();
+    if method == CopyMethod::Move {
+        // MOVE never overwrites, clear the way first
+        if options.replace {
+            let mut del = redis::pipe();
+            for key in &keys {
+                del.cmd("DEL").arg(key).ignore();
+            }
+            dst.query_pipeline::<()>(&del).await?;
+        }
+        for key in &keys {
+            pipe.cmd("MOVE").arg(key).arg(dst_db);
+        }
+    } else {
+        for key in &keys {
+            pipe.cmd("COPY").arg(key).arg(key).arg("DB").arg(dst_db);
+            if options.replace {
+                pipe.arg("REPLACE");
+            }
+        }
+    }
+
+    let done: Vec<bool> = src.query_pipeline(&pipe).await?;
+    let copied: Vec<Vec<u8>> = keys
+        .into_iter()
+        .zip(done)
+        .filter_map(|(key, done)| done.then_some(key))
+        .collect();
+    if !options.keep_ttl {
+        persist(dst, &copied).await?;
+    }
+    Ok(copied)
+}
+
+/// DUMP on the source, RESTORE on the destination. Returns the copied keys
+/// and the keys whose payload the destination cannot read.
+async fn restore_batch(
+    src: &mut RedisConnection,
+    dst: &mut RedisConnection,
+    keys: Vec<Vec<u8>>,
+    options: &CopyOptions,
+) -> RedisResult<(Vec<Vec<u8>>, Vec<Vec<u8>>)> {
+    let keys = if options.replace {
+        keys
+    } else {
+        missing_keys(dst, keys).await?
+    };
+    if keys.is_empty() {
+        return Ok((keys, Vec::new()));
+    }
+
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.cmd("DUMP").arg(key).cmd("PTTL").arg(key);
+    }
+    let dumps: Vec<(Option<Vec<u8>>, i64)> = src.query_pipeline(&pipe).await?;
+
+    let mut restores = Vec::with_capacity(keys.len());
+    for (key, (payload, pttl)) in keys.into_iter().zip(dumps) {
+        // Gone since the SCAN
+        let Some(payload) = payload else {
+            continue;
+        };
+        let ttl = if options.keep_ttl && pttl > 0 {
+            pttl
+        } else {
+            0
+        };
+        let mut cmd = redis::cmd("RESTORE");
+        cmd.arg(&key).arg(ttl).arg(payload);
+        if options.replace {
+            cmd.arg("REPLACE");
+        }
+        restores.push((key, cmd));
+    }
+
+    // Sent one by one (the connection still pipelines them) so that every
+    // key gets its own reply: a pipeline fails as a whole on one error
+    let results = join_all(restores.iter().map(|(_, cmd)| {
+        let mut dst = dst.clone();
+        async move { cmd.query_async::<()>(&mut dst).await }
+    }))
+    .await;
+    let mut copied = Vec::with_capacity(restores.len());
+    let mut incompatible = Vec::new();
+    for ((key, _), result) in restores.into_iter().zip(results) {
+        match result {
+            Ok(()) => copied.push(key),
+            Err(e) if is_incompatible_payload(&e) => incompatible.push(key),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok((copied, incompatible))
+}
+
+/// Reads whole values and writes them back command by command.
+async fn rebuild_batch(
+    src: &mut RedisConnection,
+    dst: &mut RedisConnection,
+    keys: Vec<Vec<u8>>,
+    options: &CopyOptions,
+) -> Result<Vec<Vec<u8>>, CommandError> {
+    let keys = if options.replace {
+        keys
+    } else {
+        missing_keys(dst, keys)
+            .await
+            .map_err(|e| CommandError::redis("Pipeline EXISTS error", e))?
+    };
+
+    let mut copied = Vec::new();
+    let mut groups = Vec::new();
+    for mut record in fetch_records(src, keys).await? {
+        if !options.keep_ttl {
+            record.pttl = None;
+        }
+        let key = record.key.to_bytes()?;
+        groups.push(restore_commands(&record, &key)?);
+        copied.push(key);
+    }
+    run_key_commands(dst, groups).await?;
+    Ok(copied)
+}
+
+/// Copies every batch, switching to the next method when the server does
+/// not support the current one.
+pub async fn copy_keys(
+    mut batches: KeyBatches,
+    mut dst: RedisConnection,
+    dst_db: i64,
+    same_server: bool,
+    options: CopyOptions,
+    job: &JobGuard,
+    on_progress: &Channel<CopyProgress>,
+) -> Result<CopySummary, CommandError> {
+    let mut method = match (same_server, options.delete_source) {
+        (true, true) => CopyMethod::Move,
+        (true, false) => CopyMethod::Copy,
+        (false, _) => CopyMethod::DumpRestore,
+    };
+    let mut progress = CopyProgress {
+        copied: 0,
+        skipped: 0,
+        total: batches.total,
+        method,
+    };
+
+    let mut cancelled = false;
+    while let Some((mut src, keys)) = batches.next().await? {
+        if job.is_cancelled() {
+            cancelled = true;
+            break;
+        }
+        if keys.is_empty() {
+            continue;
+        }
+
+        let requested = keys.len() as u64;
+        let copied = loop {
+            let keys = keys.clone();
+            match method {
+                CopyMethod::Copy | CopyMethod::Move => {
+                    match same_server_batch(&mut src, &mut dst, dst_db, keys, method, &options)
+                        .await
+                    {
+                        Ok(copied) => break copied,
+                        // COPY needs Redis 6.2
+                        Err(e) if method == CopyMethod::Copy && is_unknown_command(&e) => {
+                            method = CopyMethod::DumpRestore
+                        }
+                        Err(e) => return Err(CommandError::redis("COPY/MOVE error", e)),
+                    }
+                }
+                CopyMethod::DumpRestore => {
+                    let (mut copied, incompatible) =
+                        restore_batch(&mut src, &mut dst, keys, &options)
+                            .await
+                            .map_err(|e| CommandError::redis("DUMP/RESTORE error", e))?;
+                    // Only the rejected keys are rebuilt, the later batches
+                    // are rebuilt right away
+                    if !incompatible.is_empty() {
+                        method = CopyMethod::Rebuild;
+                        copied.extend(
+                            rebuild_batch(&mut src, &mut dst, incompatible, &options).await?,
+                        );
+                    }
+                    break copied;
+                }
+                CopyMethod::Rebuild => {
+                    break rebuild_batch(&mut src, &mut dst, keys, &options).await?
+                }
+            }
+        };
+
+        // MOVE already removed them
+        if options.delete_source && method != CopyMethod::Move && !copied.is_empty() {
+            let mut pipe = redis::pipe();
+            for key in &copied {
+                pipe.cmd("UNLINK").arg(key).ignore();
+            }
+            src.query_pipeline::<()>(&pipe)
+                .await
+                .map_err(|e| CommandError::redis("UNLINK error", e))?;
+        }
+
+        progress.copied += copied.len() as u64;
+        progress.skipped += requested - copied.len() as u64;
+        progress.method = method;
+        // The UI may have gone away, the copy still completes
+        let _ = on_progress.send(progress.clone());
+    }
+
+    Ok(CopySummary {
+        copied: progress.copied,
+        skipped: progress.skipped,
+        cancelled,
+        method,
+    })
+}
@@ -0,0 +1,791 @@
+//! Decoding of serialized values for display, and re-encoding on save.
+//!
+//! A value is unwrapped layer by layer (compression first) until a
+//! structured format yields a tree. The codec chain is returned with the
+//! tree, outermost first, so a save can apply the same codecs in reverse.
+//!
+//! Trees are plain JSON. What JSON cannot express is wrapped in objects
+//! with `$` keys:
+//! - `{"$base64": "..."}` raw bytes (or a string that is not UTF-8)
+//! - `{"$map": [[key, value], ...]}` a map with non-string keys
+//! - `{"$tuple": [...]}`, `{"$set": [...]}`, `{"$frozenset": [...]}` Python
+//! - `{"$class": "...", ...}` PHP and Python objects
+//! - `{"$ext": type, "$base64": "..."}` MessagePack extension
+//! - `{"$tag": n, "$value": ...}` CBOR tag
+
+mod php;
+mod pickle;
+mod protobuf;
+
+use crate::error::{CommandError, ErrorKind};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::{Read, Write};
+
+pub use protobuf::ProtoSchema;
+
+/// Upper bound for decompressed data, so a corrupt length header or a
+/// compression bomb cannot exhaust memory.
+const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Nested compression layers tried during detection (gzip inside zstd...).
+const MAX_LAYERS: usize = 4;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    Gzip,
+    Zstd,
+    /// Raw snappy block, as written by `snappy.compress`
+    Snappy,
+    /// Snappy framing format (`sNaPpY` stream identifier)
+    SnappyFramed,
+    /// LZ4 frame format (magic `04 22 4D 18`)
+    Lz4,
+    /// LZ4 block prefixed with its uncompressed size (u32, little-endian)
+    Lz4Block,
+    Json,
+    Msgpack,
+    Cbor,
+    /// Typed with a descriptor set when one is given, raw wire format otherwise
+    Protobuf,
+    /// PHP `serialize()`
+    Php,
+    /// Python pickle, protocol 2 and above
+    Pickle,
+}
+
+impl Codec {
+    fn is_compression(self) -> bool {
+        matches!(
+            self,
+            Codec::Gzip
+                | Codec::Zstd
+                | Codec::Snappy
+                | Codec::SnappyFramed
+                | Codec::Lz4
+                | Codec::Lz4Block
+        )
+    }
+
+    /// Compression codecs recognized by their magic bytes.
+    fn magic(self) -> Option<&'static [u8]> {
+        match self {
+            Codec::Gzip => Some(&[0x1f, 0x8b]),
+            Codec::Zstd => Some(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Codec::SnappyFramed => Some(b"\xff\x06\x00\x00sNaPpY"),
+            Codec::Lz4 => Some(&[0x04, 0x22, 0x4d, 0x18]),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+            Codec::Snappy => "snappy",
+            Codec::SnappyFramed => "snappy (framed)",
+            Codec::Lz4 => "LZ4",
+            Codec::Lz4Block => "LZ4 (block)",
+            Codec::Json => "JSON",
+            Codec::Msgpack => "MessagePack",
+            Codec::Cbor => "CBOR",
+            Codec::Protobuf => "protobuf",
+            Codec::Php => "PHP serialize",
+            Codec::Pickle => "pickle",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeOptions {
+    /// Chain to apply instead of detecting one, outermost first
+    #[serde(default)]
+    pub codecs: Option<Vec<Codec>>,
+    #[serde(default)]
+    pub proto: Option<ProtoSchema>,
+}
+
+#[derive(Serialize)]
+pub struct Decoded {
+    /// Outermost first; pass it back unchanged to `encode`
+    pub codecs: Vec<Codec>,
+    pub value: Value,
+}
+
+/// Decodes `bytes` with the chain from `options`, or detects one. `None`
+/// when nothing matched: the value is plain text or opaque bytes.
+pub fn decode(bytes: &[u8], options: &DecodeOptions) -> Result<Option<Decoded>, CommandError> {
+    let schema = options.proto.as_ref().map(ProtoSchema::load).transpose()?;
+
+    if let Some(codecs) = &options.codecs {
+        check_chain(codecs)?;
+        return decode_chain(bytes, codecs, schema.as_ref()).map(Some);
+    }
+    Ok(detect(bytes, schema.as_ref(), 0))
+}
+
+/// Turns an edited tree back into bytes with the chain it was decoded with.
+pub fn encode(
+    codecs: &[Codec],
+    value: &Value,
+    proto: Option<&ProtoSchema>,
+) -> Result<Vec<u8>, CommandError> {
+    check_chain(codecs)?;
+    let schema = proto.map(ProtoSchema::load).transpose()?;
+
+    let (layers, format) = match codecs.split_last() {
+        Some((last, rest)) if !last.is_compression() => (rest, Some(*last)),
+        _ => (codecs, None),
+    };
+
+    let mut bytes = match format {
+        Some(codec) => encode_format(codec, value, schema.as_ref())
+            .map_err(|e| CommandError::invalid(format!("{} encode failed: {}", codec, e)))?,
+        None => value_bytes(value).ok_or_else(|| {
+            CommandError::invalid("Expected a string or {\"$base64\": ...} value")
+        })?,
+    };
+    for codec in layers.iter().rev() {
+        bytes = compress(*codec, &bytes)
+            .map_err(|e| CommandError::invalid(format!("{} compression failed: {}", codec, e)))?;
+    }
+    Ok(bytes)
+}
+
+/// Only the last codec of a chain may be a structured format.
+fn check_chain(codecs: &[Codec]) -> Result<(), CommandError> {
+    let misplaced = codecs
+        .iter()
+        .rev()
+        .skip(1)
+        .find(|codec| !codec.is_compression());
+    match misplaced {
+        Some(codec) => Err(CommandError::invalid(format!(
+            "{} can only be the last codec of a chain",
+            codec
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn decode_chain(
+    bytes: &[u8],
+    codecs: &[Codec],
+    schema: Option<&protobuf::Schema>,
+) -> Result<Decoded, CommandError> {
+    let failed = |codec: Codec, e: String| {
+        CommandError::new(ErrorKind::Parse, format!("{} decode failed: {}", codec, e))
+    };
+
+    let mut data = bytes.to_vec();
+    for &codec in codecs {
+        if codec.is_compression() {
+            data = decompress(codec, &data).map_err(|e| failed(codec, e))?;
+        } else {
+            let value = decode_format(codec, &data, schema).map_err(|e| failed(codec, e))?;
+            return Ok(Decoded {
+                codecs: codecs.to_vec(),
+                value,
+            });
+        }
+    }
+    Ok(Decoded {
+        codecs: codecs.to_vec(),
+        value: bytes_value(data),
+    })
+}
+
+fn detect(bytes: &[u8], schema: Option<&protobuf::Schema>, depth: usize) -> Option<Decoded> {
+    let wrap = |codec: Codec, inner: Option<Decoded>, data: Vec<u8>| {
+        let mut decoded = inner.unwrap_or(Decoded {
+            codecs: Vec::new(),
+            value: bytes_value(data),
+        });
+        decoded.codecs.insert(0, codec);
+        decoded
+    };
+
+    if depth < MAX_LAYERS {
+        for codec in [Codec::Gzip, Codec::Zstd, Codec::SnappyFramed, Codec::Lz4] {
+            if codec.magic().is_some_and(|m| bytes.starts_with(m)) {
+                if let Ok(data) = decompress(codec, bytes) {
+                    let inner = detect(&data, schema, depth + 1);
+                    return Some(wrap(codec, inner, data));
+                }
+            }
+        }
+    }
+
+    if let Some((codec, value)) = detect_format(bytes, schema) {
+        return Some(Decoded {
+            codecs: vec![codec],
+            value,
+        });
+    }
+
+    // Raw blocks have no magic: only trust them when they unwrap to a
+    // structured format
+    if depth < MAX_LAYERS {
+        for codec in [Codec::Snappy, Codec::Lz4Block] {
+            if let Ok(data) = decompress(codec, bytes) {
+                if let Some(inner) = detect(&data, schema, depth + 1) {
+                    return Some(wrap(codec, Some(inner), data));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn detect_format(bytes: &[u8], schema: Option<&protobuf::Schema>) -> Option<(Codec, Value)> {
+    let text = std::str::from_utf8(bytes).ok();
+
+    // Bare numbers and quoted strings are left as text
+    if text.is_some_and(|t| t.trim_start().starts_with(['{', '['])) {
+        if let Ok(value) = serde_json::from_slice(bytes) {
+            return Some((Codec::Json, value));
+        }
+    }
+    if php::looks_like(bytes) {
+        if let Ok(value) = php::decode(bytes) {
+            return Some((Codec::Php, value));
+        }
+    }
+    if pickle::looks_like(bytes) {
+        if let Ok(value) = pickle::decode(bytes) {
+            return Some((Codec::Pickle, value));
+        }
+    }
+    if let Some(schema) = schema {
+        if let Ok(value) = schema.decode(bytes) {
+            return Some((Codec::Protobuf, value));
+        }
+    }
+    if let Some(&first) = bytes.first() {
+        // Only containers: a lone integer or string byte is too easy to hit
+        if matches!(first, 0x80..=0x9f | 0xdc..=0xdf) {
+            if let Ok(value) = decode_msgpack(bytes) {
+                return Some((Codec::Msgpack, value));
+            }
+        }
+        if matches!(first, 0x80..=0xdb) {
+            if let Ok(value) = decode_cbor(bytes) {
+                return Some((Codec::Cbor, value));
+            }
+        }
+    }
+    // Printable text parses as protobuf surprisingly often
+    if text.is_none() {
+        if let Ok(value) = protobuf::decode_raw(bytes) {
+            return Some((Codec::Protobuf, value));
+        }
+    }
+    None
+}
+
+fn decode_format(
+    codec: Codec,
+    bytes: &[u8],
+    schema: Option<&protobuf::Schema>,
+) -> Result<Value, String> {
+    match codec {
+        Codec::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+        Codec::Msgpack => decode_msgpack(bytes),
+        Codec::Cbor => decode_cbor(bytes),
+        Codec::Protobuf => match schema {
+            Some(schema) => schema.decode(bytes),
+            None => protobuf::decode_raw(bytes),
+        },
+        Codec::Php => php::decode(bytes),
+        Codec::Pickle => pickle::decode(bytes),
+        _ => Err(format!("{} is not a structured format", codec)),
+    }
+}
+
+fn encode_format(
+    codec: Codec,
+    value: &Value,
+    schema: Option<&protobuf::Schema>,
+) -> Result<Vec<u8>, String> {
+    match codec {
+        Codec::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+        Codec::Msgpack => encode_msgpack(value),
+        Codec::Cbor => encode_cbor(value),
+        Codec::Protobuf => match schema {
+            Some(schema) => schema.encode(value),
+            None => protobuf::encode_raw(value),
+        },
+        Codec::Php => php::encode(value),
+        Codec::Pickle => pickle::encode(value),
+        _ => Err(format!("{} is not a structured format", codec)),
+    }
+}
+
+// ==================== Compression ====================
+
+fn read_limited(reader: impl Read) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    reader
+        .take(MAX_DECOMPRESSED_SIZE as u64 + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+    if data.len() > MAX_DECOMPRESSED_SIZE {
+        return Err("decompressed data is too large".to_string());
+    }
+    Ok(data)
+}
+
+fn decompress(codec: Codec, bytes: &[u8]) -> Result<Vec<u8>, String> {
+    match codec {
+        Codec::Gzip => read_limited(flate2::read::GzDecoder::new(bytes)),
+        Codec::Zstd => {
+            read_limited(zstd::stream::read::Decoder::new(bytes).map_err(|e| e.to_string())?)
+        }
+        Codec::SnappyFramed => read_limited(snap::read::FrameDecoder::new(bytes)),
+        Codec::Lz4 => read_limited(lz4_flex::frame::FrameDecoder::new(bytes)),
+        Codec::Snappy => {
+            let len = snap::raw::decompress_len(bytes).map_err(|e| e.to_string())?;
+            if len > MAX_DECOMPRESSED_SIZE {
+                return Err("decompressed data is too large".to_string());
+            }
+            snap::raw::Decoder::new()
+                .decompress_vec(bytes)
+                .map_err(|e| e.to_string())
+        }
+        Codec::Lz4Block => {
+            let len = bytes
+                .get(..4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                .ok_or("missing size prefix")?;
+            // LZ4 cannot expand a block by more than ~255x
+            if len > MAX_DECOMPRESSED_SIZE || len > bytes.len().saturating_mul(255) {
+                return Err("invalid size prefix".to_string());
+            }
+            lz4_flex::block::decompress_size_prepended(bytes).map_err(|e| e.to_string())
+        }
+        _ => Err(format!("{} is not a compression codec", codec)),
+    }
+}
+
+fn compress(codec: Codec, bytes: &[u8]) -> Result<Vec<u8>, String> {
+    match codec {
+        Codec::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(bytes).map_err(|e| e.to_string())?;
+            encoder.finish().map_err(|e| e.to_string())
+        }
+        Codec::Zstd => zstd::stream::encode_all(bytes, 0).map_err(|e| e.to_string()),
+        Codec::SnappyFramed => {
+            let mut encoder = snap::write::FrameEncoder::new(Vec::new());
+            encoder.write_all(bytes).map_err(|e| e.to_string())?;
+            encoder.into_inner().map_err(|e| e.to_string())
+        }
+        Codec::Lz4 => {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+            encoder.write_all(bytes).map_err(|e| e.to_string())?;
+            encoder.finish().map_err(|e| e.to_string())
+        }
+        Codec::Snappy => snap::raw::Encoder::new()
+            .compress_vec(bytes)
+            .map_err(|e| e.to_string()),
+        Codec::Lz4Block => Ok(lz4_flex::block::compress_prepend_size(bytes)),
+        _ => Err(format!("{} is not a compression codec", codec)),
+    }
+}
+
+// ==================== MessagePack / CBOR ====================
+
+fn decode_msgpack(bytes: &[u8]) -> Result<Value, String> {
+    let mut rest = bytes;
+    let value = rmpv::decode::read_value(&mut rest).map_err(|e| e.to_string())?;
+    if !rest.is_empty() {
+        return Err(format!("{} trailing bytes", rest.len()));
+    }
+    Ok(msgpack_to_tree(value))
+}
+
+fn msgpack_to_tree(value: rmpv::Value) -> Value {
+    use rmpv::Value as M;
+    match value {
+        M::Nil => Value::Null,
+        M::Boolean(b) => Value::Bool(b),
+        M::Integer(n) => match n.as_i64() {
+            Some(i) => Value::from(i),
+            None => Value::from(n.as_u64().unwrap_or_default()),
+        },
+        M::F32(f) => Value::from(f as f64),
+        M::F64(f) => Value::from(f),
+        M::String(s) => match s.as_str() {
+            Some(text) => Value::String(text.to_string()),
+            None => base64_value(s.as_bytes()),
+        },
+        M::Binary(b) => base64_value(&b),
+        M::Array(items) => Value::Array(items.into_iter().map(msgpack_to_tree).collect()),
+        M::Map(pairs) => map_value(
+            pairs
+                .into_iter()
+                .map(|(k, v)| (msgpack_to_tree(k), msgpack_to_tree(v)))
+                .collect(),
+        ),
+        M::Ext(kind, data) => {
+            let mut object = Map::new();
+            object.insert("$ext".to_string(), Value::from(kind));
+            object.insert(
+                "$base64".to_string(),
+                general_purpose::STANDARD.encode(data).into(),
+            );
+            Value::Object(object)
+        }
+    }
+}
+
+fn encode_msgpack(value: &Value) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    rmpv::encode::write_value(&mut out, &tree_to_msgpack(value)?).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+fn tree_to_msgpack(value: &Value) -> Result<rmpv::Value, String> {
+    use rmpv::Value as M;
+    Ok(match value {
+        Value::Null => M::Nil,
+        Value::Bool(b) => M::Boolean(*b),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => M::from(i),
+            (None, Some(u)) => M::from(u),
+            _ => M::F64(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => M::from(s.as_str()),
+        Value::Array(items) => M::Array(
+            items
+                .iter()
+                .map(tree_to_msgpack)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(object) => {
+            if let Some(kind) = object.get("$ext") {
+                let kind = kind
+                    .as_i64()
+                    .and_then(|k| i8::try_from(k).ok())
+                    .ok_or("$ext must be an integer between -128 and 127")?;
+                let data = object
+                    .get("$base64")
+                    .and_then(value_bytes)
+                    .ok_or("$ext needs a $base64 payload")?;
+                return Ok(M::Ext(kind, data));
+            }
+            if let Some(bytes) = special_bytes(object) {
+                return Ok(M::Binary(bytes?));
+            }
+            M::Map(
+                map_pairs(value)?
+                    .into_iter()
+                    .map(|(k, v)| Ok((tree_to_msgpack(&k)?, tree_to_msgpack(&v)?)))
+                    .collect::<Result<_, String>>()?,
+            )
+        }
+    })
+}
+
+fn decode_cbor(bytes: &[u8]) -> Result<Value, String> {
+    let mut rest = bytes;
+    let value: ciborium::Value = ciborium::de::from_reader(&mut rest).map_err(|e| e.to_string())?;
+    if !rest.is_empty() {
+        return Err(format!("{} trailing bytes", rest.len()));
+    }
+    cbor_to_tree(value)
+}
+
+fn cbor_to_tree(value: ciborium::Value) -> Result<Value, String> {
+    use ciborium::Value as C;
+    Ok(match value {
+        C::Null => Value::Null,
+        C::Bool(b) => Value::Bool(b),
+        C::Integer(n) => {
+            let n = i128::from(n);
+            match (i64::try_from(n), u64::try_from(n)) {
+                (Ok(i), _) => Value::from(i),
+                (_, Ok(u)) => Value::from(u),
+                _ => return Err(format!("integer {} out of range", n)),
+            }
+        }
+        C::Float(f) => Value::from(f),
+        C::Text(s) => Value::String(s),
+        C::Bytes(b) => base64_value(&b),
+        C::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(cbor_to_tree)
+                .collect::<Result<_, _>>()?,
+        ),
+        C::Map(pairs) => map_value(
+            pairs
+                .into_iter()
+                .map(|(k, v)| Ok((cbor_to_tree(k)?, cbor_to_tree(v)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        C::Tag(tag, inner) => {
+            let mut object = Map::new();
+            object.insert("$tag".to_string(), Value::from(tag));
+            object.insert("$value".to_string(), cbor_to_tree(*inner)?);
+            Value::Object(object)
+        }
+        other => return Err(format!("unsupported CBOR value {:?}", other)),
+    })
+}
+
+fn encode_cbor(value: &Value) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(&tree_to_cbor(value)?, &mut out).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+fn tree_to_cbor(value: &Value) -> Result<ciborium::Value, String> {
+    use ciborium::Value as C;
+    Ok(match value {
+        Value::Null => C::Null,
+        Value::Bool(b) => C::Bool(*b),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => C::from(i),
+            (None, Some(u)) => C::from(u),
+            _ => C::Float(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => C::Text(s.clone()),
+        Value::Array(items) => C::Array(items.iter().map(tree_to_cbor).collect::<Result<_, _>>()?),
+        Value::Object(object) => {
+            if let Some(tag) = object.get("$tag") {
+                let tag = tag.as_u64().ok_or("$tag must be a positive integer")?;
+                let inner = tree_to_cbor(object.get("$value").unwrap_or(&Value::Null))?;
+                return Ok(C::Tag(tag, Box::new(inner)));
+            }
+            if let Some(bytes) = special_bytes(object) {
+                return Ok(C::Bytes(bytes?));
+            }
+            C::Map(
+                map_pairs(value)?
+                    .into_iter()
+                    .map(|(k, v)| Ok((tree_to_cbor(&k)?, tree_to_cbor(&v)?)))
+                    .collect::<Result<_, String>>()?,
+            )
+        }
+    })
+}
+
+// ==================== Tree helpers ====================
+
+/// Text when the bytes are UTF-8, `{"$base64": ...}` otherwise.
+fn bytes_value(bytes: Vec<u8>) -> Value {
+    match String::from_utf8(bytes) {
+        Ok(s) => Value::String(s),
+        Err(e) => base64_value(e.as_bytes()),
+    }
+}
+
+fn base64_value(bytes: &[u8]) -> Value {
+    let mut object = Map::new();
+    object.insert(
+        "$base64".to_string(),
+        general_purpose::STANDARD.encode(bytes).into(),
+    );
+    Value::Object(object)
+}
+
+/// Inverse of `bytes_value`.
+fn value_bytes(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::String(s) => Some(s.as_bytes().to_vec()),
+        Value::Object(object) => special_bytes(object).and_then(Result::ok),
+        _ => None,
+    }
+}
+
+/// Bytes of a `{"$base64": ...}` object, `None` for any other object.
+fn special_bytes(object: &Map<String, Value>) -> Option<Result<Vec<u8>, String>> {
+    if object.len() != 1 {
+        return None;
+    }
+    let data = object.get("$base64")?;
+    Some(
+        data.as_str()
+            .ok_or_else(|| "$base64 must be a string".to_string())
+            .and_then(|s| {
+                general_purpose::STANDARD
+                    .decode(s)
+                    .map_err(|e| e.to_string())
+            }),
+    )
+}
+
+/// A JSON object when every key is a string, `{"$map": [[k, v], ...]}`
+/// otherwise.
+fn map_value(pairs: Vec<(Value, Value)>) -> Value {
+    if pairs.iter().all(|(k, _)| k.is_string()) {
+        let object = pairs
+            .into_iter()
+            .filter_map(|(k, v)| match k {
+                Value::String(k) => Some((k, v)),
+                _ => None,
+            })
+            .collect();
+        return Value::Object(object);
+    }
+
+    let mut object = Map::new();
+    object.insert(
+        "$map".to_string(),
+        Value::Array(
+            pairs
+                .into_iter()
+                .map(|(k, v)| Value::Array(vec![k, v]))
+                .collect(),
+        ),
+    );
+    Value::Object(object)
+}
+
+/// Inverse of `map_value`.
+fn map_pairs(value: &Value) -> Result<Vec<(Value, Value)>, String> {
+    let object = value.as_object().ok_or("expected an object")?;
+    match object.get("$map") {
+        Some(Value::Array(entries)) if object.len() == 1 => entries
+            .iter()
+            .map(|entry| match entry.as_array().map(Vec::as_slice) {
+                Some([k, v]) => Ok((k.clone(), v.clone())),
+                _ => Err("$map entries must be [key, value] pairs".to_string()),
+            })
+            .collect(),
+        _ => Ok(object
+            .iter()
+            .map(|(k, v)| (Value::String(k.clone()), v.clone()))
+            .collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn compressed(codecs: &[Codec], bytes: &[u8]) -> Vec<u8> {
+        codecs.iter().rev().fold(bytes.to_vec(), |data, &codec| {
+            compress(codec, &data).unwrap()
+        })
+    }
+
+    fn detected(bytes: &[u8]) -> Option<Decoded> {
+        decode(bytes, &DecodeOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn leaves_plain_text_alone() {
+        assert!(detected(b"hello world").is_none());
+        assert!(detected(b"42").is_none());
+        assert!(detected(b"{not json").is_none());
+    }
+
+    #[test]
+    fn detects_nested_layers() {
+        let json = br#"{"a":[1,2]}"#;
+        for codecs in [
+            vec![Codec::Gzip],
+            vec![Codec::Zstd, Codec::Gzip],
+            vec![Codec::Lz4, Codec::SnappyFramed],
+            vec![Codec::Snappy],
+            vec![Codec::Gzip, Codec::Lz4Block],
+        ] {
+            let decoded = detected(&compressed(&codecs, json)).unwrap();
+            let mut expected = codecs.clone();
+            expected.push(Codec::Json);
+            assert_eq!(decoded.codecs, expected);
+            assert_eq!(decoded.value, json!({"a": [1, 2]}));
+        }
+
+        // Formats below the compression
+        let pickle = b"\x80\x03K\x07.";
+        let decoded = detected(&compressed(&[Codec::Zstd], pickle)).unwrap();
+        assert_eq!(decoded.codecs, [Codec::Zstd, Codec::Pickle]);
+        let php = b"i:7;";
+        let decoded = detected(&compressed(&[Codec::Gzip], php)).unwrap();
+        assert_eq!(decoded.codecs, [Codec::Gzip, Codec::Php]);
+    }
+
+    #[test]
+    fn stops_after_max_layers() {
+        let json = br#"[1]"#;
+        let deepest = vec![Codec::Gzip; MAX_LAYERS];
+        let decoded = detected(&compressed(&deepest, json)).unwrap();
+        assert_eq!(decoded.codecs.len(), MAX_LAYERS + 1);
+        assert_eq!(decoded.value, json!([1]));
+
+        // One layer more is left compressed
+        let too_deep = vec![Codec::Gzip; MAX_LAYERS + 1];
+        let decoded = detected(&compressed(&too_deep, json)).unwrap();
+        assert_eq!(decoded.codecs, deepest);
+        assert!(decoded.value.get("$base64").is_some());
+    }
+
+    #[test]
+    fn compressed_text_stays_text() {
+        let decoded = detected(&compressed(&[Codec::Gzip], b"hello")).unwrap();
+        assert_eq!(decoded.codecs, [Codec::Gzip]);
+        assert_eq!(decoded.value, json!("hello"));
+    }
+
+    #[test]
+    fn applies_a_given_chain() {
+        let bytes = compressed(&[Codec::Gzip], b"i:7;");
+        let options = DecodeOptions {
+            codecs: Some(vec![Codec::Gzip, Codec::Php]),
+            proto: None,
+        };
+        let decoded = decode(&bytes, &options).unwrap().unwrap();
+        assert_eq!(decoded.value, json!(7));
+        assert_eq!(
+            encode(&decoded.codecs, &decoded.value, None).unwrap().len(),
+            bytes.len()
+        );
+
+        let wrong = DecodeOptions {
+            codecs: Some(vec![Codec::Zstd, Codec::Php]),
+            proto: None,
+        };
+        assert!(decode(&bytes, &wrong).is_err());
+        let misplaced = DecodeOptions {
+            codecs: Some(vec![Codec::Json, Codec::Gzip]),
+            proto: None,
+        };
+        assert!(decode(&bytes, &misplaced).is_err());
+    }
+
+    #[test]
+    fn round_trips_through_the_chain() {
+        let bytes = compressed(&[Codec::Zstd, Codec::Gzip], br#"{"a":1}"#);
+        let decoded = detected(&bytes).unwrap();
+        let encoded = encode(&decoded.codecs, &json!({"a": 2}), None).unwrap();
+        let again = detected(&encoded).unwrap();
+        assert_eq!(again.codecs, decoded.codecs);
+        assert_eq!(again.value, json!({"a": 2}));
+    }
+
+    #[test]
+    fn rejects_corrupt_compression() {
+        let mut bytes = compressed(&[Codec::Gzip], br#"{"a":1}"#);
+        bytes.truncate(bytes.len() - 6);
+        // The magic matches but the stream is cut off
+        let options = DecodeOptions {
+            codecs: Some(vec![Codec::Gzip]),
+            proto: None,
+        };
+        assert!(decode(&bytes, &options).is_err());
+        // A size prefix far beyond what the block can hold
+        assert!(decompress(Codec::Lz4Block, &[0xff, 0xff, 0xff, 0x00, 0x00]).is_err());
+    }
+}
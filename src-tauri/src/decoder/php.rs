@@ -0,0 +1,306 @@
+//! PHP `serialize()` format.
+//!
+//! Arrays with keys `0..n` become JSON arrays, other arrays objects (PHP
+//! turns numeric string keys into integers anyway, so nothing is lost).
+//! Objects keep their class in `"$class"`. References (`r:`, `R:`) and
+//! custom serialization (`C:`) are not supported.
+
+use super::{bytes_value, special_bytes, value_bytes};
+use serde_json::{Map, Number, Value};
+
+/// Nested arrays/objects deeper than this are rejected.
+const MAX_DEPTH: usize = 128;
+
+pub fn looks_like(bytes: &[u8]) -> bool {
+    matches!(
+        bytes.get(..2),
+        Some(b"a:" | b"O:" | b"s:" | b"i:" | b"d:" | b"b:" | b"N;")
+    )
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Value, String> {
+    let mut parser = Parser { bytes, pos: 0 };
+    let value = parser.value(0)?;
+    if parser.pos != bytes.len() {
+        return Err(format!("{} trailing bytes", bytes.len() - parser.pos));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        match self.bytes.get(self.pos) {
+            Some(&b) if b == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(format!(
+                "expected {:?} at offset {}",
+                expected as char, self.pos
+            )),
+        }
+    }
+
+    /// Text up to (and consuming) `end`.
+    fn until(&mut self, end: u8) -> Result<&str, String> {
+        let rest = &self.bytes[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&b| b == end)
+            .ok_or_else(|| format!("missing {:?}", end as char))?;
+        let text = std::str::from_utf8(&rest[..len]).map_err(|e| e.to_string())?;
+        self.pos += len + 1;
+        Ok(text)
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, end: u8) -> Result<T, String> {
+        let text = self.until(end)?;
+        text.parse()
+            .map_err(|_| format!("invalid number {:?}", text))
+    }
+
+    /// `<len>:"<bytes>"`, shared by strings and class names.
+    fn quoted(&mut self) -> Result<&[u8], String> {
+        let len: usize = self.number(b':')?;
+        self.expect(b'"')?;
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or("truncated string")?;
+        let data = &self.bytes[self.pos..end];
+        self.pos = end;
+        self.expect(b'"')?;
+        Ok(data)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err("nested too deeply".to_string());
+        }
+        let tag = *self.bytes.get(self.pos).ok_or("unexpected end")?;
+        self.pos += 1;
+        if tag == b'N' {
+            self.expect(b';')?;
+            return Ok(Value::Null);
+        }
+        self.expect(b':')?;
+
+        match tag {
+            b'b' => match self.until(b';')? {
+                "0" => Ok(Value::Bool(false)),
+                "1" => Ok(Value::Bool(true)),
+                other => Err(format!("invalid boolean {:?}", other)),
+            },
+            b'i' => Ok(Value::from(self.number::<i64>(b';')?)),
+            b'd' => {
+                let text = self.until(b';')?;
+                let f: f64 = match text {
+                    "INF" => f64::INFINITY,
+                    "-INF" => f64::NEG_INFINITY,
+                    "NAN" => f64::NAN,
+                    _ => text
+                        .parse()
+                        .map_err(|_| format!("invalid float {:?}", text))?,
+                };
+                // JSON has no infinities, keep PHP's spelling
+                Ok(Number::from_f64(f)
+                    .map(Value::Number)
+                    .unwrap_or_else(|| Value::String(text.to_string())))
+            }
+            b's' => {
+                let data = self.quoted()?.to_vec();
+                self.expect(b';')?;
+                Ok(bytes_value(data))
+            }
+            b'a' => {
+                let count: usize = self.number(b':')?;
+                self.members(count, depth, Map::new())
+            }
+            b'O' => {
+                let class =
+                    String::from_utf8(self.quoted()?.to_vec()).map_err(|e| e.to_string())?;
+                self.expect(b':')?;
+                let count: usize = self.number(b':')?;
+                let mut object = Map::new();
+                object.insert("$class".to_string(), Value::String(class));
+                self.members(count, depth, object)
+            }
+            other => Err(format!("unsupported type {:?}", other as char)),
+        }
+    }
+
+    /// `{key;value...}` of an array or object; `object` may already hold
+    /// the class name.
+    fn members(
+        &mut self,
+        count: usize,
+        depth: usize,
+        mut object: Map<String, Value>,
+    ) -> Result<Value, String> {
+        self.expect(b'{')?;
+        let is_array = object.is_empty();
+        let mut sequential = is_array;
+
+        for index in 0..count {
+            let key = match self.value(depth + 1)? {
+                Value::Number(n) => {
+                    sequential &= n.as_u64() == Some(index as u64);
+                    n.to_string()
+                }
+                Value::String(s) => {
+                    sequential = false;
+                    s
+                }
+                _ => return Err("array keys must be integers or UTF-8 strings".to_string()),
+            };
+            let value = self.value(depth + 1)?;
+            object.insert(key, value);
+        }
+        self.expect(b'}')?;
+
+        if sequential {
+            return Ok(Value::Array(object.into_iter().map(|(_, v)| v).collect()));
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+pub fn encode(value: &Value) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    write_value(value, &mut out)?;
+    Ok(out)
+}
+
+fn write_string(data: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(format!("s:{}:\"", data.len()).as_bytes());
+    out.extend_from_slice(data);
+    out.extend_from_slice(b"\";");
+}
+
+/// Integer-looking keys are written as integers, like PHP stores them.
+fn write_key(key: &str, out: &mut Vec<u8>) {
+    match key.parse::<i64>() {
+        Ok(n) if n.to_string() == key => out.extend_from_slice(format!("i:{};", n).as_bytes()),
+        _ => write_string(key.as_bytes(), out),
+    }
+}
+
+fn write_value(value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+    match value {
+        Value::Null => out.extend_from_slice(b"N;"),
+        Value::Bool(b) => out.extend_from_slice(if *b { b"b:1;" } else { b"b:0;" }),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => out.extend_from_slice(format!("i:{};", i).as_bytes()),
+            None => {
+                let f = n.as_f64().ok_or("number out of range")?;
+                out.extend_from_slice(format!("d:{};", f).as_bytes());
+            }
+        },
+        Value::String(s) => write_string(s.as_bytes(), out),
+        Value::Array(items) => {
+            out.extend_from_slice(format!("a:{}:{{", items.len()).as_bytes());
+            for (i, item) in items.iter().enumerate() {
+                out.extend_from_slice(format!("i:{};", i).as_bytes());
+                write_value(item, out)?;
+            }
+            out.push(b'}');
+        }
+        Value::Object(object) => {
+            if let Some(bytes) = special_bytes(object) {
+                write_string(&bytes?, out);
+                return Ok(());
+            }
+
+            let class = object.get("$class");
+            let members: Vec<_> = object.iter().filter(|(k, _)| *k != "$class").collect();
+            match class {
+                Some(class) => {
+                    let class = value_bytes(class).ok_or("$class must be a string")?;
+                    out.extend_from_slice(format!("O:{}:\"", class.len()).as_bytes());
+                    out.extend_from_slice(&class);
+                    out.extend_from_slice(format!("\":{}:{{", members.len()).as_bytes());
+                    // Property names are always strings
+                    for (key, value) in members {
+                        write_string(key.as_bytes(), out);
+                        write_value(value, out)?;
+                    }
+                }
+                None => {
+                    out.extend_from_slice(format!("a:{}:{{", members.len()).as_bytes());
+                    for (key, value) in members {
+                        write_key(key, out);
+                        write_value(value, out)?;
+                    }
+                }
+            }
+            out.push(b'}');
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const LIST: &[u8] = b"a:3:{i:0;s:3:\"foo\";i:1;d:1.5;i:2;N;}";
+    const MAP: &[u8] = b"a:2:{s:4:\"name\";s:3:\"bob\";s:2:\"ok\";b:1;}";
+    const OBJECT: &[u8] = b"O:5:\"Point\":2:{s:1:\"x\";i:1;s:1:\"y\";i:-2;}";
+
+    fn fixtures() -> [(&'static [u8], Value); 3] {
+        [
+            (LIST, json!(["foo", 1.5, null])),
+            (MAP, json!({"name": "bob", "ok": true})),
+            (OBJECT, json!({"$class": "Point", "x": 1, "y": -2})),
+        ]
+    }
+
+    #[test]
+    fn decodes_values() {
+        for (data, expected) in fixtures() {
+            assert!(looks_like(data));
+            assert_eq!(decode(data).unwrap(), expected);
+        }
+        // Not UTF-8, the length counts bytes
+        assert_eq!(
+            decode(b"s:2:\"\xff\xfe\";").unwrap(),
+            json!({"$base64": "//4="})
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        for (data, _) in fixtures() {
+            for len in 0..data.len() {
+                assert!(decode(&data[..len]).is_err(), "{:?}", &data[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_input() {
+        // Length longer than the string
+        assert!(decode(b"s:5:\"foo\";").is_err());
+        // Fewer members than announced
+        assert!(decode(b"a:2:{i:0;i:1;}").is_err());
+        assert!(decode(b"b:2;").is_err());
+        assert!(decode(b"i:1;i:2;").is_err());
+        // References are not supported
+        assert!(decode(b"a:1:{i:0;r:1;}").is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        for (data, expected) in fixtures() {
+            let encoded = encode(&expected).unwrap();
+            assert_eq!(encoded, data);
+        }
+    }
+}
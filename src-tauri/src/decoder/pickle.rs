@@ -0,0 +1,673 @@
+//! Python pickle, protocols 2 to 5.
+//!
+//! Objects are never instantiated: a `GLOBAL` followed by `REDUCE` or
+//! `NEWOBJ` becomes `{"$class": "module:qualname", "$args": [...]}`, and a
+//! `BUILD` adds `"$state"`. Memoized values are copied when they are
+//! fetched, so shared references come out as independent values.
+//!
+//! Saving always writes protocol 3, readable by any Python 3.
+
+use super::{base64_value, bytes_value, map_pairs, map_value, special_bytes, value_bytes};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+
+const MARK: u8 = b'(';
+const STOP: u8 = b'.';
+const POP: u8 = b'0';
+const POP_MARK: u8 = b'1';
+const DUP: u8 = b'2';
+const BININT: u8 = b'J';
+const BININT1: u8 = b'K';
+const BININT2: u8 = b'M';
+const NONE: u8 = b'N';
+const REDUCE: u8 = b'R';
+const BINSTRING: u8 = b'T';
+const SHORT_BINSTRING: u8 = b'U';
+const BINUNICODE: u8 = b'X';
+const APPEND: u8 = b'a';
+const BUILD: u8 = b'b';
+const GLOBAL: u8 = b'c';
+const DICT: u8 = b'd';
+const EMPTY_DICT: u8 = b'}';
+const APPENDS: u8 = b'e';
+const BINGET: u8 = b'h';
+const LONG_BINGET: u8 = b'j';
+const LIST: u8 = b'l';
+const EMPTY_LIST: u8 = b']';
+const BINPUT: u8 = b'q';
+const LONG_BINPUT: u8 = b'r';
+const SETITEM: u8 = b's';
+const TUPLE: u8 = b't';
+const EMPTY_TUPLE: u8 = b')';
+const SETITEMS: u8 = b'u';
+const BINFLOAT: u8 = b'G';
+const PROTO: u8 = 0x80;
+const NEWOBJ: u8 = 0x81;
+const TUPLE1: u8 = 0x85;
+const TUPLE2: u8 = 0x86;
+const TUPLE3: u8 = 0x87;
+const NEWTRUE: u8 = 0x88;
+const NEWFALSE: u8 = 0x89;
+const LONG1: u8 = 0x8a;
+const LONG4: u8 = 0x8b;
+const BINBYTES: u8 = b'B';
+const SHORT_BINBYTES: u8 = b'C';
+const SHORT_BINUNICODE: u8 = 0x8c;
+const BINUNICODE8: u8 = 0x8d;
+const BINBYTES8: u8 = 0x8e;
+const EMPTY_SET: u8 = 0x8f;
+const ADDITEMS: u8 = 0x90;
+const FROZENSET: u8 = 0x91;
+const STACK_GLOBAL: u8 = 0x93;
+const MEMOIZE: u8 = 0x94;
+const FRAME: u8 = 0x95;
+const BYTEARRAY8: u8 = 0x96;
+
+/// Protocol the encoder writes.
+const WRITE_PROTOCOL: u8 = 3;
+
+pub fn looks_like(bytes: &[u8]) -> bool {
+    matches!(bytes, [PROTO, 2..=5, .., STOP])
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Value, String> {
+    Machine {
+        bytes,
+        pos: 0,
+        stack: Vec::new(),
+        marks: Vec::new(),
+        memo: HashMap::new(),
+    }
+    .run()
+}
+
+struct Machine<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    stack: Vec<Value>,
+    /// Stack heights at each MARK
+    marks: Vec<usize>,
+    memo: HashMap<u64, Value>,
+}
+
+fn tagged(tag: &str, value: Value) -> Value {
+    let mut object = Map::new();
+    object.insert(tag.to_string(), value);
+    Value::Object(object)
+}
+
+impl Machine<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or("truncated pickle")?;
+        let data = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(data)
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u64, String> {
+        let data = self.take(len)?;
+        Ok(data
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &b| (acc << 8) | u64::from(b)))
+    }
+
+    fn line(&mut self) -> Result<String, String> {
+        let rest = &self.bytes[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or("missing newline")?;
+        let text = String::from_utf8(rest[..len].to_vec()).map_err(|e| e.to_string())?;
+        self.pos += len + 1;
+        Ok(text)
+    }
+
+    fn pop(&mut self) -> Result<Value, String> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "stack underflow".to_string())
+    }
+
+    fn top(&mut self) -> Result<&mut Value, String> {
+        self.stack
+            .last_mut()
+            .ok_or_else(|| "stack underflow".to_string())
+    }
+
+    /// Everything pushed since the last MARK.
+    fn pop_mark(&mut self) -> Result<Vec<Value>, String> {
+        let height = self.marks.pop().ok_or("missing MARK")?;
+        if height > self.stack.len() {
+            return Err("corrupt MARK".to_string());
+        }
+        Ok(self.stack.split_off(height))
+    }
+
+    fn long(&mut self, len: usize) -> Result<Value, String> {
+        let data = self.take(len)?;
+        // u64 above i64::MAX needs a ninth, zero byte for the sign
+        if let [low @ .., 0] = data {
+            if low.len() == 8 && low[7] & 0x80 != 0 {
+                let mut buf = [0; 8];
+                buf.copy_from_slice(low);
+                return Ok(Value::from(u64::from_le_bytes(buf)));
+            }
+        }
+        if data.len() > 8 {
+            return Err("integer too large".to_string());
+        }
+        // Little-endian two's complement
+        let mut buf = if data.last().is_some_and(|&b| b & 0x80 != 0) {
+            [0xff; 8]
+        } else {
+            [0; 8]
+        };
+        buf[..data.len()].copy_from_slice(data);
+        Ok(Value::from(i64::from_le_bytes(buf)))
+    }
+
+    fn run(mut self) -> Result<Value, String> {
+        loop {
+            let op = *self.bytes.get(self.pos).ok_or("missing STOP")?;
+            self.pos += 1;
+
+            match op {
+                PROTO => {
+                    self.take(1)?;
+                }
+                FRAME => {
+                    self.take(8)?;
+                }
+                STOP => {
+                    if self.pos != self.bytes.len() {
+                        return Err("trailing bytes after STOP".to_string());
+                    }
+                    return self.pop();
+                }
+                MARK => self.marks.push(self.stack.len()),
+                POP => {
+                    self.pop()?;
+                }
+                POP_MARK => {
+                    self.pop_mark()?;
+                }
+                DUP => {
+                    let top = self.top()?.clone();
+                    self.stack.push(top);
+                }
+                NONE => self.stack.push(Value::Null),
+                NEWTRUE => self.stack.push(Value::Bool(true)),
+                NEWFALSE => self.stack.push(Value::Bool(false)),
+                BININT => {
+                    let n = self.uint(4)? as u32 as i32;
+                    self.stack.push(Value::from(n));
+                }
+                BININT1 => {
+                    let n = self.uint(1)?;
+                    self.stack.push(Value::from(n));
+                }
+                BININT2 => {
+                    let n = self.uint(2)?;
+                    self.stack.push(Value::from(n));
+                }
+                LONG1 => {
+                    let len = self.uint(1)? as usize;
+                    let value = self.long(len)?;
+                    self.stack.push(value);
+                }
+                LONG4 => {
+                    let len = self.uint(4)? as usize;
+                    let value = self.long(len)?;
+                    self.stack.push(value);
+                }
+                BINFLOAT => {
+                    let data = self.take(8)?;
+                    let f = f64::from_be_bytes(data.try_into().map_err(|_| "truncated float")?);
+                    self.stack.push(
+                        Number::from_f64(f)
+                            .map(Value::Number)
+                            .unwrap_or(Value::Null),
+                    );
+                }
+                SHORT_BINUNICODE | BINUNICODE | BINUNICODE8 => {
+                    let len = match op {
+                        SHORT_BINUNICODE => self.uint(1)?,
+                        BINUNICODE => self.uint(4)?,
+                        _ => self.uint(8)?,
+                    } as usize;
+                    let data = self.take(len)?.to_vec();
+                    let text = String::from_utf8(data).map_err(|e| e.to_string())?;
+                    self.stack.push(Value::String(text));
+                }
+                // Python 2 `str`, shown like text when it is UTF-8
+                SHORT_BINSTRING | BINSTRING => {
+                    let len = if op == SHORT_BINSTRING {
+                        self.uint(1)?
+                    } else {
+                        self.uint(4)?
+                    } as usize;
+                    let data = self.take(len)?.to_vec();
+                    self.stack.push(bytes_value(data));
+                }
+                SHORT_BINBYTES | BINBYTES | BINBYTES8 | BYTEARRAY8 => {
+                    let len = match op {
+                        SHORT_BINBYTES => self.uint(1)?,
+                        BINBYTES => self.uint(4)?,
+                        _ => self.uint(8)?,
+                    } as usize;
+                    let data = self.take(len)?.to_vec();
+                    self.stack.push(base64_value(&data));
+                }
+                EMPTY_LIST => self.stack.push(Value::Array(Vec::new())),
+                LIST => {
+                    let items = self.pop_mark()?;
+                    self.stack.push(Value::Array(items));
+                }
+                APPEND => {
+                    let item = self.pop()?;
+                    match self.top()? {
+                        Value::Array(items) => items.push(item),
+                        _ => return Err("APPEND to a non-list".to_string()),
+                    }
+                }
+                APPENDS => {
+                    let new_items = self.pop_mark()?;
+                    match self.top()? {
+                        Value::Array(items) => items.extend(new_items),
+                        _ => return Err("APPENDS to a non-list".to_string()),
+                    }
+                }
+                EMPTY_TUPLE => self.stack.push(tagged("$tuple", Value::Array(Vec::new()))),
+                TUPLE1 | TUPLE2 | TUPLE3 => {
+                    let n = usize::from(op - TUPLE1 + 1);
+                    if self.stack.len() < n {
+                        return Err("stack underflow".to_string());
+                    }
+                    let items = self.stack.split_off(self.stack.len() - n);
+                    self.stack.push(tagged("$tuple", Value::Array(items)));
+                }
+                TUPLE => {
+                    let items = self.pop_mark()?;
+                    self.stack.push(tagged("$tuple", Value::Array(items)));
+                }
+                EMPTY_DICT => self.stack.push(Value::Object(Map::new())),
+                DICT => {
+                    let items = self.pop_mark()?;
+                    self.stack.push(dict(Vec::new(), items)?);
+                }
+                SETITEM => {
+                    let value = self.pop()?;
+                    let key = self.pop()?;
+                    self.set_items(vec![key, value])?;
+                }
+                SETITEMS => {
+                    let items = self.pop_mark()?;
+                    self.set_items(items)?;
+                }
+                EMPTY_SET => self.stack.push(tagged("$set", Value::Array(Vec::new()))),
+                ADDITEMS => {
+                    let new_items = self.pop_mark()?;
+                    match self.top()? {
+                        Value::Object(object) => match object.get_mut("$set") {
+                            Some(Value::Array(items)) => items.extend(new_items),
+                            _ => return Err("ADDITEMS to a non-set".to_string()),
+                        },
+                        _ => return Err("ADDITEMS to a non-set".to_string()),
+                    }
+                }
+                FROZENSET => {
+                    let items = self.pop_mark()?;
+                    self.stack.push(tagged("$frozenset", Value::Array(items)));
+                }
+                GLOBAL => {
+                    let module = self.line()?;
+                    let name = self.line()?;
+                    self.stack.push(tagged(
+                        "$global",
+                        Value::String(format!("{}:{}", module, name)),
+                    ));
+                }
+                STACK_GLOBAL => {
+                    let name = self.pop()?;
+                    let module = self.pop()?;
+                    match (module, name) {
+                        (Value::String(module), Value::String(name)) => self.stack.push(tagged(
+                            "$global",
+                            Value::String(format!("{}:{}", module, name)),
+                        )),
+                        _ => return Err("STACK_GLOBAL needs two strings".to_string()),
+                    }
+                }
+                REDUCE | NEWOBJ => {
+                    let args = self.pop()?;
+                    let callable = self.pop()?;
+                    self.stack.push(call(callable, args, op == NEWOBJ)?);
+                }
+                BUILD => {
+                    let state = self.pop()?;
+                    match self.top()? {
+                        Value::Object(object) if object.contains_key("$class") => {
+                            object.insert("$state".to_string(), state);
+                        }
+                        _ => return Err("BUILD on a non-object".to_string()),
+                    }
+                }
+                BINPUT | LONG_BINPUT => {
+                    let index = self.uint(if op == BINPUT { 1 } else { 4 })?;
+                    let top = self.top()?.clone();
+                    self.memo.insert(index, top);
+                }
+                MEMOIZE => {
+                    let index = self.memo.len() as u64;
+                    let top = self.top()?.clone();
+                    self.memo.insert(index, top);
+                }
+                BINGET | LONG_BINGET => {
+                    let index = self.uint(if op == BINGET { 1 } else { 4 })?;
+                    let value = self.memo.get(&index).cloned().ok_or("unknown memo index")?;
+                    self.stack.push(value);
+                }
+                other => return Err(format!("unsupported opcode 0x{:02x}", other)),
+            }
+        }
+    }
+
+    fn set_items(&mut self, items: Vec<Value>) -> Result<(), String> {
+        let target = self.pop()?;
+        if !target.is_object() || target.get("$class").is_some() {
+            return Err("SETITEMS on a non-dict".to_string());
+        }
+        let existing = map_pairs(&target)?;
+        self.stack.push(dict(existing, items)?);
+        Ok(())
+    }
+}
+
+/// Dict from existing pairs plus a flat `[key, value, ...]` list.
+fn dict(mut pairs: Vec<(Value, Value)>, flat: Vec<Value>) -> Result<Value, String> {
+    if !flat.len().is_multiple_of(2) {
+        return Err("odd number of dict items".to_string());
+    }
+    let mut iter = flat.into_iter();
+    while let (Some(k), Some(v)) = (iter.next(), iter.next()) {
+        pairs.retain(|(existing, _)| *existing != k);
+        pairs.push((k, v));
+    }
+    Ok(map_value(pairs))
+}
+
+fn call(callable: Value, args: Value, new: bool) -> Result<Value, String> {
+    let path = callable
+        .get("$global")
+        .and_then(Value::as_str)
+        .ok_or("only globals can be called")?
+        .to_string();
+    let args = match args.get("$tuple") {
+        Some(Value::Array(items)) => items.clone(),
+        _ => return Err("call arguments must be a tuple".to_string()),
+    };
+
+    // set([...]) and frozenset([...]) as written by protocols 2 and 3
+    if !new {
+        let tag = match path.as_str() {
+            "builtins:set" | "__builtin__:set" => Some("$set"),
+            "builtins:frozenset" | "__builtin__:frozenset" => Some("$frozenset"),
+            _ => None,
+        };
+        if let Some(tag) = tag {
+            return match args.as_slice() {
+                [] => Ok(tagged(tag, Value::Array(Vec::new()))),
+                [Value::Array(items)] => Ok(tagged(tag, Value::Array(items.clone()))),
+                _ => Err(format!("unexpected arguments for {}", path)),
+            };
+        }
+    }
+
+    let mut object = Map::new();
+    object.insert("$class".to_string(), Value::String(path));
+    if new {
+        object.insert("$new".to_string(), Value::Bool(true));
+    }
+    object.insert("$args".to_string(), Value::Array(args));
+    Ok(Value::Object(object))
+}
+
+pub fn encode(value: &Value) -> Result<Vec<u8>, String> {
+    let mut out = vec![PROTO, WRITE_PROTOCOL];
+    write_value(value, &mut out)?;
+    out.push(STOP);
+    Ok(out)
+}
+
+fn write_len(op: u8, data: &[u8], out: &mut Vec<u8>) {
+    out.push(op);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+fn write_global(path: &str, out: &mut Vec<u8>) -> Result<(), String> {
+    let (module, name) = path
+        .split_once(':')
+        .ok_or_else(|| format!("expected \"module:name\", got {:?}", path))?;
+    out.push(GLOBAL);
+    for part in [module, name] {
+        out.extend_from_slice(part.as_bytes());
+        out.push(b'\n');
+    }
+    Ok(())
+}
+
+fn write_tuple(items: &[Value], out: &mut Vec<u8>) -> Result<(), String> {
+    if items.is_empty() {
+        out.push(EMPTY_TUPLE);
+        return Ok(());
+    }
+    if items.len() > 3 {
+        out.push(MARK);
+    }
+    for item in items {
+        write_value(item, out)?;
+    }
+    out.push(match items.len() {
+        1 => TUPLE1,
+        2 => TUPLE2,
+        3 => TUPLE3,
+        _ => TUPLE,
+    });
+    Ok(())
+}
+
+fn write_int(n: i64, out: &mut Vec<u8>) {
+    if (0..=0xff).contains(&n) {
+        out.push(BININT1);
+        out.push(n as u8);
+    } else if (0..=0xffff).contains(&n) {
+        out.push(BININT2);
+        out.extend_from_slice(&(n as u16).to_le_bytes());
+    } else if let Ok(n) = i32::try_from(n) {
+        out.push(BININT);
+        out.extend_from_slice(&n.to_le_bytes());
+    } else {
+        // Shortest two's complement that keeps the sign bit
+        let bytes = n.to_le_bytes();
+        let mut len = 8;
+        while len > 1 {
+            let (last, prev) = (bytes[len - 1], bytes[len - 2]);
+            if (last == 0 && prev & 0x80 == 0) || (last == 0xff && prev & 0x80 != 0) {
+                len -= 1;
+            } else {
+                break;
+            }
+        }
+        out.push(LONG1);
+        out.push(len as u8);
+        out.extend_from_slice(&bytes[..len]);
+    }
+}
+
+fn write_value(value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+    match value {
+        Value::Null => out.push(NONE),
+        Value::Bool(b) => out.push(if *b { NEWTRUE } else { NEWFALSE }),
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                write_int(i, out);
+            } else if let Some(u) = n.as_u64() {
+                // Above i64::MAX: nine bytes keep the sign bit clear
+                out.push(LONG1);
+                out.push(9);
+                out.extend_from_slice(&u.to_le_bytes());
+                out.push(0);
+            } else {
+                out.push(BINFLOAT);
+                out.extend_from_slice(&n.as_f64().unwrap_or_default().to_be_bytes());
+            }
+        }
+        Value::String(s) => write_len(BINUNICODE, s.as_bytes(), out),
+        Value::Array(items) => {
+            out.push(EMPTY_LIST);
+            if !items.is_empty() {
+                out.push(MARK);
+                for item in items {
+                    write_value(item, out)?;
+                }
+                out.push(APPENDS);
+            }
+        }
+        Value::Object(object) => {
+            if let Some(bytes) = special_bytes(object) {
+                let bytes = bytes?;
+                if bytes.len() < 256 {
+                    out.push(SHORT_BINBYTES);
+                    out.push(bytes.len() as u8);
+                    out.extend_from_slice(&bytes);
+                } else {
+                    write_len(BINBYTES, &bytes, out);
+                }
+                return Ok(());
+            }
+            if let Some(Value::Array(items)) = object.get("$tuple") {
+                return write_tuple(items, out);
+            }
+            for (tag, path) in [
+                ("$set", "builtins:set"),
+                ("$frozenset", "builtins:frozenset"),
+            ] {
+                if let Some(items) = object.get(tag) {
+                    write_global(path, out)?;
+                    write_tuple(std::slice::from_ref(items), out)?;
+                    out.push(REDUCE);
+                    return Ok(());
+                }
+            }
+            if let Some(path) = object.get("$global") {
+                return write_global(path.as_str().ok_or("$global must be a string")?, out);
+            }
+            if let Some(path) = object.get("$class") {
+                let path = value_bytes(path)
+                    .and_then(|p| String::from_utf8(p).ok())
+                    .ok_or("$class must be a string")?;
+                write_global(&path, out)?;
+                let args = match object.get("$args") {
+                    Some(Value::Array(args)) => args.as_slice(),
+                    _ => &[],
+                };
+                write_tuple(args, out)?;
+                let new = object.get("$new").and_then(Value::as_bool).unwrap_or(false);
+                out.push(if new { NEWOBJ } else { REDUCE });
+                if let Some(state) = object.get("$state") {
+                    write_value(state, out)?;
+                    out.push(BUILD);
+                }
+                return Ok(());
+            }
+
+            out.push(EMPTY_DICT);
+            let pairs = map_pairs(value)?;
+            if !pairs.is_empty() {
+                out.push(MARK);
+                for (k, v) in &pairs {
+                    write_value(k, out)?;
+                    write_value(v, out)?;
+                }
+                out.push(SETITEMS);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// `pickle.dumps(...)` output of CPython, one per protocol
+    const PROTOCOL_2: &[u8] =
+        b"\x80\x02}q\x00(X\x01\x00\x00\x00aq\x01K\x01X\x01\x00\x00\x00bq\x02]q\x03(K\x01K\x02eu.";
+    const PROTOCOL_3: &[u8] = b"\x80\x03C\x02\x00\xffq\x00.";
+    const PROTOCOL_4: &[u8] =
+        b"\x80\x04\x95\x11\x00\x00\x00\x00\x00\x00\x00\x8c\x01x\x94G?\xf8\x00\x00\x00\x00\x00\x00N\x87\x94.";
+    const PROTOCOL_5: &[u8] =
+        b"\x80\x05\x95\x09\x00\x00\x00\x00\x00\x00\x00\x8f\x94(K\x01K\x02\x90.";
+    /// `Point(x=1)` of a plain class, protocol 2
+    const OBJECT: &[u8] =
+        b"\x80\x02c__main__\nPoint\nq\x00)\x81q\x01}q\x02X\x01\x00\x00\x00xq\x03K\x01sb.";
+
+    fn fixtures() -> [(&'static [u8], Value); 5] {
+        [
+            (PROTOCOL_2, json!({"a": 1, "b": [1, 2]})),
+            (PROTOCOL_3, json!({"$base64": "AP8="})),
+            (PROTOCOL_4, json!({"$tuple": ["x", 1.5, null]})),
+            (PROTOCOL_5, json!({"$set": [1, 2]})),
+            (
+                OBJECT,
+                json!({"$class": "__main__:Point", "$new": true, "$args": [], "$state": {"x": 1}}),
+            ),
+        ]
+    }
+
+    #[test]
+    fn decodes_every_protocol() {
+        for (data, expected) in fixtures() {
+            assert!(looks_like(data));
+            assert_eq!(decode(data).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_pickles() {
+        for (data, _) in fixtures() {
+            for len in 0..data.len() {
+                assert!(decode(&data[..len]).is_err(), "{:?}", &data[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_pickles() {
+        // Unknown opcode
+        let mut data = PROTOCOL_2.to_vec();
+        data[2] = 0xff;
+        assert!(decode(&data).is_err());
+        // BINGET of a memo slot never stored
+        assert!(decode(b"\x80\x02h\x05.").is_err());
+        // APPENDS without a MARK
+        assert!(decode(b"\x80\x02]K\x01e.").is_err());
+        // Bytes after STOP
+        assert!(decode(b"\x80\x02N.N").is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        for (data, expected) in fixtures() {
+            let encoded = encode(&expected).unwrap();
+            assert_eq!(decode(&encoded).unwrap(), expected, "{:?}", data);
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! Protobuf without a schema (raw wire format) and with a user-supplied
+//! descriptor set (`protoc --descriptor_set_out`).
+//!
+//! A raw message is a list of `{"field", "type", "value"}` entries in wire
+//! order. Length-delimited values are shown as text, a nested message (an
+//! array) or `{"$base64": ...}`, whichever fits first.
+
+use super::{base64_value, value_bytes};
+use crate::error::CommandError;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Nested messages deeper than this are left as bytes.
+const MAX_DEPTH: usize = 32;
+const MAX_FIELD_NUMBER: u64 = (1 << 29) - 1;
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtoSchema {
+    /// FileDescriptorSet produced by `protoc --include_imports --descriptor_set_out`
+    pub descriptor_set_path: String,
+    /// Fully qualified message name (`my.package.User`)
+    pub message: String,
+}
+
+pub struct Schema(MessageDescriptor);
+
+impl ProtoSchema {
+    pub fn load(&self) -> Result<Schema, CommandError> {
+        let bytes = std::fs::read(&self.descriptor_set_path).map_err(|e| {
+            CommandError::invalid(format!(
+                "Failed to read descriptor set {}: {}",
+                self.descriptor_set_path, e
+            ))
+        })?;
+        let pool = DescriptorPool::decode(bytes.as_slice())
+            .map_err(|e| CommandError::invalid(format!("Invalid descriptor set: {}", e)))?;
+        pool.get_message_by_name(&self.message)
+            .map(Schema)
+            .ok_or_else(|| {
+                CommandError::invalid(format!(
+                    "Message {} not found in descriptor set",
+                    self.message
+                ))
+            })
+    }
+}
+
+impl Schema {
+    pub fn decode(&self, bytes: &[u8]) -> Result<Value, String> {
+        let message = DynamicMessage::decode(self.0.clone(), bytes).map_err(|e| e.to_string())?;
+        serde_json::to_value(&message).map_err(|e| e.to_string())
+    }
+
+    pub fn encode(&self, value: &Value) -> Result<Vec<u8>, String> {
+        let message =
+            DynamicMessage::deserialize(self.0.clone(), value).map_err(|e| e.to_string())?;
+        Ok(message.encode_to_vec())
+    }
+}
+
+pub fn decode_raw(bytes: &[u8]) -> Result<Value, String> {
+    let fields = decode_fields(bytes, 0)?;
+    if fields.is_empty() {
+        return Err("empty message".to_string());
+    }
+    Ok(Value::Array(fields))
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64, String> {
+    let mut result = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos).ok_or("truncated varint")?;
+        *pos += 1;
+        result |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+    Err("varint too long".to_string())
+}
+
+fn read_slice<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+    let end = pos.checked_add(len).filter(|&end| end <= bytes.len());
+    let slice = end.map(|end| &bytes[*pos..end]).ok_or("truncated field")?;
+    *pos += len;
+    Ok(slice)
+}
+
+fn decode_fields(bytes: &[u8], depth: usize) -> Result<Vec<Value>, String> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let key = read_varint(bytes, &mut pos)?;
+        let number = key >> 3;
+        if number == 0 || number > MAX_FIELD_NUMBER {
+            return Err(format!("invalid field number {}", number));
+        }
+
+        let (kind, value) = match key & 7 {
+            0 => ("varint", Value::from(read_varint(bytes, &mut pos)?)),
+            1 => {
+                let raw = read_slice(bytes, &mut pos, 8)?;
+                let n = u64::from_le_bytes(raw.try_into().map_err(|_| "truncated fixed64")?);
+                ("i64", Value::from(n))
+            }
+            2 => {
+                let len = read_varint(bytes, &mut pos)? as usize;
+                ("len", len_value(read_slice(bytes, &mut pos, len)?, depth))
+            }
+            5 => {
+                let raw = read_slice(bytes, &mut pos, 4)?;
+                let n = u32::from_le_bytes(raw.try_into().map_err(|_| "truncated fixed32")?);
+                ("i32", Value::from(n))
+            }
+            // Groups (3, 4) are deprecated and never written by proto3
+            other => return Err(format!("unsupported wire type {}", other)),
+        };
+
+        let mut field = Map::new();
+        field.insert("field".to_string(), Value::from(number));
+        field.insert("type".to_string(), Value::from(kind));
+        field.insert("value".to_string(), value);
+        fields.push(Value::Object(field));
+    }
+    Ok(fields)
+}
+
+fn len_value(bytes: &[u8], depth: usize) -> Value {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+            return Value::String(text.to_string());
+        }
+    }
+    if depth < MAX_DEPTH && !bytes.is_empty() {
+        if let Ok(fields) = decode_fields(bytes, depth + 1) {
+            return Value::Array(fields);
+        }
+    }
+    base64_value(bytes)
+}
+
+pub fn encode_raw(value: &Value) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    encode_fields(value, &mut out)?;
+    Ok(out)
+}
+
+fn write_varint(mut n: u64, out: &mut Vec<u8>) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn encode_fields(value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+    let fields = value
+        .as_array()
+        .ok_or("a message must be an array of fields")?;
+
+    for field in fields {
+        let number = field
+            .get("field")
+            .and_then(Value::as_u64)
+            .filter(|n| (1..=MAX_FIELD_NUMBER).contains(n))
+            .ok_or("every field needs a valid \"field\" number")?;
+        let kind = field.get("type").and_then(Value::as_str).unwrap_or("");
+        let value = field.get("value").unwrap_or(&Value::Null);
+        let as_u64 = || {
+            value
+                .as_u64()
+                .ok_or_else(|| format!("field {}: expected an unsigned integer", number))
+        };
+
+        match kind {
+            "varint" => {
+                write_varint(number << 3, out);
+                write_varint(as_u64()?, out);
+            }
+            "i64" => {
+                write_varint(number << 3 | 1, out);
+                out.extend_from_slice(&as_u64()?.to_le_bytes());
+            }
+            "i32" => {
+                let n = u32::try_from(as_u64()?)
+                    .map_err(|_| format!("field {}: fixed32 out of range", number))?;
+                write_varint(number << 3 | 5, out);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+            "len" => {
+                let data = match value {
+                    Value::Array(_) => encode_raw(value)?,
+                    other => value_bytes(other).ok_or_else(|| {
+                        format!("field {}: invalid length-delimited value", number)
+                    })?,
+                };
+                write_varint(number << 3 | 2, out);
+                write_varint(data.len() as u64, out);
+                out.extend_from_slice(&data);
+            }
+            other => return Err(format!("field {}: unknown wire type {:?}", number, other)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_reflect::prost_types::{
+        field_descriptor_proto::{Label, Type},
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    };
+    use serde_json::json;
+
+    /// `User { name: "bob", age: 30 }`
+    const USER: &[u8] = &[0x0a, 0x03, b'b', b'o', b'b', 0x10, 0x1e];
+
+    fn field(name: &str, number: i32, kind: Type) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(kind as i32),
+            json_name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Writes a descriptor set for `test.User` and loads it.
+    fn user_schema() -> Schema {
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("user.proto".to_string()),
+                package: Some("test".to_string()),
+                syntax: Some("proto3".to_string()),
+                message_type: vec![DescriptorProto {
+                    name: Some("User".to_string()),
+                    field: vec![field("name", 1, Type::String), field("age", 2, Type::Int32)],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let path = std::env::temp_dir().join(format!("urdis-test-{}.pb", std::process::id()));
+        std::fs::write(&path, set.encode_to_vec()).unwrap();
+        let schema = ProtoSchema {
+            descriptor_set_path: path.to_string_lossy().into_owned(),
+            message: "test.User".to_string(),
+        }
+        .load();
+        let _ = std::fs::remove_file(&path);
+        schema.unwrap_or_else(|e| panic!("{}", e.message))
+    }
+
+    #[test]
+    fn decodes_raw_messages() {
+        assert_eq!(
+            decode_raw(USER).unwrap(),
+            json!([
+                {"field": 1, "type": "len", "value": "bob"},
+                {"field": 2, "type": "varint", "value": 30},
+            ])
+        );
+        // A nested message in field 3
+        let nested = [0x1a, 0x02, 0x08, 0x01];
+        assert_eq!(
+            decode_raw(&nested).unwrap(),
+            json!([{"field": 3, "type": "len", "value": [{"field": 1, "type": "varint", "value": 1}]}])
+        );
+    }
+
+    #[test]
+    fn rejects_corrupt_raw_messages() {
+        // Cut inside a field; 5 bytes end right after the first one
+        for len in (1..USER.len()).filter(|&len| len != 5) {
+            assert!(decode_raw(&USER[..len]).is_err(), "{:?}", &USER[..len]);
+        }
+        assert!(decode_raw(&[]).is_err());
+        // Field number 0
+        assert!(decode_raw(&[0x00, 0x01]).is_err());
+        // Group wire type
+        assert!(decode_raw(&[0x0b]).is_err());
+        // Varint that never ends
+        assert!(decode_raw(&[0x08, 0xff, 0xff]).is_err());
+    }
+
+    #[test]
+    fn raw_round_trips() {
+        let value = decode_raw(USER).unwrap();
+        assert_eq!(encode_raw(&value).unwrap(), USER);
+    }
+
+    #[test]
+    fn decodes_with_a_schema() {
+        let schema = user_schema();
+        let value = schema.decode(USER).unwrap();
+        assert_eq!(value, json!({"name": "bob", "age": 30}));
+        assert_eq!(schema.encode(&value).unwrap(), USER);
+
+        // Length running past the end
+        assert!(schema.decode(&[0x0a, 0x05, b'b']).is_err());
+        // Wrong wire type for `name`
+        assert!(schema.decode(&[0x08, 0x01]).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_descriptor_sets() {
+        let path = std::env::temp_dir().join(format!("urdis-missing-{}.pb", std::process::id()));
+        let missing = ProtoSchema {
+            descriptor_set_path: path.to_string_lossy().into_owned(),
+            message: "test.User".to_string(),
+        };
+        assert!(missing.load().is_err());
+    }
+}
@@ -0,0 +1,108 @@
+//! Deleting every key that matches a pattern.
+//!
+//! Keys are scanned in batches and removed with UNLINK, which frees big
+//! values in the background instead of blocking the server like DEL.
+//! Servers older than 4.0 get DEL.
+
+use crate::connection::RedisConnection;
+use crate::error::{is_unknown_command, CommandError};
+use crate::jobs::JobGuard;
+use crate::keyspace::KeyBatches;
+use crate::RedisBytes;
+use redis::RedisResult;
+use serde::Serialize;
+use tauri::ipc::Channel;
+
+/// Matched keys listed in a dry run.
+const SAMPLE_SIZE: usize = 20;
+
+#[derive(Serialize, Clone)]
+pub struct DeleteProgress {
+    pub matched: u64,
+    pub deleted: u64,
+    pub total: Option<u64>,
+    /// UNLINK, or DEL after falling back
+    pub command: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct DeleteSummary {
+    pub matched: u64,
+    /// Always 0 in a dry run
+    pub deleted: u64,
+    /// The first matched keys, filled in a dry run
+    pub sample: Vec<RedisBytes>,
+    pub dry_run: bool,
+    pub cancelled: bool,
+}
+
+/// Removes `keys` one command each, so a cluster can route every key to
+/// its own slot. Returns how many existed.
+pub async fn remove_keys(
+    con: &mut RedisConnection,
+    keys: &[Vec<u8>],
+    command: &str,
+) -> RedisResult<u64> {
+    let mut pipe = redis::pipe();
+    for key in keys {
+        pipe.cmd(command).arg(key);
+    }
+    let removed: Vec<u64> = con.query_pipeline(&pipe).await?;
+    Ok(removed.into_iter().sum())
+}
+
+/// UNLINK (or DEL) for every key of `batches`. With `dry_run` the keys are
+/// only counted.
+pub async fn delete_keys(
+    mut batches: KeyBatches,
+    dry_run: bool,
+    job: &JobGuard,
+    on_progress: &Channel<DeleteProgress>,
+) -> Result<DeleteSummary, CommandError> {
+    let mut progress = DeleteProgress {
+        matched: 0,
+        deleted: 0,
+        total: batches.total,
+        command: "UNLINK",
+    };
+    let mut sample = Vec::new();
+    let mut cancelled = false;
+
+    while let Some((mut con, keys)) = batches.next().await? {
+        if job.is_cancelled() {
+            cancelled = true;
+            break;
+        }
+        if keys.is_empty() {
+            continue;
+        }
+        progress.matched += keys.len() as u64;
+
+        if dry_run {
+            let room = SAMPLE_SIZE.saturating_sub(sample.len());
+            sample.extend(keys.into_iter().take(room).map(RedisBytes::from));
+        } else {
+            progress.deleted += match remove_keys(&mut con, &keys, progress.command).await {
+                Ok(deleted) => deleted,
+                Err(e) if progress.command == "UNLINK" && is_unknown_command(&e) => {
+                    progress.command = "DEL";
+                    remove_keys(&mut con, &keys, "DEL")
+                        .await
+                        .map_err(|e| CommandError::redis("DEL error", e))?
+                }
+                Err(e) => return Err(CommandError::redis("UNLINK error", e)),
+            };
+        }
+
+        // The UI may have gone away, the deletion still completes
+        let _ = on_progress.send(progress.clone());
+    }
+
+    Ok(DeleteSummary {
+        matched: progress.matched,
+        deleted: progress.deleted,
+        sample,
+        dry_run,
+        cancelled,
+    })
+}
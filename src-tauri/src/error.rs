@@ -0,0 +1,153 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Coarse error category, enough for the UI to pick a recovery action.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Missing or wrong credentials (NOAUTH, WRONGPASS, failed AUTH)
+    Auth,
+    /// The ACL user may not run this command (NOPERM)
+    Permission,
+    /// Server unreachable, or the connection dropped
+    Connection,
+    Timeout,
+    /// Operation against a key holding the wrong kind of value
+    WrongType,
+    /// Cluster redirect that was not followed (MOVED, ASK)
+    Redirect,
+    /// Server is loading, busy or the cluster is down, retry later
+    Busy,
+    /// Any other error reply from the server
+    Server,
+    /// The reply did not have the expected shape
+    Parse,
+    /// Bad arguments or settings from the caller
+    InvalidInput,
+    /// The connection id is not (or no longer) registered
+    UnknownConnection,
+    /// Mutating command on a read-only connection profile
+    ReadOnly,
+    /// Dangerous command, repeat it with `confirm_token` to run it
+    ConfirmationRequired,
+    Ssh,
+    Internal,
+}
+
+impl ErrorKind {
+    fn retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Connection | ErrorKind::Timeout | ErrorKind::Redirect | ErrorKind::Busy
+        )
+    }
+}
+
+/// Error returned by every command.
+#[derive(Serialize, Clone, Debug)]
+pub struct CommandError {
+    pub kind: ErrorKind,
+    /// Redis error code (`WRONGTYPE`, `NOAUTH`, ...) when the server sent one
+    pub code: Option<String>,
+    pub message: String,
+    /// Repeating the call (after reconnecting or waiting) may succeed
+    pub retryable: bool,
+    /// Set with `ConfirmationRequired`, pass it back to run the command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm_token: Option<String>,
+}
+
+impl CommandError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        CommandError {
+            kind,
+            code: None,
+            message: message.into(),
+            retryable: kind.retryable(),
+            confirm_token: None,
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::InvalidInput, message)
+    }
+
+    /// Classifies a redis error, prefixing the message with `context`
+    /// ("HSCAN error: ...").
+    pub fn redis(context: &str, err: redis::RedisError) -> Self {
+        CommandError::from(err).context(context)
+    }
+
+    pub fn context(mut self, context: &str) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+
+    pub fn parse(context: &str, err: redis::ParsingError) -> Self {
+        Self::new(ErrorKind::Parse, format!("{}: {}", context, err))
+    }
+}
+
+/// A `spawn_blocking` task panicked or was cancelled.
+pub fn join_error(context: &str, err: tokio::task::JoinError) -> CommandError {
+    CommandError::new(
+        ErrorKind::Internal,
+        format!("{} task failed: {}", context, err),
+    )
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<redis::RedisError> for CommandError {
+    fn from(err: redis::RedisError) -> Self {
+        let kind = classify(&err);
+        CommandError {
+            kind,
+            code: err.code().map(str::to_string),
+            message: err.to_string(),
+            retryable: kind.retryable(),
+            confirm_token: None,
+        }
+    }
+}
+
+/// The server predates the command (COPY before 6.2, UNLINK before 4.0)
+/// or the subcommand (PUBSUB SHARDCHANNELS before 7.0).
+pub fn is_unknown_command(err: &redis::RedisError) -> bool {
+    let message = err.to_string();
+    message.contains("unknown command") || message.contains("unknown subcommand")
+}
+
+fn classify(err: &redis::RedisError) -> ErrorKind {
+    match err.code() {
+        Some("NOAUTH") | Some("WRONGPASS") => return ErrorKind::Auth,
+        Some("NOPERM") => return ErrorKind::Permission,
+        Some("WRONGTYPE") => return ErrorKind::WrongType,
+        Some("MOVED") | Some("ASK") => return ErrorKind::Redirect,
+        Some("LOADING") | Some("BUSY") | Some("TRYAGAIN") | Some("CLUSTERDOWN")
+        | Some("MASTERDOWN") => return ErrorKind::Busy,
+        _ => {}
+    }
+
+    if err.is_timeout() {
+        return ErrorKind::Timeout;
+    }
+    if err.is_connection_dropped() || err.is_connection_refusal() || err.is_io_error() {
+        return ErrorKind::Connection;
+    }
+
+    match err.kind() {
+        redis::ErrorKind::AuthenticationFailed => ErrorKind::Auth,
+        redis::ErrorKind::Parse | redis::ErrorKind::UnexpectedReturnType => ErrorKind::Parse,
+        redis::ErrorKind::InvalidClientConfig => ErrorKind::InvalidInput,
+        redis::ErrorKind::ClusterConnectionNotFound => ErrorKind::Connection,
+        redis::ErrorKind::Server(_) | redis::ErrorKind::Extension => ErrorKind::Server,
+        _ => ErrorKind::Internal,
+    }
+}
@@ -0,0 +1,348 @@
+//! Streams keys to NDJSON, per-type CSV files, or a command script that
+//! `redis-cli` can replay (`redis-cli -n 0 < keys.redis`).
+//!
+//! NDJSON is the lossless format: one `KeyRecord` per line, binary data as
+//! `{"encoding": "base64", ...}`. CSV cells hold binary data as
+//! `base64:<data>`; text that starts with `base64:` or `text:` is written
+//! as `text:<text>`, so every cell reads back one way. Stream consumer
+//! groups are not exported.
+
+use crate::error::{join_error, CommandError};
+use crate::jobs::JobGuard;
+use crate::keyspace::{fetch_records, KeyBatches, KeyRecord, KeyValue, WRITE_CHUNK};
+use crate::RedisBytes;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tauri::ipc::Channel;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Ndjson,
+    /// One `<name>.<type>.csv` file per key type next to the given path
+    Csv,
+    /// Redis commands, one per line, in redis-cli syntax
+    Commands,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ExportProgress {
+    pub exported: u64,
+    /// Keys that vanished during the export or hold module types
+    pub skipped: u64,
+    pub total: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ExportSummary {
+    pub exported: u64,
+    pub skipped: u64,
+    /// Stopped early by `cancel_job`, the files hold the keys so far
+    pub cancelled: bool,
+    pub files: Vec<String>,
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> CommandError {
+    CommandError::invalid(format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>, CommandError> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| io_error(path, e))
+}
+
+/// Blocking file writes, run on `spawn_blocking`.
+enum Output {
+    Single(PathBuf, BufWriter<File>),
+    /// CSV files opened on the first key of their type
+    PerType(PathBuf, HashMap<&'static str, (PathBuf, BufWriter<File>)>),
+}
+
+impl Output {
+    fn open(format: ExportFormat, path: &Path) -> Result<Self, CommandError> {
+        match format {
+            ExportFormat::Csv => Ok(Output::PerType(path.to_path_buf(), HashMap::new())),
+            _ => Ok(Output::Single(path.to_path_buf(), create(path)?)),
+        }
+    }
+
+    fn write(&mut self, format: ExportFormat, record: &KeyRecord) -> Result<(), CommandError> {
+        match self {
+            Output::Single(path, out) => {
+                let written = match format {
+                    ExportFormat::Ndjson => serde_json::to_writer(&mut *out, record)
+                        .map_err(std::io::Error::from)
+                        .and_then(|_| out.write_all(b"\n")),
+                    _ => write_commands(out, record),
+                };
+                written.map_err(|e| io_error(path, e))
+            }
+            Output::PerType(base, files) => {
+                let type_name = record.value.type_name();
+                if !files.contains_key(type_name) {
+                    let path = csv_path(base, type_name);
+                    let mut out = create(&path)?;
+                    writeln!(out, "{}", csv_header(&record.value))
+                        .map_err(|e| io_error(&path, e))?;
+                    files.insert(type_name, (path, out));
+                }
+                let (path, out) = files.get_mut(type_name).unwrap();
+                write_csv_rows(out, record).map_err(|e| io_error(path, e))
+            }
+        }
+    }
+
+    fn finish(self) -> Result<Vec<String>, CommandError> {
+        let files: Vec<(PathBuf, BufWriter<File>)> = match self {
+            Output::Single(path, out) => vec![(path, out)],
+            Output::PerType(_, files) => files.into_values().collect(),
+        };
+        let mut paths = Vec::with_capacity(files.len());
+        for (path, mut out) in files {
+            out.flush().map_err(|e| io_error(&path, e))?;
+            paths.push(path.display().to_string());
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+/// `dir/keys.csv` becomes `dir/keys.hash.csv` for hashes.
+fn csv_path(base: &Path, type_name: &str) -> PathBuf {
+    let stem = base
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or(Cow::Borrowed("export"));
+    base.with_file_name(format!("{}.{}.csv", stem, type_name))
+}
+
+pub async fn export_keys(
+    mut batches: KeyBatches,
+    format: ExportFormat,
+    path: &Path,
+    job: &JobGuard,
+    on_progress: &Channel<ExportProgress>,
+) -> Result<ExportSummary, CommandError> {
+    let path = path.to_path_buf();
+    let mut output = tokio::task::spawn_blocking(move || Output::open(format, &path))
+        .await
+        .map_err(|e| join_error("Export", e))??;
+    let mut progress = ExportProgress {
+        exported: 0,
+        skipped: 0,
+        total: batches.total,
+    };
+
+    let mut cancelled = false;
+    while let Some((mut con, keys)) = batches.next().await? {
+        if job.is_cancelled() {
+            cancelled = true;
+            break;
+        }
+
+        let requested = keys.len() as u64;
+        let records = fetch_records(&mut con, keys).await?;
+        let fetched = records.len() as u64;
+        let (returned, written) = tokio::task::spawn_blocking(move || {
+            let written = records
+                .iter()
+                .try_for_each(|record| output.write(format, record));
+            (output, written)
+        })
+        .await
+        .map_err(|e| join_error("Export", e))?;
+        output = returned;
+        written?;
+        progress.exported += fetched;
+        progress.skipped += requested - fetched;
+        // The UI may have gone away, the export still completes
+        let _ = on_progress.send(progress.clone());
+    }
+
+    let files = tokio::task::spawn_blocking(move || output.finish())
+        .await
+        .map_err(|e| join_error("Export", e))??;
+    Ok(ExportSummary {
+        exported: progress.exported,
+        skipped: progress.skipped,
+        cancelled,
+        files,
+    })
+}
+
+fn csv_header(value: &KeyValue) -> &'static str {
+    match value {
+        KeyValue::String(_) => "key,pttl,value",
+        KeyValue::List(_) => "key,pttl,index,value",
+        KeyValue::Set(_) => "key,pttl,member",
+        KeyValue::Zset(_) => "key,pttl,member,score",
+        KeyValue::Hash(_) => "key,pttl,field,value",
+        KeyValue::Stream(_) => "key,pttl,id,field,value",
+    }
+}
+
+fn csv_cell(bytes: &RedisBytes) -> Cow<'_, str> {
+    let text: Cow<str> = match bytes {
+        RedisBytes::Utf8(s) if s.starts_with("base64:") || s.starts_with("text:") => {
+            Cow::Owned(format!("text:{}", s))
+        }
+        RedisBytes::Utf8(s) => Cow::Borrowed(s),
+        RedisBytes::Base64(b) => Cow::Owned(format!("base64:{}", b)),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        text
+    }
+}
+
+/// One row per element; the key and TTL are repeated on every row.
+fn write_csv_rows(out: &mut impl Write, record: &KeyRecord) -> std::io::Result<()> {
+    let key = csv_cell(&record.key);
+    let pttl = record.pttl.map(|t| t.to_string()).unwrap_or_default();
+
+    match &record.value {
+        KeyValue::String(value) => writeln!(out, "{},{},{}", key, pttl, csv_cell(value))?,
+        KeyValue::List(items) => {
+            for (index, item) in items.iter().enumerate() {
+                writeln!(out, "{},{},{},{}", key, pttl, index, csv_cell(item))?;
+            }
+        }
+        KeyValue::Set(members) => {
+            for member in members {
+                writeln!(out, "{},{},{}", key, pttl, csv_cell(member))?;
+            }
+        }
+        KeyValue::Zset(members) => {
+            for (member, score) in members {
+                writeln!(out, "{},{},{},{}", key, pttl, csv_cell(member), score)?;
+            }
+        }
+        KeyValue::Hash(fields) => {
+            for (field, value) in fields {
+                writeln!(
+                    out,
+                    "{},{},{},{}",
+                    key,
+                    pttl,
+                    csv_cell(field),
+                    csv_cell(value)
+                )?;
+            }
+        }
+        KeyValue::Stream(entries) => {
+            for entry in entries {
+                for (field, value) in &entry.fields {
+                    writeln!(
+                        out,
+                        "{},{},{},{},{}",
+                        key,
+                        pttl,
+                        entry.id,
+                        csv_cell(field),
+                        csv_cell(value)
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Quotes an argument the way redis-cli splits its input lines. Text stays
+/// readable, other bytes become `\xHH` escapes.
+pub(crate) fn script_arg(bytes: &RedisBytes) -> String {
+    let raw: Cow<[u8]> = match bytes {
+        RedisBytes::Utf8(s) => Cow::Borrowed(s.as_bytes()),
+        RedisBytes::Base64(_) => Cow::Owned(bytes.to_bytes().unwrap_or_default()),
+    };
+    let is_text = matches!(bytes, RedisBytes::Utf8(_));
+
+    let mut arg = String::with_capacity(raw.len() + 2);
+    arg.push('"');
+    if is_text {
+        for c in std::str::from_utf8(&raw).unwrap_or_default().chars() {
+            match c {
+                '"' => arg.push_str("\\\""),
+                '\\' => arg.push_str("\\\\"),
+                '\n' => arg.push_str("\\n"),
+                '\r' => arg.push_str("\\r"),
+                '\t' => arg.push_str("\\t"),
+                c if c.is_control() => {
+                    for b in c.encode_utf8(&mut [0; 4]).bytes() {
+                        arg.push_str(&format!("\\x{:02x}", b));
+                    }
+                }
+                c => arg.push(c),
+            }
+        }
+    } else {
+        for &b in raw.iter() {
+            match b {
+                b'"' => arg.push_str("\\\""),
+                b'\\' => arg.push_str("\\\\"),
+                0x20..=0x7e => arg.push(b as char),
+                _ => arg.push_str(&format!("\\x{:02x}", b)),
+            }
+        }
+    }
+    arg.push('"');
+    arg
+}
+
+/// Writes `cmd key <chunk of args>` lines, `WRITE_CHUNK` items per line.
+fn write_chunked<T>(
+    out: &mut impl Write,
+    cmd: &str,
+    key: &str,
+    items: &[T],
+    item_args: impl Fn(&T) -> String,
+) -> std::io::Result<()> {
+    for chunk in items.chunks(WRITE_CHUNK) {
+        let args: Vec<String> = chunk.iter().map(&item_args).collect();
+        writeln!(out, "{} {} {}", cmd, key, args.join(" "))?;
+    }
+    Ok(())
+}
+
+/// Replaces the key: DEL first, then rebuild it and restore the TTL.
+fn write_commands(out: &mut impl Write, record: &KeyRecord) -> std::io::Result<()> {
+    let key = script_arg(&record.key);
+    writeln!(out, "DEL {}", key)?;
+
+    match &record.value {
+        KeyValue::String(value) => writeln!(out, "SET {} {}", key, script_arg(value))?,
+        KeyValue::List(items) => write_chunked(out, "RPUSH", &key, items, script_arg)?,
+        KeyValue::Set(members) => write_chunked(out, "SADD", &key, members, script_arg)?,
+        KeyValue::Zset(members) => write_chunked(out, "ZADD", &key, members, |(member, score)| {
+            format!("{} {}", score, script_arg(member))
+        })?,
+        KeyValue::Hash(fields) => write_chunked(out, "HSET", &key, fields, |(field, value)| {
+            format!("{} {}", script_arg(field), script_arg(value))
+        })?,
+        KeyValue::Stream(entries) => {
+            for entry in entries {
+                let fields: Vec<String> = entry
+                    .fields
+                    .iter()
+                    .map(|(f, v)| format!("{} {}", script_arg(f), script_arg(v)))
+                    .collect();
+                writeln!(out, "XADD {} {} {}", key, entry.id, fields.join(" "))?;
+            }
+            // XADD cannot create an empty stream, add an entry and trim it away
+            if entries.is_empty() {
+                writeln!(out, "XADD {} MAXLEN 0 0-1 _ \"\"", key)?;
+            }
+        }
+    }
+
+    if let Some(pttl) = record.pttl {
+        writeln!(out, "PEXPIRE {} {}", key, pttl)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,429 @@
+//! Replays export files: NDJSON, a JSON array of the same records, or a
+//! redis-cli command script.
+//!
+//! Every key goes through the conflict policy the first time it shows up.
+//! Keys are read in batches, their existence checked with one pipeline and
+//! the writes sent with another. Scripts may only contain commands that take
+//! the key as first argument (`SET`, `RPUSH`, `PEXPIRE`, ...) and are
+//! replayed as written; exported scripts clear each key with a `DEL` first.
+
+use crate::connection::RedisConnection;
+use crate::console::split_args;
+use crate::error::{join_error, CommandError};
+use crate::jobs::JobGuard;
+use crate::keyspace::{restore_commands, run_key_commands, KeyRecord};
+use crate::RedisBytes;
+use redis::Cmd;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use tauri::ipc::Channel;
+
+/// Records or script lines read per batch
+const IMPORT_BATCH: usize = 500;
+/// Per-key changes listed in the summary, the counts cover everything
+const MAX_REPORTED_CHANGES: usize = 1000;
+
+/// Script commands that take exactly one key, as first argument.
+const SCRIPT_COMMANDS: &[&str] = &[
+    "DEL",
+    "UNLINK",
+    "SET",
+    "SETEX",
+    "PSETEX",
+    "SETNX",
+    "APPEND",
+    "SETRANGE",
+    "RPUSH",
+    "LPUSH",
+    "SADD",
+    "ZADD",
+    "HSET",
+    "HMSET",
+    "HSETNX",
+    "XADD",
+    "EXPIRE",
+    "PEXPIRE",
+    "EXPIREAT",
+    "PEXPIREAT",
+    "PERSIST",
+    "RESTORE",
+];
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Ndjson,
+    /// A JSON array of NDJSON records, read into memory as a whole
+    Json,
+    Commands,
+}
+
+/// What to do with keys that already exist in the target database.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    /// Import as `<key>_1` (or the first free `<key>_<n>`)
+    Rename,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Create,
+    Overwrite,
+    Rename,
+    Skip,
+}
+
+#[derive(Serialize)]
+pub struct ImportChange {
+    pub key: RedisBytes,
+    pub action: ImportAction,
+    /// New name of a renamed key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<RedisBytes>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ImportProgress {
+    pub keys: u64,
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Serialize, Default)]
+pub struct ImportSummary {
+    pub created: u64,
+    pub overwritten: u64,
+    pub renamed: u64,
+    pub skipped: u64,
+    /// Nothing was written, the counts say what would have changed
+    pub dry_run: bool,
+    /// Stopped early by `cancel_job`, the keys so far were written
+    pub cancelled: bool,
+    /// The first `MAX_REPORTED_CHANGES` keys and what happened to them
+    pub changes: Vec<ImportChange>,
+}
+
+enum Item {
+    Record(KeyRecord),
+    /// Script command, the key is `argv[1]`
+    Command(Vec<Vec<u8>>),
+}
+
+impl Item {
+    fn key(&self) -> Result<Vec<u8>, CommandError> {
+        match self {
+            Item::Record(record) => record.key.to_bytes(),
+            Item::Command(argv) => Ok(argv[1].clone()),
+        }
+    }
+}
+
+/// Blocking file reads, run on `spawn_blocking`.
+struct Reader {
+    path: PathBuf,
+    format: ImportFormat,
+    lines: BufReader<File>,
+    line_no: usize,
+    bytes_read: u64,
+    total_bytes: u64,
+    /// Records of a JSON array, parsed on the first read
+    records: Option<std::vec::IntoIter<KeyRecord>>,
+}
+
+impl Reader {
+    fn open(path: PathBuf, format: ImportFormat) -> Result<Self, CommandError> {
+        let io_error = |e: std::io::Error| {
+            CommandError::invalid(format!("Failed to read {}: {}", path.display(), e))
+        };
+        let file = File::open(&path).map_err(io_error)?;
+        let total_bytes = file.metadata().map_err(io_error)?.len();
+        Ok(Reader {
+            path,
+            format,
+            lines: BufReader::new(file),
+            line_no: 0,
+            bytes_read: 0,
+            total_bytes,
+            records: None,
+        })
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> CommandError {
+        CommandError::invalid(format!(
+            "{} line {}: {}",
+            self.path.display(),
+            self.line_no,
+            message
+        ))
+    }
+
+    fn read_batch(&mut self, size: usize) -> Result<Vec<Item>, CommandError> {
+        let mut items = Vec::with_capacity(size);
+        while items.len() < size {
+            match self.next()? {
+                Some(item) => items.push(item),
+                None => break,
+            }
+        }
+        Ok(items)
+    }
+
+    fn next(&mut self) -> Result<Option<Item>, CommandError> {
+        if matches!(self.format, ImportFormat::Json) {
+            if self.records.is_none() {
+                let records: Vec<KeyRecord> =
+                    serde_json::from_reader(&mut self.lines).map_err(|e| {
+                        CommandError::invalid(format!("{}: {}", self.path.display(), e))
+                    })?;
+                self.bytes_read = self.total_bytes;
+                self.records = Some(records.into_iter());
+            }
+            return Ok(self
+                .records
+                .as_mut()
+                .and_then(Iterator::next)
+                .map(Item::Record));
+        }
+
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = self
+                .lines
+                .read_until(b'\n', &mut line)
+                .map_err(|e| self.error(e))?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.line_no += 1;
+            self.bytes_read += read as u64;
+
+            let trimmed = line.trim_ascii();
+            if trimmed.is_empty() || trimmed.starts_with(b"#") {
+                continue;
+            }
+
+            return match self.format {
+                ImportFormat::Commands => {
+                    let argv = split_args(trimmed).map_err(|e| self.error(e))?;
+                    check_script_command(&argv).map_err(|e| self.error(e))?;
+                    Ok(Some(Item::Command(argv)))
+                }
+                _ => serde_json::from_slice(trimmed)
+                    .map(|record| Some(Item::Record(record)))
+                    .map_err(|e| self.error(e)),
+            };
+        }
+    }
+}
+
+fn check_script_command(argv: &[Vec<u8>]) -> Result<(), String> {
+    let name = String::from_utf8_lossy(&argv[0]).to_uppercase();
+    if !SCRIPT_COMMANDS.contains(&name.as_str()) {
+        return Err(format!("{} is not supported in import scripts", name));
+    }
+    if argv.len() < 2 || (matches!(name.as_str(), "DEL" | "UNLINK") && argv.len() > 2) {
+        return Err(format!("{} must be given exactly one key", name));
+    }
+    Ok(())
+}
+
+/// Where the items of a key end up: `None` when it is skipped.
+type Target = Option<Vec<u8>>;
+
+struct Importer {
+    policy: ConflictPolicy,
+    dry_run: bool,
+    decided: HashMap<Vec<u8>, Target>,
+    /// Names this import writes to, taken even before they exist
+    claimed: HashSet<Vec<u8>>,
+    summary: ImportSummary,
+}
+
+impl Importer {
+    /// First free `<key>_<n>`.
+    async fn free_name(
+        &self,
+        con: &mut RedisConnection,
+        key: &[u8],
+    ) -> Result<Vec<u8>, CommandError> {
+        let mut n = 1;
+        loop {
+            let mut candidate = key.to_vec();
+            candidate.extend_from_slice(format!("_{}", n).as_bytes());
+            n += 1;
+            if self.claimed.contains(&candidate) {
+                continue;
+            }
+            let exists: bool = redis::cmd("EXISTS")
+                .arg(&candidate)
+                .query_async(con)
+                .await
+                .map_err(|e| CommandError::redis("EXISTS error", e))?;
+            if !exists {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    /// Applies the conflict policy to keys seen for the first time.
+    async fn decide(
+        &mut self,
+        con: &mut RedisConnection,
+        keys: &[Vec<u8>],
+    ) -> Result<(), CommandError> {
+        let mut seen = HashSet::new();
+        let new_keys: Vec<&Vec<u8>> = keys
+            .iter()
+            .filter(|key| !self.decided.contains_key(*key) && seen.insert(*key))
+            .collect();
+        if new_keys.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        for key in &new_keys {
+            pipe.cmd("EXISTS").arg(*key);
+        }
+        let exists: Vec<bool> = con
+            .query_pipeline(&pipe)
+            .await
+            .map_err(|e| CommandError::redis("Pipeline EXISTS error", e))?;
+
+        for (key, exists) in new_keys.into_iter().zip(exists) {
+            let taken = exists || self.claimed.contains(key);
+            let (action, target) = match (taken, self.policy) {
+                (false, _) => (ImportAction::Create, Some(key.clone())),
+                (true, ConflictPolicy::Skip) => (ImportAction::Skip, None),
+                (true, ConflictPolicy::Overwrite) => (ImportAction::Overwrite, Some(key.clone())),
+                (true, ConflictPolicy::Rename) => {
+                    (ImportAction::Rename, Some(self.free_name(con, key).await?))
+                }
+            };
+
+            let summary = &mut self.summary;
+            match action {
+                ImportAction::Create => summary.created += 1,
+                ImportAction::Overwrite => summary.overwritten += 1,
+                ImportAction::Rename => summary.renamed += 1,
+                ImportAction::Skip => summary.skipped += 1,
+            }
+            if summary.changes.len() < MAX_REPORTED_CHANGES {
+                summary.changes.push(ImportChange {
+                    key: RedisBytes::from(key.clone()),
+                    action,
+                    target: (action == ImportAction::Rename)
+                        .then(|| RedisBytes::from(target.clone().unwrap_or_default())),
+                });
+            }
+
+            if let Some(target) = &target {
+                self.claimed.insert(target.clone());
+            }
+            self.decided.insert(key.clone(), target);
+        }
+        Ok(())
+    }
+
+    /// Decides new keys and writes one batch, grouped by target key.
+    async fn import_batch(
+        &mut self,
+        con: &mut RedisConnection,
+        items: Vec<Item>,
+    ) -> Result<(), CommandError> {
+        let keys = items.iter().map(Item::key).collect::<Result<Vec<_>, _>>()?;
+        self.decide(con, &keys).await?;
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let mut groups: Vec<Vec<Cmd>> = Vec::new();
+        let mut group_of: HashMap<Vec<u8>, usize> = HashMap::new();
+
+        for (item, key) in items.into_iter().zip(keys) {
+            let Some(Some(target)) = self.decided.get(&key) else {
+                continue;
+            };
+            let cmds = match item {
+                Item::Record(record) => restore_commands(&record, target)?,
+                Item::Command(mut argv) => {
+                    argv[1] = target.clone();
+                    let mut cmd = redis::cmd(&String::from_utf8_lossy(&argv[0]));
+                    for arg in &argv[1..] {
+                        cmd.arg(arg);
+                    }
+                    vec![cmd]
+                }
+            };
+
+            let index = *group_of.entry(target.clone()).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[index].extend(cmds);
+        }
+
+        run_key_commands(con, groups).await
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn import_keys(
+    mut con: RedisConnection,
+    path: PathBuf,
+    format: ImportFormat,
+    policy: ConflictPolicy,
+    dry_run: bool,
+    job: &JobGuard,
+    on_progress: &Channel<ImportProgress>,
+) -> Result<ImportSummary, CommandError> {
+    let mut reader = tokio::task::spawn_blocking(move || Reader::open(path, format))
+        .await
+        .map_err(|e| join_error("Import", e))??;
+    let mut importer = Importer {
+        policy,
+        dry_run,
+        decided: HashMap::new(),
+        claimed: HashSet::new(),
+        summary: ImportSummary {
+            dry_run,
+            ..Default::default()
+        },
+    };
+
+    loop {
+        if job.is_cancelled() {
+            importer.summary.cancelled = true;
+            break;
+        }
+
+        let (returned, items) = tokio::task::spawn_blocking(move || {
+            let items = reader.read_batch(IMPORT_BATCH);
+            (reader, items)
+        })
+        .await
+        .map_err(|e| join_error("Import", e))?;
+        reader = returned;
+        let items = items?;
+        if items.is_empty() {
+            break;
+        }
+
+        importer.import_batch(&mut con, items).await?;
+        // The UI may have gone away, the import still completes
+        let _ = on_progress.send(ImportProgress {
+            keys: importer.decided.len() as u64,
+            bytes_read: reader.bytes_read,
+            total_bytes: reader.total_bytes,
+        });
+    }
+
+    Ok(importer.summary)
+}
@@ -0,0 +1,404 @@
+//! Parsing of the INFO reply into typed sections.
+//!
+//! Fields come and go between server versions, so every typed field is
+//! optional and the raw `key: value` pairs of all sections are kept as
+//! well for anything not modelled here.
+
+use crate::connection::RedisConnection;
+use crate::error::CommandError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// `key: value` pairs of one INFO section.
+pub type RawSection = BTreeMap<String, String>;
+
+#[derive(Serialize)]
+pub struct ServerSection {
+    pub redis_version: Option<String>,
+    pub redis_mode: Option<String>,
+    pub os: Option<String>,
+    pub arch_bits: Option<u32>,
+    pub process_id: Option<u64>,
+    pub tcp_port: Option<u16>,
+    pub uptime_in_seconds: Option<u64>,
+    pub hz: Option<u32>,
+    pub executable: Option<String>,
+    pub config_file: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ClientsSection {
+    pub connected_clients: Option<u64>,
+    pub blocked_clients: Option<u64>,
+    pub tracking_clients: Option<u64>,
+    pub maxclients: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct MemorySection {
+    pub used_memory: Option<u64>,
+    pub used_memory_rss: Option<u64>,
+    pub used_memory_peak: Option<u64>,
+    pub used_memory_dataset: Option<u64>,
+    pub used_memory_lua: Option<u64>,
+    pub total_system_memory: Option<u64>,
+    /// 0 when unlimited
+    pub maxmemory: Option<u64>,
+    pub maxmemory_policy: Option<String>,
+    pub mem_fragmentation_ratio: Option<f64>,
+    pub allocator_frag_ratio: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct PersistenceSection {
+    pub loading: Option<bool>,
+    pub rdb_changes_since_last_save: Option<u64>,
+    pub rdb_bgsave_in_progress: Option<bool>,
+    /// Unix seconds
+    pub rdb_last_save_time: Option<u64>,
+    pub rdb_last_bgsave_status: Option<String>,
+    pub aof_enabled: Option<bool>,
+    pub aof_rewrite_in_progress: Option<bool>,
+    pub aof_last_write_status: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct StatsSection {
+    pub total_connections_received: Option<u64>,
+    pub total_commands_processed: Option<u64>,
+    pub instantaneous_ops_per_sec: Option<u64>,
+    pub total_net_input_bytes: Option<u64>,
+    pub total_net_output_bytes: Option<u64>,
+    pub instantaneous_input_kbps: Option<f64>,
+    pub instantaneous_output_kbps: Option<f64>,
+    pub rejected_connections: Option<u64>,
+    pub expired_keys: Option<u64>,
+    pub evicted_keys: Option<u64>,
+    pub keyspace_hits: Option<u64>,
+    pub keyspace_misses: Option<u64>,
+    pub pubsub_channels: Option<u64>,
+    pub pubsub_patterns: Option<u64>,
+    pub total_error_replies: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ReplicaInfo {
+    pub ip: Option<String>,
+    pub port: Option<u16>,
+    pub state: Option<String>,
+    pub offset: Option<u64>,
+    pub lag: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ReplicationSection {
+    /// master or slave
+    pub role: Option<String>,
+    pub connected_slaves: Option<u64>,
+    pub master_host: Option<String>,
+    pub master_port: Option<u16>,
+    pub master_link_status: Option<String>,
+    pub master_repl_offset: Option<u64>,
+    pub replicas: Vec<ReplicaInfo>,
+}
+
+/// CPU seconds consumed since the server started.
+#[derive(Serialize)]
+pub struct CpuSection {
+    pub used_cpu_sys: Option<f64>,
+    pub used_cpu_user: Option<f64>,
+    pub used_cpu_sys_children: Option<f64>,
+    pub used_cpu_user_children: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct KeyspaceDb {
+    pub db: i64,
+    pub keys: u64,
+    pub expires: u64,
+    pub avg_ttl: u64,
+}
+
+#[derive(Serialize)]
+pub struct CommandStat {
+    pub command: String,
+    pub calls: u64,
+    pub usec: u64,
+    pub usec_per_call: f64,
+    pub rejected_calls: Option<u64>,
+    pub failed_calls: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ErrorStat {
+    /// Error prefix, e.g. `WRONGTYPE`
+    pub error: String,
+    pub count: u64,
+}
+
+/// Latency percentiles (microseconds) of one command, Redis 7+.
+#[derive(Serialize)]
+pub struct LatencyStat {
+    pub command: String,
+    pub p50: Option<f64>,
+    pub p99: Option<f64>,
+    pub p999: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct ServerInfo {
+    pub server: ServerSection,
+    pub clients: ClientsSection,
+    pub memory: MemorySection,
+    pub persistence: PersistenceSection,
+    pub stats: StatsSection,
+    pub replication: ReplicationSection,
+    pub cpu: CpuSection,
+    pub keyspace: Vec<KeyspaceDb>,
+    pub commandstats: Vec<CommandStat>,
+    pub errorstats: Vec<ErrorStat>,
+    pub latencystats: Vec<LatencyStat>,
+    /// Every section as sent, keyed by lowercase section name
+    pub sections: BTreeMap<String, RawSection>,
+}
+
+trait Field {
+    fn field<T: FromStr>(&self, key: &str) -> Option<T>;
+    fn flag(&self, key: &str) -> Option<bool>;
+}
+
+impl Field for RawSection {
+    fn field<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key)?.parse().ok()
+    }
+
+    fn flag(&self, key: &str) -> Option<bool> {
+        self.get(key).map(|v| v == "1")
+    }
+}
+
+/// `a=1,b=2` values of keyspace, commandstats and replica lines.
+fn nested(value: &str) -> RawSection {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+pub fn parse(text: &str) -> ServerInfo {
+    let mut sections: BTreeMap<String, RawSection> = BTreeMap::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix('#') {
+            current = name.trim().to_ascii_lowercase();
+            sections.entry(current.clone()).or_default();
+        } else if let Some((key, value)) = line.split_once(':') {
+            sections
+                .entry(current.clone())
+                .or_default()
+                .insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let empty = RawSection::new();
+    let section = |name: &str| sections.get(name).unwrap_or(&empty);
+
+    let s = section("server");
+    let server = ServerSection {
+        redis_version: s.field("redis_version"),
+        redis_mode: s.field("redis_mode"),
+        os: s.field("os"),
+        arch_bits: s.field("arch_bits"),
+        process_id: s.field("process_id"),
+        tcp_port: s.field("tcp_port"),
+        uptime_in_seconds: s.field("uptime_in_seconds"),
+        hz: s.field("hz"),
+        executable: s.field("executable"),
+        config_file: s.field("config_file"),
+    };
+
+    let s = section("clients");
+    let clients = ClientsSection {
+        connected_clients: s.field("connected_clients"),
+        blocked_clients: s.field("blocked_clients"),
+        tracking_clients: s.field("tracking_clients"),
+        maxclients: s.field("maxclients"),
+    };
+
+    let s = section("memory");
+    let memory = MemorySection {
+        used_memory: s.field("used_memory"),
+        used_memory_rss: s.field("used_memory_rss"),
+        used_memory_peak: s.field("used_memory_peak"),
+        used_memory_dataset: s.field("used_memory_dataset"),
+        used_memory_lua: s.field("used_memory_lua"),
+        total_system_memory: s.field("total_system_memory"),
+        maxmemory: s.field("maxmemory"),
+        maxmemory_policy: s.field("maxmemory_policy"),
+        mem_fragmentation_ratio: s.field("mem_fragmentation_ratio"),
+        allocator_frag_ratio: s.field("allocator_frag_ratio"),
+    };
+
+    let s = section("persistence");
+    let persistence = PersistenceSection {
+        loading: s.flag("loading"),
+        rdb_changes_since_last_save: s.field("rdb_changes_since_last_save"),
+        rdb_bgsave_in_progress: s.flag("rdb_bgsave_in_progress"),
+        rdb_last_save_time: s.field("rdb_last_save_time"),
+        rdb_last_bgsave_status: s.field("rdb_last_bgsave_status"),
+        aof_enabled: s.flag("aof_enabled"),
+        aof_rewrite_in_progress: s.flag("aof_rewrite_in_progress"),
+        aof_last_write_status: s.field("aof_last_write_status"),
+    };
+
+    let s = section("stats");
+    let stats = StatsSection {
+        total_connections_received: s.field("total_connections_received"),
+        total_commands_processed: s.field("total_commands_processed"),
+        instantaneous_ops_per_sec: s.field("instantaneous_ops_per_sec"),
+        total_net_input_bytes: s.field("total_net_input_bytes"),
+        total_net_output_bytes: s.field("total_net_output_bytes"),
+        instantaneous_input_kbps: s.field("instantaneous_input_kbps"),
+        instantaneous_output_kbps: s.field("instantaneous_output_kbps"),
+        rejected_connections: s.field("rejected_connections"),
+        expired_keys: s.field("expired_keys"),
+        evicted_keys: s.field("evicted_keys"),
+        keyspace_hits: s.field("keyspace_hits"),
+        keyspace_misses: s.field("keyspace_misses"),
+        pubsub_channels: s.field("pubsub_channels"),
+        pubsub_patterns: s.field("pubsub_patterns"),
+        total_error_replies: s.field("total_error_replies"),
+    };
+
+    let s = section("replication");
+    // slave0:ip=10.0.0.2,port=6380,state=online,offset=1234,lag=0
+    let replicas = s
+        .iter()
+        .filter(|(k, _)| {
+            k.strip_prefix("slave")
+                .is_some_and(|n| n.parse::<u32>().is_ok())
+        })
+        .map(|(_, v)| {
+            let r = nested(v);
+            ReplicaInfo {
+                ip: r.field("ip"),
+                port: r.field("port"),
+                state: r.field("state"),
+                offset: r.field("offset"),
+                lag: r.field("lag"),
+            }
+        })
+        .collect();
+    let replication = ReplicationSection {
+        role: s.field("role"),
+        connected_slaves: s.field("connected_slaves"),
+        master_host: s.field("master_host"),
+        master_port: s.field("master_port"),
+        master_link_status: s.field("master_link_status"),
+        master_repl_offset: s.field("master_repl_offset"),
+        replicas,
+    };
+
+    let s = section("cpu");
+    let cpu = CpuSection {
+        used_cpu_sys: s.field("used_cpu_sys"),
+        used_cpu_user: s.field("used_cpu_user"),
+        used_cpu_sys_children: s.field("used_cpu_sys_children"),
+        used_cpu_user_children: s.field("used_cpu_user_children"),
+    };
+
+    // db0:keys=1,expires=0,avg_ttl=0
+    let mut keyspace: Vec<KeyspaceDb> = section("keyspace")
+        .iter()
+        .filter_map(|(k, v)| {
+            let db = k.strip_prefix("db")?.parse().ok()?;
+            let d = nested(v);
+            Some(KeyspaceDb {
+                db,
+                keys: d.field("keys").unwrap_or(0),
+                expires: d.field("expires").unwrap_or(0),
+                avg_ttl: d.field("avg_ttl").unwrap_or(0),
+            })
+        })
+        .collect();
+    keyspace.sort_by_key(|d| d.db);
+
+    // cmdstat_get:calls=10,usec=25,usec_per_call=2.50,rejected_calls=0,failed_calls=0
+    let mut commandstats: Vec<CommandStat> = section("commandstats")
+        .iter()
+        .filter_map(|(k, v)| {
+            let c = nested(v);
+            Some(CommandStat {
+                command: k.strip_prefix("cmdstat_")?.to_string(),
+                calls: c.field("calls").unwrap_or(0),
+                usec: c.field("usec").unwrap_or(0),
+                usec_per_call: c.field("usec_per_call").unwrap_or(0.0),
+                rejected_calls: c.field("rejected_calls"),
+                failed_calls: c.field("failed_calls"),
+            })
+        })
+        .collect();
+    commandstats.sort_by_key(|c| std::cmp::Reverse(c.calls));
+
+    // errorstat_WRONGTYPE:count=3
+    let errorstats = section("errorstats")
+        .iter()
+        .filter_map(|(k, v)| {
+            Some(ErrorStat {
+                error: k.strip_prefix("errorstat_")?.to_string(),
+                count: nested(v).field("count").unwrap_or(0),
+            })
+        })
+        .collect();
+
+    // latency_percentiles_usec_get:p50=1.003,p99=2.007,p99.9=3.007
+    let latencystats = section("latencystats")
+        .iter()
+        .filter_map(|(k, v)| {
+            let l = nested(v);
+            Some(LatencyStat {
+                command: k.strip_prefix("latency_percentiles_usec_")?.to_string(),
+                p50: l.field("p50"),
+                p99: l.field("p99"),
+                p999: l.field("p99.9"),
+            })
+        })
+        .collect();
+
+    ServerInfo {
+        server,
+        clients,
+        memory,
+        persistence,
+        stats,
+        replication,
+        cpu,
+        keyspace,
+        commandstats,
+        errorstats,
+        latencystats,
+        sections,
+    }
+}
+
+/// INFO with every section but the module ones.
+pub async fn fetch(con: &mut RedisConnection) -> Result<ServerInfo, CommandError> {
+    fetch_sections(con, &["all"]).await
+}
+
+/// INFO restricted to `sections`, the server's default sections when empty.
+/// More than one section needs Redis 7.0.
+pub async fn fetch_sections(
+    con: &mut RedisConnection,
+    sections: &[&str],
+) -> Result<ServerInfo, CommandError> {
+    let info: redis::Value = redis::cmd("INFO")
+        .arg(sections)
+        .query_async(con)
+        .await
+        .map_err(|e| CommandError::redis("INFO error", e))?;
+    Ok(parse(&crate::info_text(info)?))
+}
@@ -0,0 +1,154 @@
+use crate::error::CommandError;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Running work keyed by an id the caller picks, so the UI can cancel or
+/// stop it before the command that started it returns. Shared by `Jobs`
+/// and `crate::tasks::Tasks`.
+pub struct Registry<T> {
+    kind: &'static str,
+    inner: Arc<Mutex<Entries<T>>>,
+}
+
+struct Entries<T> {
+    next_generation: u64,
+    /// Handle and the generation it was registered with
+    running: HashMap<String, (u64, T)>,
+}
+
+/// One registration, removed again when dropped unless the id has since
+/// been taken by a newer registration.
+pub struct Registration<T> {
+    id: String,
+    generation: u64,
+    inner: Arc<Mutex<Entries<T>>>,
+}
+
+impl<T> Registry<T> {
+    /// `kind` names the entries in errors ("Job", "Task").
+    pub fn new(kind: &'static str) -> Self {
+        Registry {
+            kind,
+            inner: Arc::new(Mutex::new(Entries {
+                next_generation: 0,
+                running: HashMap::new(),
+            })),
+        }
+    }
+
+    pub fn insert(&self, id: &str, handle: T) -> Result<Registration<T>, CommandError> {
+        let mut entries = self.inner.lock().unwrap();
+        let registration = self.reserve(&mut entries, id)?;
+        entries
+            .running
+            .insert(id.to_string(), (registration.generation, handle));
+        Ok(registration)
+    }
+
+    /// Registers the handle `make` builds from the registration. It runs
+    /// under the registry lock, so whatever it starts cannot finish (and
+    /// drop the registration) before the handle is stored.
+    pub fn insert_with(
+        &self,
+        id: &str,
+        make: impl FnOnce(Registration<T>) -> T,
+    ) -> Result<(), CommandError> {
+        let mut entries = self.inner.lock().unwrap();
+        let registration = self.reserve(&mut entries, id)?;
+        let generation = registration.generation;
+        let handle = make(registration);
+        entries.running.insert(id.to_string(), (generation, handle));
+        Ok(())
+    }
+
+    fn reserve(&self, entries: &mut Entries<T>, id: &str) -> Result<Registration<T>, CommandError> {
+        if entries.running.contains_key(id) {
+            return Err(CommandError::invalid(format!(
+                "{} {} is already running",
+                self.kind, id
+            )));
+        }
+        let generation = entries.next_generation;
+        entries.next_generation += 1;
+        Ok(Registration {
+            id: id.to_string(),
+            generation,
+            inner: self.inner.clone(),
+        })
+    }
+
+    /// Runs `f` on the handle of `id`. Returns false when nothing runs
+    /// under that id.
+    pub fn with(&self, id: &str, f: impl FnOnce(&T)) -> bool {
+        match self.inner.lock().unwrap().running.get(id) {
+            Some((_, handle)) => {
+                f(handle);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Unregisters `id` and returns its handle.
+    pub fn take(&self, id: &str) -> Option<T> {
+        let mut entries = self.inner.lock().unwrap();
+        entries.running.remove(id).map(|(_, handle)| handle)
+    }
+}
+
+impl<T> Drop for Registration<T> {
+    fn drop(&mut self) {
+        let mut entries = self.inner.lock().unwrap();
+        if entries
+            .running
+            .get(&self.id)
+            .is_some_and(|(generation, _)| *generation == self.generation)
+        {
+            entries.running.remove(&self.id);
+        }
+    }
+}
+
+/// Long-running commands (export, import, ...) that the UI can cancel.
+pub struct Jobs {
+    registry: Registry<Arc<AtomicBool>>,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Jobs {
+            registry: Registry::new("Job"),
+        }
+    }
+}
+
+/// Registration of a running job, removed again when dropped.
+pub struct JobGuard {
+    cancelled: Arc<AtomicBool>,
+    _registration: Registration<Arc<AtomicBool>>,
+}
+
+impl Jobs {
+    pub fn start(&self, id: &str) -> Result<JobGuard, CommandError> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let registration = self.registry.insert(id, cancelled.clone())?;
+        Ok(JobGuard {
+            cancelled,
+            _registration: registration,
+        })
+    }
+
+    /// Asks a job to stop at its next checkpoint. Returns false when no such
+    /// job is running (it may just have finished).
+    pub fn cancel(&self, id: &str) -> bool {
+        self.registry
+            .with(id, |cancelled| cancelled.store(true, Ordering::Relaxed))
+    }
+}
+
+impl JobGuard {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
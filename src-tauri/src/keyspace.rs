@@ -0,0 +1,589 @@
+//! Walking a keyspace in batches and reading whole keys, for bulk commands
+//! (export, ...) that work on far more keys than the key list shows.
+
+use crate::connection::{ConnectionManager, RedisConnection};
+use crate::error::{CommandError, ErrorKind};
+use crate::{
+    format_redis_bytes_list, format_redis_bytes_zset, format_stream_entries, RedisBytes,
+    StreamEntry,
+};
+use redis::Cmd;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// SCAN COUNT hint for bulk walks, larger than the key list uses
+const BULK_SCAN_COUNT: usize = 1000;
+/// Keys of an explicit list fetched per pipeline
+const KEY_LIST_BATCH: usize = 500;
+/// Elements per RPUSH/SADD/ZADD/HSET when a key is rebuilt
+pub const WRITE_CHUNK: usize = 100;
+
+/// Keys a bulk command works on.
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeySelection {
+    /// Every key matching a SCAN MATCH pattern (`*` for all)
+    Pattern(String),
+    Keys(Vec<RedisBytes>),
+}
+
+enum Source {
+    Scan {
+        nodes: Vec<RedisConnection>,
+        node: usize,
+        cursor: u64,
+        pattern: String,
+    },
+    List {
+        con: RedisConnection,
+        keys: std::vec::IntoIter<Vec<u8>>,
+    },
+}
+
+/// The keys of a `KeySelection`, one batch at a time. In cluster mode a
+/// pattern is scanned master by master.
+pub struct KeyBatches {
+    source: Source,
+    /// Number of keys, when known up front (an explicit list, or `*`)
+    pub total: Option<u64>,
+}
+
+impl KeyBatches {
+    pub async fn new(
+        state: &ConnectionManager,
+        connection_id: &str,
+        db: i64,
+        selection: KeySelection,
+    ) -> Result<Self, CommandError> {
+        match selection {
+            KeySelection::Keys(keys) => Ok(KeyBatches {
+                total: Some(keys.len() as u64),
+                source: Source::List {
+                    con: state.get_connection(connection_id, db).await?,
+                    keys: crate::decode_bytes_list(&keys)?.into_iter(),
+                },
+            }),
+            KeySelection::Pattern(pattern) => {
+                let mut nodes = state.get_scan_connections(connection_id, db, true).await?;
+                let pattern = if pattern.is_empty() {
+                    "*".to_string()
+                } else {
+                    pattern
+                };
+
+                let mut total = None;
+                if pattern == "*" {
+                    let mut sum = 0;
+                    for con in &mut nodes {
+                        let size: u64 = redis::cmd("DBSIZE")
+                            .query_async(con)
+                            .await
+                            .map_err(|e| CommandError::redis("DBSIZE error", e))?;
+                        sum += size;
+                    }
+                    total = Some(sum);
+                }
+
+                Ok(KeyBatches {
+                    total,
+                    source: Source::Scan {
+                        nodes,
+                        node: 0,
+                        cursor: 0,
+                        pattern,
+                    },
+                })
+            }
+        }
+    }
+
+    /// The next batch and the connection serving its keys, `None` once the
+    /// selection is exhausted. A pattern batch may be empty when a SCAN step
+    /// matched nothing; callers use it as a checkpoint all the same.
+    pub async fn next(&mut self) -> Result<Option<(RedisConnection, Vec<Vec<u8>>)>, CommandError> {
+        match &mut self.source {
+            Source::List { con, keys } => {
+                let batch: Vec<Vec<u8>> = keys.by_ref().take(KEY_LIST_BATCH).collect();
+                Ok((!batch.is_empty()).then(|| (con.clone(), batch)))
+            }
+            Source::Scan {
+                nodes,
+                node,
+                cursor,
+                pattern,
+            } => {
+                let Some(con) = nodes.get_mut(*node) else {
+                    return Ok(None);
+                };
+                let (next_cursor, batch): (u64, Vec<Vec<u8>>) = redis::cmd("SCAN")
+                    .arg(*cursor)
+                    .arg("MATCH")
+                    .arg(pattern.as_str())
+                    .arg("COUNT")
+                    .arg(BULK_SCAN_COUNT)
+                    .query_async(con)
+                    .await
+                    .map_err(|e| CommandError::redis("SCAN error", e))?;
+                let con = con.clone();
+
+                if next_cursor == 0 {
+                    *node += 1;
+                }
+                *cursor = next_cursor;
+                Ok(Some((con, batch)))
+            }
+        }
+    }
+}
+
+/// A whole key as written to and read from export files.
+#[derive(Serialize, Deserialize)]
+pub struct KeyRecord {
+    pub key: RedisBytes,
+    /// Milliseconds left to live, absent for persistent keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pttl: Option<i64>,
+    #[serde(flatten)]
+    pub value: KeyValue,
+}
+
+/// Full value of a key, tagged with its Redis TYPE name.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum KeyValue {
+    String(RedisBytes),
+    List(Vec<RedisBytes>),
+    Set(Vec<RedisBytes>),
+    Zset(#[serde(with = "zset_members")] Vec<(RedisBytes, f64)>),
+    Hash(Vec<(RedisBytes, RedisBytes)>),
+    Stream(Vec<StreamEntry>),
+}
+
+impl KeyValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            KeyValue::String(_) => "string",
+            KeyValue::List(_) => "list",
+            KeyValue::Set(_) => "set",
+            KeyValue::Zset(_) => "zset",
+            KeyValue::Hash(_) => "hash",
+            KeyValue::Stream(_) => "stream",
+        }
+    }
+}
+
+/// Sorted set members with their scores. JSON has no infinity, so `inf`
+/// and `-inf` scores are written as those strings instead of `null`.
+mod zset_members {
+    use crate::RedisBytes;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Score {
+        Number(f64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(
+        members: &[(RedisBytes, f64)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(members.iter().map(|(member, score)| {
+            let score = if score.is_finite() {
+                Score::Number(*score)
+            } else if *score > 0.0 {
+                Score::Text("inf".to_string())
+            } else {
+                Score::Text("-inf".to_string())
+            };
+            (member, score)
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(RedisBytes, f64)>, D::Error> {
+        Vec::<(RedisBytes, Score)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(member, score)| {
+                let score = match score {
+                    Score::Number(score) => score,
+                    Score::Text(text) => match text.to_ascii_lowercase().as_str() {
+                        "inf" | "+inf" => f64::INFINITY,
+                        "-inf" => f64::NEG_INFINITY,
+                        _ => return Err(de::Error::custom(format!("invalid score: {}", text))),
+                    },
+                };
+                Ok((member, score))
+            })
+            .collect()
+    }
+}
+
+/// Reads type, TTL and full value of every key in two pipelines. Keys that
+/// vanished in between or hold module types are left out.
+pub async fn fetch_records(
+    con: &mut RedisConnection,
+    keys: Vec<Vec<u8>>,
+) -> Result<Vec<KeyRecord>, CommandError> {
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.cmd("TYPE").arg(key).cmd("PTTL").arg(key);
+    }
+    let meta: Vec<(String, i64)> = con
+        .query_pipeline(&pipe)
+        .await
+        .map_err(|e| CommandError::redis("Pipeline TYPE/PTTL error", e))?;
+
+    let mut pipe = redis::pipe();
+    for (key, (key_type, _)) in keys.iter().zip(&meta) {
+        match key_type.as_str() {
+            "string" => pipe.cmd("GET").arg(key),
+            "list" => pipe.cmd("LRANGE").arg(key).arg(0).arg(-1),
+            "set" => pipe.cmd("SMEMBERS").arg(key),
+            "zset" => pipe.cmd("ZRANGE").arg(key).arg(0).arg(-1).arg("WITHSCORES"),
+            "hash" => pipe.cmd("HGETALL").arg(key),
+            "stream" => pipe.cmd("XRANGE").arg(key).arg("-").arg("+"),
+            _ => pipe.cmd("EXISTS").arg(key), // Dummy command to keep alignment
+        };
+    }
+    let values: Vec<redis::Value> = con
+        .query_pipeline(&pipe)
+        .await
+        .map_err(|e| CommandError::redis("Pipeline value error", e))?;
+
+    let mut records = Vec::with_capacity(keys.len());
+    for ((key, (key_type, pttl)), val) in keys.into_iter().zip(meta).zip(values) {
+        let value = match key_type.as_str() {
+            "string" => redis::from_redis_value::<Option<Vec<u8>>>(val)
+                .ok()
+                .flatten()
+                .map(|v| KeyValue::String(RedisBytes::from(v))),
+            "list" => redis::from_redis_value::<Vec<Vec<u8>>>(val)
+                .ok()
+                .map(|v| KeyValue::List(format_redis_bytes_list(v))),
+            "set" => redis::from_redis_value::<Vec<Vec<u8>>>(val)
+                .ok()
+                .map(|v| KeyValue::Set(format_redis_bytes_list(v))),
+            "zset" => redis::from_redis_value::<Vec<(Vec<u8>, f64)>>(val)
+                .ok()
+                .map(|v| KeyValue::Zset(format_redis_bytes_zset(v))),
+            "hash" => redis::from_redis_value::<HashMap<Vec<u8>, Vec<u8>>>(val)
+                .ok()
+                .map(|v| {
+                    KeyValue::Hash(
+                        v.into_iter()
+                            .map(|(f, v)| (RedisBytes::from(f), RedisBytes::from(v)))
+                            .collect(),
+                    )
+                }),
+            "stream" => redis::from_redis_value::<Vec<(String, Vec<Vec<u8>>)>>(val)
+                .ok()
+                .map(|v| KeyValue::Stream(format_stream_entries(v))),
+            _ => None,
+        };
+
+        // An empty collection means the key was deleted after TYPE
+        let vanished = match &value {
+            Some(KeyValue::List(v) | KeyValue::Set(v)) => v.is_empty(),
+            Some(KeyValue::Zset(v)) => v.is_empty(),
+            Some(KeyValue::Hash(v)) => v.is_empty(),
+            _ => false,
+        };
+        if let (Some(value), false) = (value, vanished) {
+            records.push(KeyRecord {
+                key: RedisBytes::from(key),
+                pttl: (pttl >= 0).then_some(pttl),
+                value,
+            });
+        }
+    }
+    Ok(records)
+}
+
+fn key_cmd(name: &str, key: &[u8]) -> Cmd {
+    let mut cmd = redis::cmd(name);
+    cmd.arg(key);
+    cmd
+}
+
+/// `name key <args of items>` commands, `WRITE_CHUNK` items each.
+fn chunked<T>(
+    name: &str,
+    key: &[u8],
+    items: &[T],
+    item_args: impl Fn(&mut Cmd, &T) -> Result<(), CommandError>,
+) -> Result<Vec<Cmd>, CommandError> {
+    let mut cmds = Vec::new();
+    for chunk in items.chunks(WRITE_CHUNK) {
+        let mut cmd = key_cmd(name, key);
+        for item in chunk {
+            item_args(&mut cmd, item)?;
+        }
+        cmds.push(cmd);
+    }
+    Ok(cmds)
+}
+
+/// Commands that recreate `record` under `key`, replacing whatever is there.
+pub fn restore_commands(record: &KeyRecord, key: &[u8]) -> Result<Vec<Cmd>, CommandError> {
+    let mut cmds = vec![key_cmd("DEL", key)];
+
+    match &record.value {
+        KeyValue::String(value) => {
+            let mut cmd = key_cmd("SET", key);
+            cmd.arg(value.to_bytes()?);
+            cmds.push(cmd);
+        }
+        KeyValue::List(items) => cmds.extend(chunked("RPUSH", key, items, |cmd, item| {
+            cmd.arg(item.to_bytes()?);
+            Ok(())
+        })?),
+        KeyValue::Set(members) => cmds.extend(chunked("SADD", key, members, |cmd, member| {
+            cmd.arg(member.to_bytes()?);
+            Ok(())
+        })?),
+        KeyValue::Zset(members) => {
+            cmds.extend(chunked("ZADD", key, members, |cmd, (member, score)| {
+                cmd.arg(*score).arg(member.to_bytes()?);
+                Ok(())
+            })?)
+        }
+        KeyValue::Hash(fields) => {
+            cmds.extend(chunked("HSET", key, fields, |cmd, (field, value)| {
+                cmd.arg(field.to_bytes()?).arg(value.to_bytes()?);
+                Ok(())
+            })?)
+        }
+        KeyValue::Stream(entries) => {
+            for entry in entries {
+                let mut cmd = key_cmd("XADD", key);
+                cmd.arg(&entry.id);
+                for (field, value) in &entry.fields {
+                    cmd.arg(field.to_bytes()?).arg(value.to_bytes()?);
+                }
+                cmds.push(cmd);
+            }
+            // XADD cannot create an empty stream, add an entry and trim it away
+            if entries.is_empty() {
+                let mut cmd = key_cmd("XADD", key);
+                cmd.arg("MAXLEN").arg(0).arg("0-1").arg("_").arg("");
+                cmds.push(cmd);
+            }
+        }
+    }
+
+    if let Some(pttl) = record.pttl {
+        let mut cmd = key_cmd("PEXPIRE", key);
+        cmd.arg(pttl);
+        cmds.push(cmd);
+    }
+    Ok(cmds)
+}
+
+/// Runs groups of commands, each group in order. A single node gets one
+/// pipeline; in cluster mode every group is its own pipeline (a group must
+/// only touch one hash slot), sent concurrently.
+pub async fn run_key_commands(
+    con: &mut RedisConnection,
+    groups: Vec<Vec<Cmd>>,
+) -> Result<(), CommandError> {
+    let pipeline = |cmds: Vec<Cmd>| {
+        let mut pipe = redis::pipe();
+        for cmd in cmds {
+            pipe.add_command(cmd).ignore();
+        }
+        pipe
+    };
+
+    match con {
+        RedisConnection::Single(con) => pipeline(groups.into_iter().flatten().collect())
+            .exec_async(con)
+            .await
+            .map_err(|e| CommandError::redis("Pipeline write error", e)),
+        RedisConnection::Cluster(con) => {
+            let mut tasks = tokio::task::JoinSet::new();
+            for group in groups {
+                let pipe = pipeline(group);
+                let mut con = con.clone();
+                tasks.spawn(async move { pipe.exec_async(&mut con).await });
+            }
+            while let Some(joined) = tasks.join_next().await {
+                joined
+                    .map_err(|e| {
+                        CommandError::new(ErrorKind::Internal, format!("Write task failed: {}", e))
+                    })?
+                    .map_err(|e| CommandError::redis("Pipeline write error", e))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Separator of the segments of a key name (`user:42:profile`), the one
+/// the key tree uses. Memory analysis totals keys per prefix, the slowlog
+/// groups them per pattern.
+pub struct KeyDelimiter(String);
+
+impl KeyDelimiter {
+    pub fn new(delimiter: String) -> Result<Self, CommandError> {
+        if delimiter.is_empty() {
+            return Err(CommandError::invalid("The key delimiter must not be empty"));
+        }
+        Ok(KeyDelimiter(delimiter))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Offsets of the delimiters in `key`, where its prefixes end.
+    pub fn prefix_ends<'a>(&'a self, key: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let delimiter = self.0.as_bytes();
+        key.windows(delimiter.len())
+            .enumerate()
+            .filter(move |(_, w)| *w == delimiter)
+            .map(|(i, _)| i)
+    }
+}
+
+/// Redis glob matching as used by SCAN MATCH: `*`, `?`, `[abc]`, `[^a-z]`
+/// and `\` escapes. Iterative: on a mismatch only the last `*` takes one
+/// more byte, so patterns like `*a*a*b` stay linear in practice.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Pattern position after the last `*` and the string position it
+    // resumes from
+    let mut star: Option<(usize, usize)> = None;
+    while s < string.len() {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            star = Some((p, s));
+            continue;
+        }
+        if let Some(next) = match_token(pattern, p, string[s]) {
+            p = next;
+            s += 1;
+            continue;
+        }
+        let Some((star_p, star_s)) = star else {
+            return false;
+        };
+        p = star_p;
+        s = star_s + 1;
+        star = Some((star_p, s));
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Matches `c` against the one-byte token at `p` (`?`, a class, an escaped
+/// or a plain byte). Returns where the next token starts.
+fn match_token(pattern: &[u8], mut p: usize, c: u8) -> Option<usize> {
+    match *pattern.get(p)? {
+        b'?' => Some(p + 1),
+        b'[' => {
+            p += 1;
+            let negate = pattern.get(p) == Some(&b'^');
+            if negate {
+                p += 1;
+            }
+            let mut matched = false;
+            loop {
+                match pattern.get(p) {
+                    // Unterminated class, match up to the end
+                    None => break,
+                    Some(b']') => {
+                        p += 1;
+                        break;
+                    }
+                    Some(b'\\') if p + 1 < pattern.len() => {
+                        p += 1;
+                        matched |= pattern[p] == c;
+                    }
+                    Some(&from) if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                        let to = pattern[p + 2];
+                        p += 2;
+                        matched |= (from.min(to)..=from.max(to)).contains(&c);
+                    }
+                    Some(&other) => matched |= other == c,
+                }
+                p += 1;
+            }
+            (matched != negate).then_some(p)
+        }
+        b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        other => (other == c).then_some(p + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_match, KeyRecord, KeyValue};
+    use crate::RedisBytes;
+
+    fn matches(pattern: &str, string: &str) -> bool {
+        glob_match(pattern.as_bytes(), string.as_bytes())
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("user:*", "user:42"));
+        assert!(matches("*:42", "user:42"));
+        assert!(matches("u*r:*2", "user:42"));
+        assert!(matches("user:??", "user:42"));
+        assert!(!matches("user:?", "user:42"));
+        assert!(!matches("user:*", "session:1"));
+        assert!(matches("a**b", "ab"));
+        assert!(!matches("a*b", "abc"));
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert!(matches("h[ae]llo", "hallo"));
+        assert!(!matches("h[ae]llo", "hillo"));
+        assert!(matches("h[^e]llo", "hallo"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(matches("h[a-c]llo", "hbllo"));
+        assert!(matches("h[c-a]llo", "hbllo"));
+        assert!(matches("h[\\]]llo", "h]llo"));
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        // Unterminated class
+        assert!(matches("a[bc", "ab"));
+    }
+
+    #[test]
+    fn backtracking_stays_linear() {
+        let string = "a".repeat(10_000);
+        assert!(!matches("*a*a*a*a*a*a*a*a*b", &string));
+        assert!(matches("*a*a*a*a*a*a*a*a*", &string));
+    }
+
+    #[test]
+    fn infinite_scores_survive_ndjson() {
+        let record = KeyRecord {
+            key: RedisBytes::Utf8("ranks".to_string()),
+            pttl: None,
+            value: KeyValue::Zset(vec![
+                (RedisBytes::Utf8("low".to_string()), f64::NEG_INFINITY),
+                (RedisBytes::Utf8("mid".to_string()), 1.5),
+                (RedisBytes::Utf8("high".to_string()), f64::INFINITY),
+            ]),
+        };
+        let line = serde_json::to_string(&record).unwrap();
+        assert!(line.contains(r#""-inf""#) && !line.contains("null"));
+
+        let read: KeyRecord = serde_json::from_str(&line).unwrap();
+        let KeyValue::Zset(members) = read.value else {
+            panic!("not a zset");
+        };
+        let scores: Vec<f64> = members.iter().map(|(_, score)| *score).collect();
+        assert_eq!(scores, [f64::NEG_INFINITY, 1.5, f64::INFINITY]);
+    }
+}
@@ -1,135 +1,246 @@
+mod connection;
+mod console;
+mod copy;
+mod decoder;
+mod delete;
+mod error;
+mod export;
+mod import;
+mod info;
+mod jobs;
+mod keyspace;
+mod memory;
+mod monitor;
+mod pubsub;
+mod rdb;
+mod safety;
+mod sampler;
+mod slowlog;
+mod ssh_tunnel;
+mod tasks;
+
 use base64::{engine::general_purpose, Engine as _};
+use connection::{ConnectionInfo, ConnectionManager, RedisConfig, RedisConnection, SentinelStatus};
+use console::Reply;
+use copy::{CopyOptions, CopyProgress, CopySummary};
+use decoder::{Codec, DecodeOptions, Decoded, ProtoSchema};
+use delete::{DeleteProgress, DeleteSummary};
+use error::{is_unknown_command, CommandError, ErrorKind};
+use export::{ExportFormat, ExportProgress, ExportSummary};
+use import::{ConflictPolicy, ImportFormat, ImportProgress, ImportSummary};
+use info::ServerInfo;
+use jobs::Jobs;
+use keyspace::{KeyBatches, KeyDelimiter, KeySelection};
+use memory::{MemoryOptions, MemoryReport};
+use monitor::{MonitorEvent, MonitorFilter};
+use pubsub::{PubSubEvent, Subscriptions};
+use rdb::{RdbFile, RdbSummary};
 use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
+use slowlog::SlowlogReport;
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tasks::Tasks;
 use tauri::State;
-use tokio::sync::Mutex;
-
-#[derive(Deserialize, Serialize, Clone)]
-pub struct RedisConfig {
-    pub host: String,
-    pub port: String,
-    pub username: Option<String>,
-    pub password: Option<String>,
-    #[serde(rename = "enableSSL")]
-    pub enable_ssl: bool,
-}
 
 #[derive(Serialize)]
 #[serde(tag = "type", content = "value")]
 pub enum RedisValue {
     String(String),
     Binary(String), // Base64 encoded
-    List(Vec<String>),
-    Set(Vec<String>),
-    ZSet(Vec<(String, f64)>),
-    Hash(HashMap<String, String>),
+    List(Vec<RedisBytes>),
+    Set(Vec<RedisBytes>),
+    ZSet(Vec<(RedisBytes, f64)>),
+    /// Field/value pairs; a JSON object would need UTF-8 field names
+    Hash(Vec<(RedisBytes, RedisBytes)>),
+    Stream(StreamValue),
     None,
 }
 
-impl RedisConfig {
-    fn to_url(&self) -> String {
-        let protocol = if self.enable_ssl { "rediss" } else { "redis" };
-        let auth = match (&self.username, &self.password) {
-            (Some(u), Some(p)) if !u.is_empty() && !p.is_empty() => format!("{}:{}@", u, p),
-            (None, Some(p)) if !p.is_empty() => format!(":{}@", p),
-            (Some(u), None) if !u.is_empty() => format!("{}@", u),
-            _ => "".to_string(),
-        };
-        format!("{}://{}{}:{}", protocol, auth, self.host, self.port)
+/// A list element, set member, hash field or value as exchanged with the
+/// UI. Valid UTF-8 travels as text, anything else as Base64, and write
+/// commands accept the same shape back so binary data is never mangled.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "encoding", content = "data", rename_all = "lowercase")]
+pub enum RedisBytes {
+    Utf8(String),
+    Base64(String),
+}
+
+impl RedisBytes {
+    fn to_bytes(&self) -> Result<Vec<u8>, CommandError> {
+        match self {
+            RedisBytes::Utf8(s) => Ok(s.as_bytes().to_vec()),
+            RedisBytes::Base64(s) => general_purpose::STANDARD
+                .decode(s)
+                .map_err(|e| CommandError::invalid(format!("Invalid Base64 value: {}", e))),
+        }
     }
 }
 
-#[derive(Default)]
-pub struct ConnectionManager {
-    state: Mutex<ConnectionState>,
+impl From<Vec<u8>> for RedisBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(s) => RedisBytes::Utf8(s),
+            Err(e) => RedisBytes::Base64(general_purpose::STANDARD.encode(e.into_bytes())),
+        }
+    }
 }
 
-#[derive(Default)]
-struct ConnectionState {
-    connections: HashMap<i64, redis::aio::MultiplexedConnection>,
-    config: Option<RedisConfig>,
+impl fmt::Display for RedisBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedisBytes::Utf8(s) => write!(f, "{:?}", s),
+            RedisBytes::Base64(s) => write!(f, "base64:{}", s),
+        }
+    }
 }
 
-impl ConnectionManager {
-    async fn get_connection(
-        &self,
-        new_config: &RedisConfig,
-        db: i64,
-    ) -> Result<redis::aio::MultiplexedConnection, String> {
-        let mut state = self.state.lock().await;
+fn decode_bytes_list(items: &[RedisBytes]) -> Result<Vec<Vec<u8>>, CommandError> {
+    items.iter().map(RedisBytes::to_bytes).collect()
+}
 
-        let config_changed = match &state.config {
-            Some(existing) => {
-                existing.host != new_config.host
-                    || existing.port != new_config.port
-                    || existing.username != new_config.username
-                    || existing.password != new_config.password
-                    || existing.enable_ssl != new_config.enable_ssl
-            }
-            None => true,
-        };
+/// Number of list/set/zset/hash elements returned with the first page of a
+/// key. The rest is fetched on demand with the range/scan commands.
+const VALUE_PAGE_SIZE: usize = 500;
 
-        if config_changed {
-            state.connections.clear();
-            state.config = Some(new_config.clone());
-        }
+/// Number of stream entries returned per XRANGE page.
+const STREAM_PAGE_SIZE: usize = 100;
 
-        if let Some(conn) = state.connections.get(&db) {
-            return Ok(conn.clone());
-        }
+#[derive(Serialize, Deserialize)]
+pub struct StreamEntry {
+    pub id: String,
+    pub fields: Vec<(RedisBytes, RedisBytes)>,
+}
 
-        // Create new connection for this DB
-        let client = redis::Client::open(new_config.to_url())
-            .map_err(|e| format!("Failed to create Redis client: {}", e))?;
+/// Metadata from XINFO STREAM.
+#[derive(Serialize, Default)]
+pub struct StreamInfo {
+    pub length: i64,
+    pub radix_tree_keys: i64,
+    pub radix_tree_nodes: i64,
+    pub last_generated_id: String,
+    pub first_entry_id: Option<String>,
+    pub last_entry_id: Option<String>,
+    pub groups: i64,
+}
 
-        let mut conn = client
-            .get_multiplexed_async_connection()
-            .await
-            .map_err(|e| format!("Connection failed: {}", e))?;
+/// One row of XINFO GROUPS.
+#[derive(Serialize)]
+pub struct StreamConsumerGroup {
+    pub name: String,
+    pub consumers: i64,
+    pub pending: i64,
+    pub last_delivered_id: String,
+    pub lag: Option<i64>,
+}
 
-        // SELECT the DB immediately for this connection
-        let _: () = redis::cmd("SELECT")
-            .arg(db)
-            .query_async(&mut conn)
-            .await
-            .map_err(|e| format!("Failed to select DB {}: {}", db, e))?;
+/// One page of `stream_range`.
+#[derive(Serialize)]
+pub struct StreamPage {
+    pub entries: Vec<StreamEntry>,
+    /// ID to pass as `start` for the next page, `None` when exhausted
+    pub next_id: Option<String>,
+}
 
-        state.connections.insert(db, conn.clone());
-        Ok(conn)
-    }
+#[derive(Serialize)]
+pub struct StreamValue {
+    pub entries: Vec<StreamEntry>,
+    /// ID to pass to `stream_range` for the next page, `None` when exhausted
+    pub next_id: Option<String>,
+    pub info: Option<StreamInfo>,
+    pub groups: Vec<StreamConsumerGroup>,
 }
 
+/// Connects and returns the id every other command takes.
 #[tauri::command]
-async fn connect_redis(
+async fn open_connection(
     config: RedisConfig,
     state: State<'_, ConnectionManager>,
-) -> Result<String, String> {
-    let mut con = state.get_connection(&config, 0).await?;
-    let response: String = redis::cmd("PING")
+) -> Result<String, CommandError> {
+    let connection_id = state.open(config).await?;
+    let mut con = state.get_connection(&connection_id, 0).await?;
+    let response: Result<String, CommandError> = redis::cmd("PING")
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("Failed to execute PING: {}", e))?;
-
-    if response == "PONG" {
-        Ok("Successfully connected to Redis server!".to_string())
-    } else {
-        Ok(format!("Unexpected response: {}", response))
+        .map_err(|e| CommandError::redis("Failed to execute PING", e));
+
+    match response {
+        Ok(response) if response == "PONG" => Ok(connection_id),
+        Ok(response) => {
+            state.close(&connection_id).await?;
+            Err(CommandError::new(
+                ErrorKind::Server,
+                format!("Unexpected response: {}", response),
+            ))
+        }
+        Err(e) => {
+            state.close(&connection_id).await?;
+            Err(e)
+        }
     }
 }
 
+/// INFO is sent to every node by cluster connections, which reply with a
+/// node -> text map. Any single node is good enough for our callers, as
+/// long as it is the same one each time (the sampler diffs counters).
+fn info_text(value: redis::Value) -> Result<String, CommandError> {
+    let value = match value {
+        redis::Value::Map(nodes) => nodes
+            .into_iter()
+            .min_by_key(|(node, _)| redis::from_redis_value::<String>(node.clone()).ok())
+            .map(|(_, v)| v)
+            .unwrap_or(redis::Value::Nil),
+        v => v,
+    };
+    redis::from_redis_value::<String>(value).map_err(|e| CommandError::parse("INFO parse error", e))
+}
+
+/// Indexes an RDB file for offline browsing. The returned id works with
+/// the key listing and value commands like a (read-only) connection id.
+#[tauri::command]
+async fn open_rdb(
+    path: String,
+    state: State<'_, ConnectionManager>,
+) -> Result<RdbSummary, CommandError> {
+    let rdb = Arc::new(rdb::blocking(move || RdbFile::open(&path)).await?);
+    let connection_id = state.open_rdb(rdb.clone()).await;
+    Ok(rdb.summary(connection_id))
+}
+
+/// Closes the pooled connections (and SSH tunnel) of one session.
+#[tauri::command]
+async fn close_connection(
+    connection_id: String,
+    state: State<'_, ConnectionManager>,
+) -> Result<(), CommandError> {
+    state.close(&connection_id).await
+}
+
+#[tauri::command]
+async fn list_connections(
+    state: State<'_, ConnectionManager>,
+) -> Result<Vec<ConnectionInfo>, CommandError> {
+    Ok(state.list().await)
+}
+
 #[tauri::command]
 async fn get_redis_version(
-    config: RedisConfig,
+    connection_id: String,
     state: State<'_, ConnectionManager>,
-) -> Result<String, String> {
-    let mut con = state.get_connection(&config, 0).await?;
+) -> Result<String, CommandError> {
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb.redis_version();
+    }
+    let mut con = state.get_connection(&connection_id, 0).await?;
 
-    let info: String = redis::cmd("INFO")
+    let info: redis::Value = redis::cmd("INFO")
         .arg("server")
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("INFO error: {}", e))?;
+        .map_err(|e| CommandError::redis("INFO error", e))?;
+    let info = info_text(info)?;
 
     // Parse redis_version from INFO output
     for line in info.lines() {
@@ -138,19 +249,33 @@ async fn get_redis_version(
         }
     }
 
-    Err("Could not determine Redis version".to_string())
+    Err(CommandError::new(
+        ErrorKind::Parse,
+        "Could not determine Redis version",
+    ))
+}
+
+#[tauri::command]
+async fn get_sentinel_status(
+    connection_id: String,
+    state: State<'_, ConnectionManager>,
+) -> Result<SentinelStatus, CommandError> {
+    state.sentinel_status(&connection_id).await
 }
 
 #[tauri::command]
 async fn hash_set_field_ttl(
-    config: RedisConfig,
+    connection_id: String,
     db: i64,
-    key: String,
-    fields: Vec<String>,
+    key: RedisBytes,
+    fields: Vec<RedisBytes>,
     ttl: i64,
     state: State<'_, ConnectionManager>,
-) -> Result<(), String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<(), CommandError> {
+    state.ensure_writable(&connection_id, "HEXPIRE").await?;
+    let key = key.to_bytes()?;
+    let fields = decode_bytes_list(&fields)?;
+    let mut con = state.get_connection(&connection_id, db).await?;
 
     // HEXPIRE key seconds FIELDS count field1 field2 ...
     let mut cmd = redis::cmd("HEXPIRE");
@@ -163,27 +288,46 @@ async fn hash_set_field_ttl(
     let _: Vec<i64> = cmd
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("HEXPIRE error: {}", e))?;
+        .map_err(|e| CommandError::redis("HEXPIRE error", e))?;
 
     Ok(())
 }
 
 #[derive(Serialize)]
 pub struct RedisKeyInfo {
-    pub name: String,
+    pub name: RedisBytes,
     pub key_type: String,
 }
 
+/// Splits a `list_keys` cursor into (node index, node SCAN cursor).
+/// Single nodes use the plain SCAN cursor; cluster cursors are `node:cursor`.
+fn parse_scan_cursor(cursor: &str) -> Result<(usize, u64), CommandError> {
+    let parsed = match cursor.split_once(':') {
+        Some((node, c)) => node.parse().ok().zip(c.parse().ok()),
+        None => cursor.parse().ok().map(|c| (0, c)),
+    };
+    parsed.ok_or_else(|| CommandError::invalid(format!("Invalid SCAN cursor: {}", cursor)))
+}
+
 #[tauri::command]
 async fn list_keys(
-    config: RedisConfig,
+    connection_id: String,
     db: i64,
-    cursor: u64,
+    cursor: String,
     pattern: String,
     current_count: usize,
     state: State<'_, ConnectionManager>,
-) -> Result<(u64, Vec<RedisKeyInfo>), String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<(String, Vec<RedisKeyInfo>), CommandError> {
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb::blocking(move || rdb.list_keys(db, &cursor, &pattern)).await;
+    }
+    let nodes = state
+        .get_scan_connections(&connection_id, db, cursor == "0")
+        .await?;
+    let (node_idx, node_cursor) = parse_scan_cursor(&cursor)?;
+    let mut con = nodes.get(node_idx).cloned().ok_or_else(|| {
+        CommandError::invalid(format!("SCAN cursor points to unknown node {}", node_idx))
+    })?;
 
     // Dynamic COUNT only for pattern search (not for listing all keys)
     let has_pattern = !pattern.is_empty() && pattern != "*";
@@ -205,15 +349,15 @@ async fn list_keys(
         2000
     };
 
-    let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
-        .arg(cursor)
+    let (next_cursor, batch): (u64, Vec<Vec<u8>>) = redis::cmd("SCAN")
+        .arg(node_cursor)
         .arg("MATCH")
         .arg(if pattern.is_empty() { "*" } else { &pattern })
         .arg("COUNT")
         .arg(scan_count)
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("SCAN error: {}", e))?;
+        .map_err(|e| CommandError::redis("SCAN error", e))?;
 
     let mut keys = Vec::new();
     if !batch.is_empty() {
@@ -226,13 +370,27 @@ async fn list_keys(
         let types: Vec<String> = pipe
             .query_async(&mut con)
             .await
-            .map_err(|e| format!("Pipeline TYPE error: {}", e))?;
+            .map_err(|e| CommandError::redis("Pipeline TYPE error", e))?;
 
-        for (name, key_type) in batch.into_iter().zip(types.into_iter()) {
-            keys.push(RedisKeyInfo { name, key_type });
+        for (name, key_type) in batch.into_iter().zip(types) {
+            keys.push(RedisKeyInfo {
+                name: RedisBytes::from(name),
+                key_type,
+            });
         }
     }
 
+    // When a cluster master is exhausted, continue with the next one
+    let next_cursor = if nodes.len() == 1 {
+        next_cursor.to_string()
+    } else if next_cursor != 0 {
+        format!("{}:{}", node_idx, next_cursor)
+    } else if node_idx + 1 < nodes.len() {
+        format!("{}:0", node_idx + 1)
+    } else {
+        "0".to_string()
+    };
+
     Ok((next_cursor, keys))
 }
 
@@ -240,99 +398,258 @@ async fn list_keys(
 pub struct RedisKeyData {
     pub key_type: String,
     pub value: RedisValue,
+    /// Total number of elements (or bytes for strings), independent of
+    /// how much of the value was returned
+    pub length: i64,
     pub ttl: i64,
     pub memory: i64,
     pub encoding: String,
 }
 
-fn format_redis_bytes(bytes: Vec<u8>) -> (String, bool) {
-    match String::from_utf8(bytes) {
-        Ok(s) => (s, true),
-        Err(e) => (general_purpose::STANDARD.encode(e.into_bytes()), false),
+fn format_redis_string(bytes: Vec<u8>) -> RedisValue {
+    match RedisBytes::from(bytes) {
+        RedisBytes::Utf8(s) => RedisValue::String(s),
+        RedisBytes::Base64(s) => RedisValue::Binary(s),
     }
 }
 
-// Updated formatters for other types
-fn format_redis_bytes_list(bytes_list: Vec<Vec<u8>>) -> Vec<String> {
-    bytes_list
-        .into_iter()
-        .map(|b| format_redis_bytes(b).0)
-        .collect()
+fn format_redis_bytes_list(bytes_list: Vec<Vec<u8>>) -> Vec<RedisBytes> {
+    bytes_list.into_iter().map(RedisBytes::from).collect()
 }
 
-fn format_redis_bytes_zset(zset: Vec<(Vec<u8>, f64)>) -> Vec<(String, f64)> {
+fn format_redis_bytes_zset(zset: Vec<(Vec<u8>, f64)>) -> Vec<(RedisBytes, f64)> {
     zset.into_iter()
-        .map(|(b, s)| (format_redis_bytes(b).0, s))
+        .map(|(b, s)| (RedisBytes::from(b), s))
         .collect()
 }
 
-fn format_redis_bytes_hash(hash: HashMap<String, Vec<u8>>) -> HashMap<String, String> {
+fn format_redis_bytes_hash(hash: HashMap<Vec<u8>, Vec<u8>>) -> Vec<(RedisBytes, RedisBytes)> {
     hash.into_iter()
-        .map(|(k, v)| (k, format_redis_bytes(v).0))
+        .map(|(k, v)| (RedisBytes::from(k), RedisBytes::from(v)))
+        .collect()
+}
+
+// HSCAN / XRANGE style flat [field, value, field, value, ...] reply
+fn format_redis_bytes_pairs(flat: Vec<Vec<u8>>) -> Vec<(RedisBytes, RedisBytes)> {
+    let mut pairs = Vec::with_capacity(flat.len() / 2);
+    let mut iter = flat.into_iter();
+    while let (Some(f), Some(v)) = (iter.next(), iter.next()) {
+        pairs.push((RedisBytes::from(f), RedisBytes::from(v)));
+    }
+    pairs
+}
+
+/// Command that returns the cardinality of a key of the given type.
+fn length_command(key_type: &str) -> Option<&'static str> {
+    match key_type {
+        "string" => Some("STRLEN"),
+        "list" => Some("LLEN"),
+        "set" => Some("SCARD"),
+        "zset" => Some("ZCARD"),
+        "hash" => Some("HLEN"),
+        "stream" => Some("XLEN"),
+        _ => None,
+    }
+}
+
+fn format_stream_entries(raw: Vec<(String, Vec<Vec<u8>>)>) -> Vec<StreamEntry> {
+    raw.into_iter()
+        .map(|(id, flat)| StreamEntry {
+            id,
+            fields: format_redis_bytes_pairs(flat),
+        })
+        .collect()
+}
+
+/// XRANGE is queried with `count + 1`: the extra entry only tells us where
+/// the next page starts and is dropped from the result.
+fn split_stream_page(mut entries: Vec<StreamEntry>, count: usize) -> StreamPage {
+    let next_id = if entries.len() > count {
+        let next = entries.pop().map(|e| e.id);
+        entries.truncate(count);
+        next
+    } else {
+        None
+    };
+    StreamPage { entries, next_id }
+}
+
+fn stream_info_int(map: &HashMap<String, redis::Value>, field: &str) -> Option<i64> {
+    map.get(field)
+        .and_then(|v| redis::from_redis_value::<Option<i64>>(v.clone()).ok())
+        .flatten()
+}
+
+fn stream_info_string(map: &HashMap<String, redis::Value>, field: &str) -> Option<String> {
+    map.get(field)
+        .and_then(|v| redis::from_redis_value::<Option<String>>(v.clone()).ok())
+        .flatten()
+}
+
+// first-entry / last-entry are [id, [field, value, ...]] or nil
+fn stream_info_entry_id(map: &HashMap<String, redis::Value>, field: &str) -> Option<String> {
+    map.get(field)
+        .and_then(|v| v.as_sequence())
+        .and_then(|s| s.first())
+        .and_then(|id| redis::from_redis_value::<String>(id.clone()).ok())
+}
+
+fn parse_stream_info(map: HashMap<String, redis::Value>) -> StreamInfo {
+    StreamInfo {
+        length: stream_info_int(&map, "length").unwrap_or(0),
+        radix_tree_keys: stream_info_int(&map, "radix-tree-keys").unwrap_or(0),
+        radix_tree_nodes: stream_info_int(&map, "radix-tree-nodes").unwrap_or(0),
+        last_generated_id: stream_info_string(&map, "last-generated-id").unwrap_or_default(),
+        first_entry_id: stream_info_entry_id(&map, "first-entry"),
+        last_entry_id: stream_info_entry_id(&map, "last-entry"),
+        groups: stream_info_int(&map, "groups").unwrap_or(0),
+    }
+}
+
+fn parse_stream_groups(groups: Vec<HashMap<String, redis::Value>>) -> Vec<StreamConsumerGroup> {
+    groups
+        .into_iter()
+        .map(|g| StreamConsumerGroup {
+            name: stream_info_string(&g, "name").unwrap_or_default(),
+            consumers: stream_info_int(&g, "consumers").unwrap_or(0),
+            pending: stream_info_int(&g, "pending").unwrap_or(0),
+            last_delivered_id: stream_info_string(&g, "last-delivered-id").unwrap_or_default(),
+            // Only reported by Redis 7.0+, and nil when the lag is unknown
+            lag: stream_info_int(&g, "lag"),
+        })
         .collect()
 }
 
+async fn fetch_stream_page(
+    con: &mut RedisConnection,
+    key: &[u8],
+    start: &str,
+    count: usize,
+) -> Result<StreamPage, CommandError> {
+    let raw: Vec<(String, Vec<Vec<u8>>)> = redis::cmd("XRANGE")
+        .arg(key)
+        .arg(start)
+        .arg("+")
+        .arg("COUNT")
+        .arg(count + 1)
+        .query_async(con)
+        .await
+        .map_err(|e| CommandError::redis("XRANGE error", e))?;
+
+    Ok(split_stream_page(format_stream_entries(raw), count))
+}
+
 #[tauri::command]
 async fn get_key_value(
-    config: RedisConfig,
-    key: String,
+    connection_id: String,
+    key: RedisBytes,
     db: i64,
     state: State<'_, ConnectionManager>,
-) -> Result<RedisKeyData, String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<RedisKeyData, CommandError> {
+    let key = key.to_bytes()?;
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb::blocking(move || rdb.key_data(db, &key)).await;
+    }
+    let mut con = state.get_connection(&connection_id, db).await?;
 
     // 1. Get Key Type
     let key_type: String = redis::cmd("TYPE")
         .arg(&key)
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("Failed to get key type: {}", e))?;
+        .map_err(|e| CommandError::redis("Failed to get key type", e))?;
 
-    // 2. Get Metadata (TTL, Memory, Encoding) using pipeline
+    // 2. Get Metadata (TTL, Memory, Encoding, Length) using pipeline
+    let length_cmd = length_command(&key_type);
     let mut pipe = redis::pipe();
     pipe.cmd("TTL").arg(&key);
     pipe.cmd("MEMORY").arg("USAGE").arg(&key);
     pipe.cmd("OBJECT").arg("ENCODING").arg(&key);
+    pipe.cmd(length_cmd.unwrap_or("EXISTS")).arg(&key); // EXISTS keeps alignment
 
-    let metadata: (i64, Option<i64>, Option<String>) = pipe
+    let metadata: (i64, Option<i64>, Option<String>, i64) = pipe
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("Failed to get metadata: {}", e))?;
+        .map_err(|e| CommandError::redis("Failed to get metadata", e))?;
 
     let ttl = metadata.0;
     let memory = metadata.1.unwrap_or(0);
     let encoding = metadata.2.unwrap_or_else(|| "none".to_string());
+    let length = if length_cmd.is_some() { metadata.3 } else { 0 };
+    let fits_in_page = length <= VALUE_PAGE_SIZE as i64;
 
-    // 3. Get Value
+    // 3. Get Value (only the first page for big collections)
     let value = match key_type.as_str() {
         "string" => {
-            let val: Vec<u8> = con.get(&key).await.map_err(|e| e.to_string())?;
-            let (s, is_utf8) = format_redis_bytes(val);
-            if is_utf8 {
-                RedisValue::String(s)
-            } else {
-                RedisValue::Binary(s)
-            }
+            let val: Vec<u8> = con.get(&key).await?;
+            format_redis_string(val)
         }
         "list" => {
-            let val: Vec<Vec<u8>> = con.lrange(&key, 0, -1).await.map_err(|e| e.to_string())?;
+            let val: Vec<Vec<u8>> = con.lrange(&key, 0, VALUE_PAGE_SIZE as isize - 1).await?;
             RedisValue::List(format_redis_bytes_list(val))
         }
         "set" => {
-            let val: Vec<Vec<u8>> = con.smembers(&key).await.map_err(|e| e.to_string())?;
+            let val: Vec<Vec<u8>> = if fits_in_page {
+                con.smembers(&key).await?
+            } else {
+                let (_, members): (u64, Vec<Vec<u8>>) = redis::cmd("SSCAN")
+                    .arg(&key)
+                    .arg(0)
+                    .arg("COUNT")
+                    .arg(VALUE_PAGE_SIZE)
+                    .query_async(&mut con)
+                    .await
+                    .map_err(|e| CommandError::redis("SSCAN error", e))?;
+                members
+            };
             RedisValue::Set(format_redis_bytes_list(val))
         }
         "zset" => {
             let val: Vec<(Vec<u8>, f64)> = con
-                .zrange_withscores(&key, 0, -1)
-                .await
-                .map_err(|e| e.to_string())?;
+                .zrange_withscores(&key, 0, VALUE_PAGE_SIZE as isize - 1)
+                .await?;
             RedisValue::ZSet(format_redis_bytes_zset(val))
         }
         "hash" => {
-            let val: HashMap<String, Vec<u8>> =
-                con.hgetall(&key).await.map_err(|e| e.to_string())?;
-            RedisValue::Hash(format_redis_bytes_hash(val))
+            if fits_in_page {
+                let val: HashMap<Vec<u8>, Vec<u8>> = con.hgetall(&key).await?;
+                RedisValue::Hash(format_redis_bytes_hash(val))
+            } else {
+                let (_, flat): (u64, Vec<Vec<u8>>) = redis::cmd("HSCAN")
+                    .arg(&key)
+                    .arg(0)
+                    .arg("COUNT")
+                    .arg(VALUE_PAGE_SIZE)
+                    .query_async(&mut con)
+                    .await
+                    .map_err(|e| CommandError::redis("HSCAN error", e))?;
+                RedisValue::Hash(format_redis_bytes_pairs(flat))
+            }
+        }
+        "stream" => {
+            let StreamPage { entries, next_id } =
+                fetch_stream_page(&mut con, &key, "-", STREAM_PAGE_SIZE).await?;
+
+            let info: HashMap<String, redis::Value> = redis::cmd("XINFO")
+                .arg("STREAM")
+                .arg(&key)
+                .query_async(&mut con)
+                .await
+                .map_err(|e| CommandError::redis("XINFO STREAM error", e))?;
+
+            let groups: Vec<HashMap<String, redis::Value>> = redis::cmd("XINFO")
+                .arg("GROUPS")
+                .arg(&key)
+                .query_async(&mut con)
+                .await
+                .map_err(|e| CommandError::redis("XINFO GROUPS error", e))?;
+
+            RedisValue::Stream(StreamValue {
+                entries,
+                next_id,
+                info: Some(parse_stream_info(info)),
+                groups: parse_stream_groups(groups),
+            })
         }
         _ => RedisValue::None,
     };
@@ -340,6 +657,7 @@ async fn get_key_value(
     Ok(RedisKeyData {
         key_type,
         value,
+        length,
         ttl,
         memory,
         encoding,
@@ -348,12 +666,16 @@ async fn get_key_value(
 
 #[tauri::command]
 async fn get_batch_key_values(
-    config: RedisConfig,
-    keys: Vec<String>,
+    connection_id: String,
+    keys: Vec<RedisBytes>,
     db: i64,
     state: State<'_, ConnectionManager>,
-) -> Result<Vec<RedisValue>, String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<Vec<RedisValue>, CommandError> {
+    let keys = decode_bytes_list(&keys)?;
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb::blocking(move || rdb.batch_values(db, &keys)).await;
+    }
+    let mut con = state.get_connection(&connection_id, db).await?;
 
     if keys.is_empty() {
         return Ok(Vec::new());
@@ -364,10 +686,10 @@ async fn get_batch_key_values(
     for key in &keys {
         pipe.cmd("TYPE").arg(key);
     }
-    let types: Vec<String> = pipe
-        .query_async(&mut con)
+    let types: Vec<String> = con
+        .query_pipeline(&pipe)
         .await
-        .map_err(|e| format!("Pipeline Pass 1 (TYPE) failed: {}", e))?;
+        .map_err(|e| CommandError::redis("Pipeline Pass 1 (TYPE) failed", e))?;
 
     // Pass 2: Get all values using pipeline
     let mut pipe = redis::pipe();
@@ -377,16 +699,36 @@ async fn get_batch_key_values(
                 pipe.cmd("GET").arg(key);
             }
             "list" => {
-                pipe.cmd("LRANGE").arg(key).arg(0).arg(-1);
+                pipe.cmd("LRANGE").arg(key).arg(0).arg(VALUE_PAGE_SIZE - 1);
             }
             "set" => {
-                pipe.cmd("SMEMBERS").arg(key);
+                pipe.cmd("SSCAN")
+                    .arg(key)
+                    .arg(0)
+                    .arg("COUNT")
+                    .arg(VALUE_PAGE_SIZE);
             }
             "zset" => {
-                pipe.cmd("ZRANGE").arg(key).arg(0).arg(-1).arg("WITHSCORES");
+                pipe.cmd("ZRANGE")
+                    .arg(key)
+                    .arg(0)
+                    .arg(VALUE_PAGE_SIZE - 1)
+                    .arg("WITHSCORES");
             }
             "hash" => {
-                pipe.cmd("HGETALL").arg(key);
+                pipe.cmd("HSCAN")
+                    .arg(key)
+                    .arg(0)
+                    .arg("COUNT")
+                    .arg(VALUE_PAGE_SIZE);
+            }
+            "stream" => {
+                pipe.cmd("XRANGE")
+                    .arg(key)
+                    .arg("-")
+                    .arg("+")
+                    .arg("COUNT")
+                    .arg(STREAM_PAGE_SIZE + 1);
             }
             _ => {
                 pipe.cmd("EXISTS").arg(key); // Dummy command to keep alignment
@@ -395,41 +737,46 @@ async fn get_batch_key_values(
     }
 
     // Executing Pass 2
-    let values: Vec<redis::Value> = pipe
-        .query_async(&mut con)
+    let values: Vec<redis::Value> = con
+        .query_pipeline(&pipe)
         .await
-        .map_err(|e| format!("Pipeline Pass 2 (VALUE) failed: {}", e))?;
+        .map_err(|e| CommandError::redis("Pipeline Pass 2 (VALUE) failed", e))?;
 
     // Map raw redis::Value back to our RedisValue enum
     let mut results = Vec::with_capacity(keys.len());
-    for (val, key_type) in values.into_iter().zip(types.into_iter()) {
+    for (val, key_type) in values.into_iter().zip(types) {
         let rv = match key_type.as_str() {
             "string" => val
                 .as_sequence()
-                .and_then(|s| s.get(0))
+                .and_then(|s| s.first())
                 .cloned()
                 .or(Some(val.clone()))
                 .and_then(|v| redis::from_redis_value::<Vec<u8>>(v).ok())
-                .map(|v| {
-                    let (s, is_utf8) = format_redis_bytes(v);
-                    if is_utf8 {
-                        RedisValue::String(s)
-                    } else {
-                        RedisValue::Binary(s)
-                    }
-                })
+                .map(format_redis_string)
                 .unwrap_or(RedisValue::None),
             "list" => redis::from_redis_value::<Vec<Vec<u8>>>(val)
                 .map(|v| RedisValue::List(format_redis_bytes_list(v)))
                 .unwrap_or(RedisValue::None),
-            "set" => redis::from_redis_value::<Vec<Vec<u8>>>(val)
-                .map(|v| RedisValue::Set(format_redis_bytes_list(v)))
+            "set" => redis::from_redis_value::<(u64, Vec<Vec<u8>>)>(val)
+                .map(|(_, v)| RedisValue::Set(format_redis_bytes_list(v)))
                 .unwrap_or(RedisValue::None),
             "zset" => redis::from_redis_value::<Vec<(Vec<u8>, f64)>>(val)
                 .map(|v| RedisValue::ZSet(format_redis_bytes_zset(v)))
                 .unwrap_or(RedisValue::None),
-            "hash" => redis::from_redis_value::<HashMap<String, Vec<u8>>>(val)
-                .map(|v| RedisValue::Hash(format_redis_bytes_hash(v)))
+            "hash" => redis::from_redis_value::<(u64, Vec<Vec<u8>>)>(val)
+                .map(|(_, v)| RedisValue::Hash(format_redis_bytes_pairs(v)))
+                .unwrap_or(RedisValue::None),
+            "stream" => redis::from_redis_value::<Vec<(String, Vec<Vec<u8>>)>>(val)
+                .map(|v| {
+                    let StreamPage { entries, next_id } =
+                        split_stream_page(format_stream_entries(v), STREAM_PAGE_SIZE);
+                    RedisValue::Stream(StreamValue {
+                        entries,
+                        next_id,
+                        info: None,
+                        groups: Vec::new(),
+                    })
+                })
                 .unwrap_or(RedisValue::None),
             _ => RedisValue::None,
         };
@@ -441,17 +788,30 @@ async fn get_batch_key_values(
 
 #[tauri::command]
 async fn get_db_sizes(
-    config: RedisConfig,
+    connection_id: String,
     state: State<'_, ConnectionManager>,
-) -> Result<Vec<i64>, String> {
-    let mut con = state.get_connection(&config, 0).await?;
+) -> Result<Vec<i64>, CommandError> {
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return Ok(rdb.db_sizes());
+    }
+    let mut con = state.get_connection(&connection_id, 0).await?;
+
+    // Cluster only has DB 0; DBSIZE is summed over all masters by the client
+    if con.is_cluster() {
+        let total: i64 = redis::cmd("DBSIZE")
+            .query_async(&mut con)
+            .await
+            .map_err(|e| CommandError::redis("DBSIZE error", e))?;
+        return Ok(vec![total]);
+    }
+
     let mut sizes: Vec<i64> = vec![0; 16];
 
     let info: String = redis::cmd("INFO")
         .arg("keyspace")
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("Failed to get INFO keyspace: {}", e))?;
+        .map_err(|e| CommandError::redis("Failed to get INFO keyspace", e))?;
 
     // Parse INFO keyspace
     // format: # Keyspace\r\ndb0:keys=1,expires=0,avg_ttl=0\r\ndb1:keys=10,expires=0,avg_ttl=0
@@ -481,14 +841,129 @@ async fn get_db_sizes(
 
 #[tauri::command]
 async fn set_key_value(
-    config: RedisConfig,
-    key: String,
-    value: String,
+    connection_id: String,
+    key: RedisBytes,
+    value: RedisBytes,
+    db: i64,
+    state: State<'_, ConnectionManager>,
+) -> Result<(), CommandError> {
+    state.ensure_writable(&connection_id, "SET").await?;
+    let key = key.to_bytes()?;
+    let value = value.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    let _: () = con.set(key, value).await?;
+    Ok(())
+}
+
+/// Initial payload of a new key, tagged like `RedisValue`.
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "value")]
+pub enum NewKeyValue {
+    String(RedisBytes),
+    List(Vec<RedisBytes>),
+    Set(Vec<RedisBytes>),
+    ZSet(Vec<(RedisBytes, f64)>),
+    Hash(Vec<(RedisBytes, RedisBytes)>),
+    /// Fields of the first entry, added with an auto-generated ID
+    Stream(Vec<(RedisBytes, RedisBytes)>),
+}
+
+/// Arguments per command call in `CREATE_KEY_SCRIPT`, kept even so
+/// field/value and score/member pairs never straddle two calls.
+const CREATE_KEY_BATCH: usize = 5000;
+
+/// KEYS[1] = key. ARGV: if_not_exists flag, ttl (<= 0 for none), batch
+/// size, build command, then its arguments. Runs as one script so the key
+/// is never seen half-built and an existing key is only replaced on request.
+const CREATE_KEY_SCRIPT: &str = r"
+if ARGV[1] == '1' and redis.call('EXISTS', KEYS[1]) == 1 then
+    return 0
+end
+redis.call('DEL', KEYS[1])
+local batch = tonumber(ARGV[3])
+for i = 5, #ARGV, batch do
+    redis.call(ARGV[4], KEYS[1], unpack(ARGV, i, math.min(i + batch - 1, #ARGV)))
+end
+if tonumber(ARGV[2]) > 0 then
+    redis.call('EXPIRE', KEYS[1], ARGV[2])
+end
+return 1
+";
+
+/// Creates `key` with its first value and optional TTL in one atomic step.
+/// With `if_not_exists` an existing key is left alone and reported as an
+/// error; otherwise it is replaced.
+#[tauri::command]
+async fn create_key(
+    connection_id: String,
     db: i64,
+    key: RedisBytes,
+    value: NewKeyValue,
+    ttl: Option<i64>,
+    if_not_exists: bool,
     state: State<'_, ConnectionManager>,
-) -> Result<(), String> {
-    let mut con = state.get_connection(&config, db).await?;
-    let _: () = con.set(key, value).await.map_err(|e| e.to_string())?;
+) -> Result<(), CommandError> {
+    state.ensure_writable(&connection_id, "Create key").await?;
+    let key = key.to_bytes()?;
+
+    let pairs = |pairs: &[(RedisBytes, RedisBytes)]| -> Result<Vec<Vec<u8>>, CommandError> {
+        let mut args = Vec::with_capacity(pairs.len() * 2);
+        for (f, v) in pairs {
+            args.push(f.to_bytes()?);
+            args.push(v.to_bytes()?);
+        }
+        Ok(args)
+    };
+
+    let (command, args): (&str, Vec<Vec<u8>>) = match value {
+        NewKeyValue::String(s) => ("SET", vec![s.to_bytes()?]),
+        NewKeyValue::List(items) => ("RPUSH", decode_bytes_list(&items)?),
+        NewKeyValue::Set(members) => ("SADD", decode_bytes_list(&members)?),
+        NewKeyValue::ZSet(members) => {
+            let mut args = Vec::with_capacity(members.len() * 2);
+            for (member, score) in &members {
+                args.push(score.to_string().into_bytes());
+                args.push(member.to_bytes()?);
+            }
+            ("ZADD", args)
+        }
+        NewKeyValue::Hash(fields) => ("HSET", pairs(&fields)?),
+        NewKeyValue::Stream(fields) => {
+            if fields.len() * 2 > CREATE_KEY_BATCH {
+                return Err(CommandError::invalid(
+                    "Too many fields for one stream entry",
+                ));
+            }
+            let mut args = vec![b"*".to_vec()];
+            args.extend(pairs(&fields)?);
+            ("XADD", args)
+        }
+    };
+    // Redis has no empty collections, the key would simply not exist
+    if args.is_empty() || (command == "XADD" && args.len() == 1) {
+        return Err(CommandError::invalid(
+            "A new key needs at least one element",
+        ));
+    }
+
+    let mut con = state.get_connection(&connection_id, db).await?;
+    let created: i64 = redis::Script::new(CREATE_KEY_SCRIPT)
+        .key(&key)
+        .arg(if if_not_exists { "1" } else { "0" })
+        .arg(ttl.unwrap_or(-1))
+        .arg(CREATE_KEY_BATCH)
+        .arg(command)
+        .arg(&args)
+        .invoke_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("Create key error", e))?;
+
+    if created == 0 {
+        return Err(CommandError::invalid(format!(
+            "Key {} already exists",
+            String::from_utf8_lossy(&key)
+        )));
+    }
     Ok(())
 }
 
@@ -496,59 +971,78 @@ async fn set_key_value(
 
 #[tauri::command]
 async fn hash_scan(
-    config: RedisConfig,
+    connection_id: String,
     db: i64,
-    key: String,
+    key: RedisBytes,
     cursor: u64,
     count: usize,
     state: State<'_, ConnectionManager>,
-) -> Result<(u64, Vec<String>), String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<(u64, Vec<RedisBytes>), CommandError> {
+    let key = key.to_bytes()?;
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb::blocking(move || rdb.hash_scan(db, &key, cursor, count)).await;
+    }
+    let mut con = state.get_connection(&connection_id, db).await?;
 
     // HSCAN returns (cursor, [field1, value1, field2, value2, ...])
-    let result: (u64, Vec<String>) = redis::cmd("HSCAN")
+    let result: (u64, Vec<Vec<u8>>) = redis::cmd("HSCAN")
         .arg(&key)
         .arg(cursor)
         .arg("COUNT")
         .arg(count)
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("HSCAN error: {}", e))?;
+        .map_err(|e| CommandError::redis("HSCAN error", e))?;
 
     // Extract only field names (every even index: 0, 2, 4, ...)
-    let fields: Vec<String> = result.1.iter().step_by(2).cloned().collect();
+    let fields = result
+        .1
+        .into_iter()
+        .step_by(2)
+        .map(RedisBytes::from)
+        .collect();
 
     Ok((result.0, fields))
 }
 
 #[tauri::command]
 async fn hash_get_field(
-    config: RedisConfig,
+    connection_id: String,
     db: i64,
-    key: String,
-    field: String,
+    key: RedisBytes,
+    field: RedisBytes,
     state: State<'_, ConnectionManager>,
-) -> Result<Option<String>, String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<Option<RedisBytes>, CommandError> {
+    let key = key.to_bytes()?;
+    let field = field.to_bytes()?;
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb::blocking(move || rdb.hash_get_field(db, &key, &field)).await;
+    }
+    let mut con = state.get_connection(&connection_id, db).await?;
 
-    redis::cmd("HGET")
+    let value: Option<Vec<u8>> = redis::cmd("HGET")
         .arg(&key)
         .arg(&field)
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("HGET error: {}", e))
+        .map_err(|e| CommandError::redis("HGET error", e))?;
+
+    Ok(value.map(RedisBytes::from))
 }
 
 #[tauri::command]
 async fn hash_set_field(
-    config: RedisConfig,
+    connection_id: String,
     db: i64,
-    key: String,
-    field: String,
-    value: String,
+    key: RedisBytes,
+    field: RedisBytes,
+    value: RedisBytes,
     state: State<'_, ConnectionManager>,
-) -> Result<(), String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<(), CommandError> {
+    state.ensure_writable(&connection_id, "HSET").await?;
+    let key = key.to_bytes()?;
+    let (field, value) = (field.to_bytes()?, value.to_bytes()?);
+    let mut con = state.get_connection(&connection_id, db).await?;
 
     let _: () = redis::cmd("HSET")
         .arg(&key)
@@ -556,20 +1050,23 @@ async fn hash_set_field(
         .arg(&value)
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("HSET error: {}", e))?;
+        .map_err(|e| CommandError::redis("HSET error", e))?;
 
     Ok(())
 }
 
 #[tauri::command]
 async fn hash_add_field(
-    config: RedisConfig,
+    connection_id: String,
     db: i64,
-    key: String,
-    field: String,
+    key: RedisBytes,
+    field: RedisBytes,
     state: State<'_, ConnectionManager>,
-) -> Result<bool, String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<bool, CommandError> {
+    state.ensure_writable(&connection_id, "HSETNX").await?;
+    let key = key.to_bytes()?;
+    let field = field.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
 
     let created: i32 = redis::cmd("HSETNX")
         .arg(&key)
@@ -577,29 +1074,34 @@ async fn hash_add_field(
         .arg("New Member")
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("HSETNX error: {}", e))?;
+        .map_err(|e| CommandError::redis("HSETNX error", e))?;
 
     Ok(created == 1)
 }
 
 #[tauri::command]
 async fn hash_rename_field(
-    config: RedisConfig,
+    connection_id: String,
     db: i64,
-    key: String,
-    old_field: String,
-    new_field: String,
+    key: RedisBytes,
+    old_field: RedisBytes,
+    new_field: RedisBytes,
     state: State<'_, ConnectionManager>,
-) -> Result<(), String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<(), CommandError> {
+    state
+        .ensure_writable(&connection_id, "Rename field")
+        .await?;
+    let key = key.to_bytes()?;
+    let (old_field, new_field) = (old_field.to_bytes()?, new_field.to_bytes()?);
+    let mut con = state.get_connection(&connection_id, db).await?;
 
     // Get current value
-    let value: String = redis::cmd("HGET")
+    let value: Vec<u8> = redis::cmd("HGET")
         .arg(&key)
         .arg(&old_field)
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("HGET error: {}", e))?;
+        .map_err(|e| CommandError::redis("HGET error", e))?;
 
     // Transaction: HDEL old + HSET new
     let _: () = redis::pipe()
@@ -613,87 +1115,1630 @@ async fn hash_rename_field(
         .arg(&value)
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("Rename transaction error: {}", e))?;
+        .map_err(|e| CommandError::redis("Rename transaction error", e))?;
 
     Ok(())
 }
 
 #[tauri::command]
 async fn hash_delete_field(
-    config: RedisConfig,
+    connection_id: String,
     db: i64,
-    key: String,
-    field: String,
+    key: RedisBytes,
+    field: RedisBytes,
     state: State<'_, ConnectionManager>,
-) -> Result<(), String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<(), CommandError> {
+    state.ensure_writable(&connection_id, "HDEL").await?;
+    let key = key.to_bytes()?;
+    let field = field.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
 
     let _: () = redis::cmd("HDEL")
         .arg(&key)
         .arg(&field)
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("HDEL error: {}", e))?;
+        .map_err(|e| CommandError::redis("HDEL error", e))?;
 
     Ok(())
 }
 
+// ==================== List / Set / ZSet Commands ====================
+
 #[tauri::command]
-async fn delete_keys(
-    config: RedisConfig,
+async fn list_range(
+    connection_id: String,
     db: i64,
-    keys: Vec<String>,
+    key: RedisBytes,
+    start: i64,
+    stop: i64,
     state: State<'_, ConnectionManager>,
-) -> Result<i64, String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<Vec<RedisBytes>, CommandError> {
+    let key = key.to_bytes()?;
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb::blocking(move || rdb.list_range(db, &key, start, stop)).await;
+    }
+    let mut con = state.get_connection(&connection_id, db).await?;
 
-    if keys.is_empty() {
-        return Ok(0);
+    let val: Vec<Vec<u8>> = redis::cmd("LRANGE")
+        .arg(&key)
+        .arg(start)
+        .arg(stop)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("LRANGE error", e))?;
+
+    Ok(format_redis_bytes_list(val))
+}
+
+#[tauri::command]
+async fn set_scan(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    cursor: u64,
+    count: usize,
+    pattern: Option<String>,
+    state: State<'_, ConnectionManager>,
+) -> Result<(u64, Vec<RedisBytes>), CommandError> {
+    let key = key.to_bytes()?;
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb::blocking(move || rdb.set_scan(db, &key, cursor, count, pattern.as_deref()))
+            .await;
+    }
+    let mut con = state.get_connection(&connection_id, db).await?;
+
+    let mut cmd = redis::cmd("SSCAN");
+    cmd.arg(&key).arg(cursor);
+    if let Some(p) = pattern.as_deref().filter(|p| !p.is_empty() && *p != "*") {
+        cmd.arg("MATCH").arg(p);
     }
+    cmd.arg("COUNT").arg(count);
 
-    let deleted_count: i64 = redis::cmd("DEL")
-        .arg(&keys)
+    let (next_cursor, members): (u64, Vec<Vec<u8>>) = cmd
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("DEL error: {}", e))?;
+        .map_err(|e| CommandError::redis("SSCAN error", e))?;
+
+    Ok((next_cursor, format_redis_bytes_list(members)))
+}
 
-    Ok(deleted_count)
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ZSetRangeBy {
+    Rank,
+    Score,
+    Lex,
 }
 
+/// `min`/`max` are only used for score and lex ranges and accept the usual
+/// Redis syntax (`-inf`, `(1.5`, `[a`, `-`, `+`). They default to the full range.
 #[tauri::command]
-async fn set_key_ttl(
-    config: RedisConfig,
+#[allow(clippy::too_many_arguments)]
+async fn zset_range(
+    connection_id: String,
     db: i64,
-    key: String,
-    ttl: i64,
+    key: RedisBytes,
+    by: ZSetRangeBy,
+    min: Option<String>,
+    max: Option<String>,
+    offset: usize,
+    limit: usize,
     state: State<'_, ConnectionManager>,
-) -> Result<(), String> {
-    let mut con = state.get_connection(&config, db).await?;
+) -> Result<Vec<(RedisBytes, f64)>, CommandError> {
+    let key = key.to_bytes()?;
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb::blocking(move || {
+            rdb.zset_range(db, &key, by, min.as_deref(), max.as_deref(), offset, limit)
+        })
+        .await;
+    }
+    let mut con = state.get_connection(&connection_id, db).await?;
 
-    let _: () = redis::cmd("EXPIRE")
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let val: Vec<(Vec<u8>, f64)> = match by {
+        ZSetRangeBy::Rank => redis::cmd("ZRANGE")
+            .arg(&key)
+            .arg(offset)
+            .arg(offset + limit - 1)
+            .arg("WITHSCORES")
+            .query_async(&mut con)
+            .await
+            .map_err(|e| CommandError::redis("ZRANGE error", e))?,
+        ZSetRangeBy::Score => redis::cmd("ZRANGEBYSCORE")
+            .arg(&key)
+            .arg(min.as_deref().unwrap_or("-inf"))
+            .arg(max.as_deref().unwrap_or("+inf"))
+            .arg("WITHSCORES")
+            .arg("LIMIT")
+            .arg(offset)
+            .arg(limit)
+            .query_async(&mut con)
+            .await
+            .map_err(|e| CommandError::redis("ZRANGEBYSCORE error", e))?,
+        ZSetRangeBy::Lex => {
+            // ZRANGEBYLEX has no WITHSCORES, look the scores up in one pipeline
+            let members: Vec<Vec<u8>> = redis::cmd("ZRANGEBYLEX")
+                .arg(&key)
+                .arg(min.as_deref().unwrap_or("-"))
+                .arg(max.as_deref().unwrap_or("+"))
+                .arg("LIMIT")
+                .arg(offset)
+                .arg(limit)
+                .query_async(&mut con)
+                .await
+                .map_err(|e| CommandError::redis("ZRANGEBYLEX error", e))?;
+
+            if members.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let mut pipe = redis::pipe();
+            for member in &members {
+                pipe.cmd("ZSCORE").arg(&key).arg(member);
+            }
+            let scores: Vec<Option<f64>> = pipe
+                .query_async(&mut con)
+                .await
+                .map_err(|e| CommandError::redis("Pipeline ZSCORE error", e))?;
+
+            members
+                .into_iter()
+                .zip(scores)
+                .map(|(m, s)| (m, s.unwrap_or(0.0)))
+                .collect()
+        }
+    };
+
+    Ok(format_redis_bytes_zset(val))
+}
+
+// ==================== Stream Commands ====================
+
+#[tauri::command]
+async fn stream_range(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    start: Option<String>,
+    count: usize,
+    state: State<'_, ConnectionManager>,
+) -> Result<StreamPage, CommandError> {
+    let key = key.to_bytes()?;
+    if let Some(rdb) = state.rdb_file(&connection_id).await {
+        return rdb::blocking(move || rdb.stream_range(db, &key, start.as_deref(), count)).await;
+    }
+    let mut con = state.get_connection(&connection_id, db).await?;
+
+    let start = start.unwrap_or_else(|| "-".to_string());
+    fetch_stream_page(&mut con, &key, &start, count).await
+}
+
+// ==================== List / Set / ZSet Editing ====================
+
+/// Outcome of a collection edit: how many elements were affected and the
+/// collection size afterwards (0 once Redis dropped the emptied key).
+#[derive(Serialize)]
+pub struct EditResult {
+    pub changed: i64,
+    pub length: i64,
+}
+
+/// Rejects the edit up front when `key` holds another type. Missing keys
+/// pass only if the command creates them (push, add).
+async fn ensure_key_type(
+    con: &mut RedisConnection,
+    key: &[u8],
+    expected: &str,
+    allow_missing: bool,
+) -> Result<(), CommandError> {
+    let key_type: String = redis::cmd("TYPE")
+        .arg(key)
+        .query_async(con)
+        .await
+        .map_err(|e| CommandError::redis("Failed to get key type", e))?;
+
+    match key_type.as_str() {
+        t if t == expected => Ok(()),
+        "none" if allow_missing => Ok(()),
+        "none" => Err(CommandError::invalid(format!(
+            "Key {} does not exist",
+            String::from_utf8_lossy(key)
+        ))),
+        other => {
+            let mut error = CommandError::new(
+                ErrorKind::WrongType,
+                format!(
+                    "Key {} holds a {}, not a {}",
+                    String::from_utf8_lossy(key),
+                    other,
+                    expected
+                ),
+            );
+            error.code = Some("WRONGTYPE".to_string());
+            Err(error)
+        }
+    }
+}
+
+#[tauri::command]
+async fn list_push(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    values: Vec<RedisBytes>,
+    head: bool,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    let name = if head { "LPUSH" } else { "RPUSH" };
+    state.ensure_writable(&connection_id, name).await?;
+    let key = key.to_bytes()?;
+    if values.is_empty() {
+        return Err(CommandError::invalid("Nothing to push"));
+    }
+    let values = decode_bytes_list(&values)?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "list", true).await?;
+
+    let length: i64 = redis::cmd(name)
         .arg(&key)
-        .arg(ttl)
+        .arg(&values)
         .query_async(&mut con)
         .await
-        .map_err(|e| format!("EXPIRE error: {}", e))?;
+        .map_err(|e| CommandError::redis(&format!("{} error", name), e))?;
 
-    Ok(())
+    Ok(EditResult {
+        changed: values.len() as i64,
+        length,
+    })
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
+#[tauri::command]
+async fn list_insert(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    pivot: RedisBytes,
+    value: RedisBytes,
+    before: bool,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "LINSERT").await?;
+    let key = key.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "list", false).await?;
+
+    let length: i64 = redis::cmd("LINSERT")
+        .arg(&key)
+        .arg(if before { "BEFORE" } else { "AFTER" })
+        .arg(pivot.to_bytes()?)
+        .arg(value.to_bytes()?)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("LINSERT error", e))?;
+
+    if length < 0 {
+        return Err(CommandError::invalid(format!(
+            "Pivot {} not found in list",
+            pivot
+        )));
+    }
+    Ok(EditResult { changed: 1, length })
+}
+
+#[tauri::command]
+async fn list_set(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    index: i64,
+    value: RedisBytes,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "LSET").await?;
+    let key = key.to_bytes()?;
+    let value = value.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "list", false).await?;
+
+    let (_, length): ((), i64) = redis::pipe()
+        .atomic()
+        .cmd("LSET")
+        .arg(&key)
+        .arg(index)
+        .arg(&value)
+        .cmd("LLEN")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("LSET error", e))?;
+
+    Ok(EditResult { changed: 1, length })
+}
+
+/// LREM semantics: `count` > 0 removes from the head, < 0 from the tail,
+/// 0 removes every occurrence.
+#[tauri::command]
+async fn list_remove(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    value: RedisBytes,
+    count: i64,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "LREM").await?;
+    let key = key.to_bytes()?;
+    let value = value.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "list", false).await?;
+
+    let (changed, length): (i64, i64) = redis::pipe()
+        .atomic()
+        .cmd("LREM")
+        .arg(&key)
+        .arg(count)
+        .arg(&value)
+        .cmd("LLEN")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("LREM error", e))?;
+
+    Ok(EditResult { changed, length })
+}
+
+#[tauri::command]
+async fn list_trim(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    start: i64,
+    stop: i64,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "LTRIM").await?;
+    let key = key.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "list", false).await?;
+
+    let (before, (), length): (i64, (), i64) = redis::pipe()
+        .atomic()
+        .cmd("LLEN")
+        .arg(&key)
+        .cmd("LTRIM")
+        .arg(&key)
+        .arg(start)
+        .arg(stop)
+        .cmd("LLEN")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("LTRIM error", e))?;
+
+    Ok(EditResult {
+        changed: before - length,
+        length,
+    })
+}
+
+#[tauri::command]
+async fn set_add_members(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    members: Vec<RedisBytes>,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "SADD").await?;
+    let key = key.to_bytes()?;
+    if members.is_empty() {
+        return Err(CommandError::invalid("No members to add"));
+    }
+    let members = decode_bytes_list(&members)?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "set", true).await?;
+
+    let (changed, length): (i64, i64) = redis::pipe()
+        .atomic()
+        .cmd("SADD")
+        .arg(&key)
+        .arg(&members)
+        .cmd("SCARD")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("SADD error", e))?;
+
+    Ok(EditResult { changed, length })
+}
+
+#[tauri::command]
+async fn set_remove_members(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    members: Vec<RedisBytes>,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "SREM").await?;
+    let key = key.to_bytes()?;
+    if members.is_empty() {
+        return Err(CommandError::invalid("No members to remove"));
+    }
+    let members = decode_bytes_list(&members)?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "set", false).await?;
+
+    let (changed, length): (i64, i64) = redis::pipe()
+        .atomic()
+        .cmd("SREM")
+        .arg(&key)
+        .arg(&members)
+        .cmd("SCARD")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("SREM error", e))?;
+
+    Ok(EditResult { changed, length })
+}
+
+/// Moves members to `destination` with SMOVE. `length` is the size of the
+/// source set afterwards. In cluster mode both keys must share a slot.
+#[tauri::command]
+async fn set_move_members(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    destination: RedisBytes,
+    members: Vec<RedisBytes>,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "SMOVE").await?;
+    let key = key.to_bytes()?;
+    let destination = destination.to_bytes()?;
+    if members.is_empty() {
+        return Err(CommandError::invalid("No members to move"));
+    }
+    let members = decode_bytes_list(&members)?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "set", false).await?;
+    ensure_key_type(&mut con, &destination, "set", true).await?;
+
+    let mut pipe = redis::pipe();
+    for member in &members {
+        pipe.cmd("SMOVE").arg(&key).arg(&destination).arg(member);
+    }
+    let moved: Vec<i64> = pipe
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("SMOVE error", e))?;
+
+    let length: i64 = redis::cmd("SCARD")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("SCARD error", e))?;
+
+    Ok(EditResult {
+        changed: moved.iter().sum(),
+        length,
+    })
+}
+
+/// Adds members that are not in the set yet; existing scores are kept.
+#[tauri::command]
+async fn zset_add_members(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    members: Vec<(RedisBytes, f64)>,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "ZADD").await?;
+    let key = key.to_bytes()?;
+    if members.is_empty() {
+        return Err(CommandError::invalid("No members to add"));
+    }
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "zset", true).await?;
+
+    let mut zadd = redis::cmd("ZADD");
+    zadd.arg(&key).arg("NX");
+    for (member, score) in &members {
+        zadd.arg(*score).arg(member.to_bytes()?);
+    }
+    let (changed, length): (i64, i64) = redis::pipe()
+        .atomic()
+        .add_command(zadd)
+        .cmd("ZCARD")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("ZADD error", e))?;
+
+    Ok(EditResult { changed, length })
+}
+
+/// Sets the score of an existing member. `changed` is 0 when the score was
+/// already equal.
+#[tauri::command]
+async fn zset_update_score(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    member: RedisBytes,
+    score: f64,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "ZADD").await?;
+    let key = key.to_bytes()?;
+    let member_bytes = member.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "zset", false).await?;
+
+    let (exists, changed, length): (Option<f64>, i64, i64) = redis::pipe()
+        .atomic()
+        .cmd("ZSCORE")
+        .arg(&key)
+        .arg(&member_bytes)
+        .cmd("ZADD")
+        .arg(&key)
+        .arg("XX")
+        .arg("CH")
+        .arg(score)
+        .arg(&member_bytes)
+        .cmd("ZCARD")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("ZADD error", e))?;
+
+    if exists.is_none() {
+        return Err(CommandError::invalid(format!(
+            "Member {} not found in sorted set",
+            member
+        )));
+    }
+    Ok(EditResult { changed, length })
+}
+
+/// ZINCRBY, returns the new score. Creates the member if missing.
+#[tauri::command]
+async fn zset_increment_score(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    member: RedisBytes,
+    increment: f64,
+    state: State<'_, ConnectionManager>,
+) -> Result<f64, CommandError> {
+    state.ensure_writable(&connection_id, "ZINCRBY").await?;
+    let key = key.to_bytes()?;
+    let member = member.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "zset", true).await?;
+
+    redis::cmd("ZINCRBY")
+        .arg(&key)
+        .arg(increment)
+        .arg(&member)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("ZINCRBY error", e))
+}
+
+#[tauri::command]
+async fn zset_remove_members(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    members: Vec<RedisBytes>,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state.ensure_writable(&connection_id, "ZREM").await?;
+    let key = key.to_bytes()?;
+    if members.is_empty() {
+        return Err(CommandError::invalid("No members to remove"));
+    }
+    let members = decode_bytes_list(&members)?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "zset", false).await?;
+
+    let (changed, length): (i64, i64) = redis::pipe()
+        .atomic()
+        .cmd("ZREM")
+        .arg(&key)
+        .arg(&members)
+        .cmd("ZCARD")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("ZREM error", e))?;
+
+    Ok(EditResult { changed, length })
+}
+
+/// `min`/`max` use ZRANGEBYSCORE syntax: `-inf`, `+inf`, `(5` for exclusive.
+#[tauri::command]
+async fn zset_remove_by_score(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    min: String,
+    max: String,
+    state: State<'_, ConnectionManager>,
+) -> Result<EditResult, CommandError> {
+    state
+        .ensure_writable(&connection_id, "ZREMRANGEBYSCORE")
+        .await?;
+    let key = key.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+    ensure_key_type(&mut con, &key, "zset", false).await?;
+
+    let (changed, length): (i64, i64) = redis::pipe()
+        .atomic()
+        .cmd("ZREMRANGEBYSCORE")
+        .arg(&key)
+        .arg(&min)
+        .arg(&max)
+        .cmd("ZCARD")
+        .arg(&key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("ZREMRANGEBYSCORE error", e))?;
+
+    Ok(EditResult { changed, length })
+}
+
+// ==================== Value Decoders ====================
+
+fn decoder_task_error(e: tokio::task::JoinError) -> CommandError {
+    CommandError::new(ErrorKind::Internal, format!("Decoder task failed: {}", e))
+}
+
+/// Decodes a string value, hash value or element for display. Without
+/// `options.codecs` the chain is detected; `None` means plain text or
+/// opaque bytes.
+#[tauri::command]
+async fn decode_value(
+    value: RedisBytes,
+    options: DecodeOptions,
+) -> Result<Option<Decoded>, CommandError> {
+    let bytes = value.to_bytes()?;
+    tokio::task::spawn_blocking(move || decoder::decode(&bytes, &options))
+        .await
+        .map_err(decoder_task_error)?
+}
+
+/// Re-encodes an edited tree with the chain `decode_value` returned. The
+/// result is written back with `set_key_value`, `hash_set_field`, ...
+#[tauri::command]
+async fn encode_value(
+    codecs: Vec<Codec>,
+    value: serde_json::Value,
+    proto: Option<ProtoSchema>,
+) -> Result<RedisBytes, CommandError> {
+    tokio::task::spawn_blocking(move || decoder::encode(&codecs, &value, proto.as_ref()))
+        .await
+        .map_err(decoder_task_error)?
+        .map(RedisBytes::from)
+}
+
+/// Deletes `keys` with DEL, or with UNLINK when `unlink` is set so big
+/// values are freed in the background.
+#[tauri::command]
+async fn delete_keys(
+    connection_id: String,
+    db: i64,
+    keys: Vec<RedisBytes>,
+    unlink: Option<bool>,
+    confirm_token: Option<String>,
+    state: State<'_, ConnectionManager>,
+) -> Result<i64, CommandError> {
+    let keys = decode_bytes_list(&keys)?;
+    let command = if unlink.unwrap_or(false) {
+        "UNLINK"
+    } else {
+        "DEL"
+    };
+    state.ensure_writable(&connection_id, command).await?;
+    if keys.is_empty() {
+        return Ok(0);
+    }
+
+    let argv: Vec<Vec<u8>> = std::iter::once(command.as_bytes().to_vec())
+        .chain(keys.iter().cloned())
+        .collect();
+    state
+        .confirm(&connection_id, &argv, confirm_token.as_deref())
+        .await?;
+
+    let mut con = state.get_connection(&connection_id, db).await?;
+
+    let deleted_count: redis::RedisResult<i64> =
+        redis::cmd(command).arg(&keys).query_async(&mut con).await;
+    match deleted_count {
+        Ok(count) => Ok(count),
+        // UNLINK is Redis 4.0+
+        Err(e) if command == "UNLINK" && is_unknown_command(&e) => redis::cmd("DEL")
+            .arg(&keys)
+            .query_async(&mut con)
+            .await
+            .map_err(|e| CommandError::redis("DEL error", e)),
+        Err(e) => Err(CommandError::redis(&format!("{} error", command), e)),
+    }
+}
+
+#[tauri::command]
+async fn set_key_ttl(
+    connection_id: String,
+    db: i64,
+    key: RedisBytes,
+    ttl: i64,
+    state: State<'_, ConnectionManager>,
+) -> Result<(), CommandError> {
+    state.ensure_writable(&connection_id, "EXPIRE").await?;
+    let key = key.to_bytes()?;
+    let mut con = state.get_connection(&connection_id, db).await?;
+
+    let _: () = redis::cmd("EXPIRE")
+        .arg(&key)
+        .arg(ttl)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("EXPIRE error", e))?;
+
+    Ok(())
+}
+
+/// Runs one redis-cli style command line on the pooled connection for `db`.
+#[tauri::command]
+async fn execute_command(
+    connection_id: String,
+    db: i64,
+    command: String,
+    confirm_token: Option<String>,
+    state: State<'_, ConnectionManager>,
+) -> Result<Reply, CommandError> {
+    let args = console::split_args(command.as_bytes())?;
+    let Some((name, rest)) = args.split_first() else {
+        return Err(CommandError::invalid("Empty command"));
+    };
+    let name_str = String::from_utf8_lossy(name);
+    console::check_supported(&args)?;
+
+    let mut con = state.get_connection(&connection_id, db).await?;
+    if state.is_read_only(&connection_id).await?
+        && state
+            .is_mutating_command(&connection_id, &mut con, &args)
+            .await?
+    {
+        return Err(safety::read_only_error(&name_str));
+    }
+    state
+        .confirm(&connection_id, &args, confirm_token.as_deref())
+        .await?;
+    let mut cmd = redis::cmd(&name_str);
+    for arg in rest {
+        cmd.arg(arg.as_slice());
+    }
+
+    match cmd.query_async::<redis::Value>(&mut con).await {
+        Ok(value) => Ok(Reply::from(value)),
+        Err(e) => console::error_reply(e),
+    }
+}
+
+// ==================== Server Info ====================
+
+/// Every INFO section, parsed. On a cluster this is one node's view.
+#[tauri::command]
+async fn get_server_info(
+    connection_id: String,
+    state: State<'_, ConnectionManager>,
+) -> Result<ServerInfo, CommandError> {
+    let mut con = state.get_connection(&connection_id, 0).await?;
+    info::fetch(&mut con).await
+}
+
+/// Polls INFO every `interval_ms` and sends samples with rates (ops/sec,
+/// hit ratio, network, CPU) to `on_event` until `stop_task(task_id)`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn start_info_sampler(
+    connection_id: String,
+    interval_ms: u64,
+    task_id: String,
+    on_event: tauri::ipc::Channel<sampler::SamplerEvent>,
+    app: tauri::AppHandle,
+    state: State<'_, ConnectionManager>,
+    tasks: State<'_, Tasks>,
+) -> Result<(), CommandError> {
+    // Fail here rather than in the first event if the id is stale
+    state.get_connection(&connection_id, 0).await?;
+    let interval = std::time::Duration::from_millis(interval_ms).max(sampler::MIN_INTERVAL);
+    tasks.spawn(
+        &task_id,
+        sampler::run(app, connection_id, interval, on_event),
+    )
+}
+
+/// Stops a background task such as the INFO sampler.
+#[tauri::command]
+async fn stop_task(task_id: String, tasks: State<'_, Tasks>) -> Result<bool, CommandError> {
+    Ok(tasks.stop(&task_id))
+}
+
+// ==================== Monitor ====================
+
+/// Starts a MONITOR session on its own connection and streams the lines
+/// matching `filter` to `on_event`. It stops by itself after `max_seconds`
+/// or `max_lines` matches (both capped), or with `stop_monitor`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn start_monitor(
+    connection_id: String,
+    filter: MonitorFilter,
+    max_seconds: Option<u64>,
+    max_lines: Option<u64>,
+    monitor_id: String,
+    confirm_token: Option<String>,
+    on_event: tauri::ipc::Channel<MonitorEvent>,
+    state: State<'_, ConnectionManager>,
+    tasks: State<'_, Tasks>,
+) -> Result<(), CommandError> {
+    state
+        .confirm(
+            &connection_id,
+            &[b"MONITOR".to_vec()],
+            confirm_token.as_deref(),
+        )
+        .await?;
+    let duration = max_seconds
+        .map(std::time::Duration::from_secs)
+        .unwrap_or(monitor::DEFAULT_DURATION)
+        .min(monitor::MAX_DURATION);
+    let max_lines = max_lines
+        .unwrap_or(monitor::DEFAULT_LINES)
+        .clamp(1, monitor::MAX_LINES);
+
+    let client = state.dedicated_client(&connection_id, "MONITOR").await?;
+    let session = client
+        .get_async_monitor()
+        .await
+        .map_err(|e| CommandError::redis("MONITOR error", e))?;
+    tasks.spawn(
+        &monitor_id,
+        monitor::run(session, filter, duration, max_lines, on_event),
+    )
+}
+
+/// Ends a MONITOR session and closes its connection.
+#[tauri::command]
+async fn stop_monitor(monitor_id: String, tasks: State<'_, Tasks>) -> Result<bool, CommandError> {
+    Ok(tasks.stop(&monitor_id))
+}
+
+// ==================== Pub/Sub ====================
+
+/// Subscribes to channels, patterns and shard channels on a connection of
+/// its own and streams the messages to `on_event` until `unsubscribe`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn subscribe(
+    connection_id: String,
+    channels: Vec<String>,
+    patterns: Vec<String>,
+    shard_channels: Vec<String>,
+    subscription_id: String,
+    on_event: tauri::ipc::Channel<PubSubEvent>,
+    state: State<'_, ConnectionManager>,
+    tasks: State<'_, Tasks>,
+) -> Result<(), CommandError> {
+    if channels.is_empty() && patterns.is_empty() && shard_channels.is_empty() {
+        return Err(CommandError::invalid("Nothing to subscribe to"));
+    }
+    let subscriptions = Subscriptions {
+        channels,
+        patterns,
+        shard_channels,
+    };
+    let messages = match state.dedicated_cluster_builder(&connection_id).await? {
+        Some(builder) => pubsub::open_cluster(builder, subscriptions).await?,
+        None => {
+            let client = state.dedicated_client(&connection_id, "Pub/Sub").await?;
+            pubsub::open(client, subscriptions).await?
+        }
+    };
+    tasks.spawn(&subscription_id, pubsub::run(messages, on_event))
+}
+
+/// Ends a subscription and closes its connection.
+#[tauri::command]
+async fn unsubscribe(
+    subscription_id: String,
+    tasks: State<'_, Tasks>,
+) -> Result<bool, CommandError> {
+    Ok(tasks.stop(&subscription_id))
+}
+
+/// PUBLISH, or SPUBLISH to a shard channel. Returns the number of clients
+/// that received the message.
+#[tauri::command]
+async fn publish(
+    connection_id: String,
+    channel: RedisBytes,
+    message: RedisBytes,
+    shard: Option<bool>,
+    state: State<'_, ConnectionManager>,
+) -> Result<i64, CommandError> {
+    let command = if shard.unwrap_or(false) {
+        "SPUBLISH"
+    } else {
+        "PUBLISH"
+    };
+    state.ensure_writable(&connection_id, command).await?;
+    let mut con = state.get_connection(&connection_id, 0).await?;
+    redis::cmd(command)
+        .arg(channel.to_bytes()?)
+        .arg(message.to_bytes()?)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis(&format!("{} error", command), e))
+}
+
+#[derive(Serialize)]
+struct ChannelInfo {
+    name: RedisBytes,
+    subscribers: i64,
+}
+
+#[derive(Serialize)]
+struct PubSubInfo {
+    channels: Vec<ChannelInfo>,
+    /// Pattern subscriptions across all clients
+    patterns: i64,
+    /// Empty before Redis 7.0
+    shard_channels: Vec<ChannelInfo>,
+}
+
+/// Active channels matching `pattern` with their subscriber counts, most
+/// subscribed first.
+async fn channel_counts(
+    con: &mut RedisConnection,
+    list: &str,
+    count: &str,
+    pattern: &str,
+) -> redis::RedisResult<Vec<ChannelInfo>> {
+    let mut names: Vec<Vec<u8>> = redis::cmd("PUBSUB")
+        .arg(list)
+        .arg(pattern)
+        .query_async(con)
+        .await?;
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    names.sort();
+
+    let counts: HashMap<Vec<u8>, i64> = redis::cmd("PUBSUB")
+        .arg(count)
+        .arg(&names)
+        .query_async(con)
+        .await?;
+    let mut channels: Vec<ChannelInfo> = names
+        .into_iter()
+        .map(|name| ChannelInfo {
+            subscribers: counts.get(&name).copied().unwrap_or(0),
+            name: RedisBytes::from(name),
+        })
+        .collect();
+    channels.sort_by_key(|c| std::cmp::Reverse(c.subscribers));
+    Ok(channels)
+}
+
+/// PUBSUB CHANNELS / NUMSUB / NUMPAT, plus the shard variants on 7.0+.
+#[tauri::command]
+async fn get_pubsub_info(
+    connection_id: String,
+    pattern: Option<String>,
+    state: State<'_, ConnectionManager>,
+) -> Result<PubSubInfo, CommandError> {
+    let pattern = pattern.filter(|p| !p.is_empty());
+    let pattern = pattern.as_deref().unwrap_or("*");
+    let mut con = state.get_connection(&connection_id, 0).await?;
+
+    let channels = channel_counts(&mut con, "CHANNELS", "NUMSUB", pattern)
+        .await
+        .map_err(|e| CommandError::redis("PUBSUB error", e))?;
+    let patterns: i64 = redis::cmd("PUBSUB")
+        .arg("NUMPAT")
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("PUBSUB error", e))?;
+    let shard_channels =
+        match channel_counts(&mut con, "SHARDCHANNELS", "SHARDNUMSUB", pattern).await {
+            Ok(shard_channels) => shard_channels,
+            Err(e) if is_unknown_command(&e) => Vec::new(),
+            Err(e) => return Err(CommandError::redis("PUBSUB error", e)),
+        };
+
+    Ok(PubSubInfo {
+        channels,
+        patterns,
+        shard_channels,
+    })
+}
+
+// ==================== Slowlog ====================
+
+/// Slowlog entries of every node plus their grouping by command and key
+/// pattern (key segments split on `delimiter`).
+#[tauri::command]
+async fn get_slowlog(
+    connection_id: String,
+    count: Option<i64>,
+    delimiter: String,
+    state: State<'_, ConnectionManager>,
+) -> Result<SlowlogReport, CommandError> {
+    let delimiter = KeyDelimiter::new(delimiter)?;
+    slowlog::report(&state, &connection_id, count, &delimiter).await
+}
+
+#[tauri::command]
+async fn reset_slowlog(
+    connection_id: String,
+    confirm_token: Option<String>,
+    state: State<'_, ConnectionManager>,
+) -> Result<(), CommandError> {
+    state
+        .ensure_writable(&connection_id, "SLOWLOG RESET")
+        .await?;
+    let argv = [b"SLOWLOG".to_vec(), b"RESET".to_vec()];
+    state
+        .confirm(&connection_id, &argv, confirm_token.as_deref())
+        .await?;
+    let mut con = state.get_connection(&connection_id, 0).await?;
+
+    let _: redis::Value = redis::cmd("SLOWLOG")
+        .arg("RESET")
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("SLOWLOG RESET error", e))?;
+    Ok(())
+}
+
+/// Sets `slowlog-log-slower-than` (microseconds, -1 disables the log, 0
+/// logs everything) and optionally `slowlog-max-len`.
+#[tauri::command]
+async fn set_slowlog_config(
+    connection_id: String,
+    threshold_us: i64,
+    max_len: Option<i64>,
+    confirm_token: Option<String>,
+    state: State<'_, ConnectionManager>,
+) -> Result<(), CommandError> {
+    if threshold_us < -1 || max_len.is_some_and(|n| n < 0) {
+        return Err(CommandError::invalid(
+            "The threshold must be -1 or more and the length not negative",
+        ));
+    }
+    state.ensure_writable(&connection_id, "CONFIG SET").await?;
+
+    let mut argv = vec![
+        b"CONFIG".to_vec(),
+        b"SET".to_vec(),
+        b"slowlog-log-slower-than".to_vec(),
+        threshold_us.to_string().into_bytes(),
+    ];
+    if let Some(max_len) = max_len {
+        argv.push(b"slowlog-max-len".to_vec());
+        argv.push(max_len.to_string().into_bytes());
+    }
+    state
+        .confirm(&connection_id, &argv, confirm_token.as_deref())
+        .await?;
+    let mut con = state.get_connection(&connection_id, 0).await?;
+
+    let mut cmd = redis::cmd("CONFIG");
+    cmd.arg("SET")
+        .arg("slowlog-log-slower-than")
+        .arg(threshold_us);
+    let _: redis::Value = cmd
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("CONFIG SET error", e))?;
+    // Separately, multiple parameters in one CONFIG SET need Redis 7
+    if let Some(max_len) = max_len {
+        let _: redis::Value = redis::cmd("CONFIG")
+            .arg("SET")
+            .arg("slowlog-max-len")
+            .arg(max_len)
+            .query_async(&mut con)
+            .await
+            .map_err(|e| CommandError::redis("CONFIG SET error", e))?;
+    }
+    Ok(())
+}
+
+// ==================== Bulk Jobs ====================
+
+/// Stops a running export, import, copy, deletion or analysis at its next
+/// batch.
+#[tauri::command]
+async fn cancel_job(job_id: String, jobs: State<'_, Jobs>) -> Result<bool, CommandError> {
+    Ok(jobs.cancel(&job_id))
+}
+
+/// Writes every selected key with type, TTL and full value to `path`.
+/// Progress is reported per batch; `cancel_job(job_id)` stops it early.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_keys(
+    connection_id: String,
+    db: i64,
+    selection: KeySelection,
+    format: ExportFormat,
+    path: String,
+    job_id: String,
+    on_progress: tauri::ipc::Channel<ExportProgress>,
+    state: State<'_, ConnectionManager>,
+    jobs: State<'_, Jobs>,
+) -> Result<ExportSummary, CommandError> {
+    let job = jobs.start(&job_id)?;
+    let batches = KeyBatches::new(&state, &connection_id, db, selection).await?;
+    export::export_keys(
+        batches,
+        format,
+        std::path::Path::new(&path),
+        &job,
+        &on_progress,
+    )
+    .await
+}
+
+/// Replays an export file into `db`. With `dry_run` nothing is written and
+/// the summary lists what the import would do.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn import_keys(
+    connection_id: String,
+    db: i64,
+    path: String,
+    format: ImportFormat,
+    conflict_policy: ConflictPolicy,
+    dry_run: bool,
+    job_id: String,
+    on_progress: tauri::ipc::Channel<ImportProgress>,
+    state: State<'_, ConnectionManager>,
+    jobs: State<'_, Jobs>,
+) -> Result<ImportSummary, CommandError> {
+    if !dry_run {
+        state.ensure_writable(&connection_id, "IMPORT").await?;
+    }
+    let job = jobs.start(&job_id)?;
+    let con = state.get_connection(&connection_id, db).await?;
+    import::import_keys(
+        con,
+        std::path::PathBuf::from(path),
+        format,
+        conflict_policy,
+        dry_run,
+        &job,
+        &on_progress,
+    )
+    .await
+}
+
+/// Copies the selected keys to `dst_db` of `dst_connection_id`, which may be
+/// the source connection itself. With `delete_source` the keys are moved.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn copy_keys(
+    src_connection_id: String,
+    src_db: i64,
+    dst_connection_id: String,
+    dst_db: i64,
+    selection: KeySelection,
+    replace: bool,
+    keep_ttl: bool,
+    delete_source: bool,
+    job_id: String,
+    on_progress: tauri::ipc::Channel<CopyProgress>,
+    state: State<'_, ConnectionManager>,
+    jobs: State<'_, Jobs>,
+) -> Result<CopySummary, CommandError> {
+    let same_server = state
+        .same_server(&src_connection_id, &dst_connection_id)
+        .await?;
+    if same_server && src_db == dst_db {
+        return Err(CommandError::invalid(
+            "Source and destination are the same database",
+        ));
+    }
+    state.ensure_writable(&dst_connection_id, "COPY").await?;
+    if delete_source {
+        state.ensure_writable(&src_connection_id, "MOVE").await?;
+    }
+
+    let job = jobs.start(&job_id)?;
+    let dst = state.get_connection(&dst_connection_id, dst_db).await?;
+    let batches = KeyBatches::new(&state, &src_connection_id, src_db, selection).await?;
+    let options = CopyOptions {
+        replace,
+        keep_ttl,
+        delete_source,
+    };
+    copy::copy_keys(
+        batches,
+        dst,
+        dst_db,
+        same_server,
+        options,
+        &job,
+        &on_progress,
+    )
+    .await
+}
+
+/// Sizes every key matching `pattern` with MEMORY USAGE and totals them per
+/// type, per key prefix (split on `delimiter`) and per TTL range. At most
+/// `sample_limit` keys are examined; partial reports stream while it runs.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn analyze_memory(
+    connection_id: String,
+    db: i64,
+    pattern: String,
+    delimiter: String,
+    sample_limit: Option<u64>,
+    top: usize,
+    job_id: String,
+    on_progress: tauri::ipc::Channel<MemoryReport>,
+    state: State<'_, ConnectionManager>,
+    jobs: State<'_, Jobs>,
+) -> Result<MemoryReport, CommandError> {
+    let options = MemoryOptions {
+        delimiter: KeyDelimiter::new(delimiter)?,
+        sample_limit,
+        top,
+    };
+    let job = jobs.start(&job_id)?;
+    let batches =
+        KeyBatches::new(&state, &connection_id, db, KeySelection::Pattern(pattern)).await?;
+    memory::analyze_memory(batches, options, &job, &on_progress).await
+}
+
+/// Deletes every key matching `pattern` with UNLINK, batch by batch. With
+/// `dry_run` nothing is deleted and the summary holds the match count and a
+/// sample of the keys.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn delete_by_pattern(
+    connection_id: String,
+    db: i64,
+    pattern: String,
+    dry_run: bool,
+    confirm_token: Option<String>,
+    job_id: String,
+    on_progress: tauri::ipc::Channel<DeleteProgress>,
+    state: State<'_, ConnectionManager>,
+    jobs: State<'_, Jobs>,
+) -> Result<DeleteSummary, CommandError> {
+    if pattern.is_empty() {
+        return Err(CommandError::invalid("The pattern must not be empty"));
+    }
+    if !dry_run {
+        state.ensure_writable(&connection_id, "UNLINK").await?;
+        state
+            .confirm_pattern_delete(&connection_id, &pattern, confirm_token.as_deref())
+            .await?;
+    }
+
+    let job = jobs.start(&job_id)?;
+    let batches =
+        KeyBatches::new(&state, &connection_id, db, KeySelection::Pattern(pattern)).await?;
+    delete::delete_keys(batches, dry_run, &job, &on_progress).await
+}
+
+// ==================== Clients ====================
+
+/// One row of CLIENT LIST.
+#[derive(Serialize)]
+struct ClientInfo {
+    id: u64,
+    addr: String,
+    name: String,
+    /// ACL user, Redis 6.0+
+    user: Option<String>,
+    /// Seconds since the connection was opened
+    age: u64,
+    /// Seconds since the last command
+    idle: u64,
+    db: i64,
+    /// Last command run
+    cmd: String,
+    flags: String,
+    /// Channels, patterns and shard channels subscribed to
+    subscriptions: u64,
+    /// Query buffer length (qbuf)
+    query_buffer: u64,
+    /// Output buffer memory (omem)
+    output_buffer: u64,
+    /// Total memory, Redis 6.0+ (tot-mem)
+    memory: Option<u64>,
+    /// Cluster master the client is connected to
+    node: Option<String>,
+    /// One of UrDis's own connections
+    own: bool,
+}
+
+/// `id=3 addr=127.0.0.1:50188 name= age=2 idle=0 flags=N db=0 ...`
+fn parse_client_line(line: &str, node: Option<&str>, own_name: &str) -> Option<ClientInfo> {
+    let fields: HashMap<&str, &str> = line
+        .split_whitespace()
+        .filter_map(|field| field.split_once('='))
+        .collect();
+    let number = |name: &str| fields.get(name).and_then(|v| v.parse::<u64>().ok());
+    let text = |name: &str| fields.get(name).unwrap_or(&"").to_string();
+
+    let name = text("name");
+    Some(ClientInfo {
+        id: number("id")?,
+        addr: text("addr"),
+        own: !name.is_empty() && name == own_name,
+        name,
+        user: fields.get("user").map(|u| u.to_string()),
+        age: number("age").unwrap_or(0),
+        idle: number("idle").unwrap_or(0),
+        db: fields.get("db").and_then(|v| v.parse().ok()).unwrap_or(0),
+        cmd: text("cmd"),
+        flags: text("flags"),
+        subscriptions: ["sub", "psub", "ssub"]
+            .iter()
+            .filter_map(|name| number(name))
+            .sum(),
+        query_buffer: number("qbuf").unwrap_or(0),
+        output_buffer: number("omem").unwrap_or(0),
+        memory: number("tot-mem"),
+        node: node.map(str::to_string),
+    })
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ClientSort {
+    Id,
+    Age,
+    Idle,
+    Db,
+    Memory,
+    QueryBuffer,
+    OutputBuffer,
+}
+
+impl ClientSort {
+    fn key(self, client: &ClientInfo) -> i64 {
+        match self {
+            ClientSort::Id => client.id as i64,
+            ClientSort::Age => client.age as i64,
+            ClientSort::Idle => client.idle as i64,
+            ClientSort::Db => client.db,
+            ClientSort::Memory => client.memory.unwrap_or(0) as i64,
+            ClientSort::QueryBuffer => client.query_buffer as i64,
+            ClientSort::OutputBuffer => client.output_buffer as i64,
+        }
+    }
+}
+
+/// CLIENT LIST of every node. `filter` is a case-insensitive substring of
+/// the address, name, user or last command.
+#[tauri::command]
+async fn client_list(
+    connection_id: String,
+    filter: Option<String>,
+    sort_by: Option<ClientSort>,
+    descending: Option<bool>,
+    state: State<'_, ConnectionManager>,
+) -> Result<Vec<ClientInfo>, CommandError> {
+    let own_name = state.client_name(&connection_id).await?;
+    let nodes = state.get_node_connections(&connection_id, 0).await?;
+    let filter = filter.unwrap_or_default().to_lowercase();
+
+    let mut clients = Vec::new();
+    for (node, mut con) in nodes {
+        let text: String = redis::cmd("CLIENT")
+            .arg("LIST")
+            .query_async(&mut con)
+            .await
+            .map_err(|e| CommandError::redis("CLIENT LIST error", e))?;
+        clients.extend(
+            text.lines()
+                .filter_map(|line| parse_client_line(line, node.as_deref(), &own_name))
+                .filter(|client| {
+                    filter.is_empty()
+                        || [&client.addr, &client.name, &client.cmd]
+                            .into_iter()
+                            .chain(client.user.as_ref())
+                            .any(|field| field.to_lowercase().contains(&filter))
+                }),
+        );
+    }
+
+    let sort_by = sort_by.unwrap_or(ClientSort::Id);
+    clients.sort_by_key(|client| sort_by.key(client));
+    if descending.unwrap_or(false) {
+        clients.reverse();
+    }
+    Ok(clients)
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "by", content = "value", rename_all = "snake_case")]
+enum ClientKillTarget {
+    Id(u64),
+    Addr(String),
+    User(String),
+}
+
+/// CLIENT KILL by id, address or ACL user, on `node` or on every node.
+/// Returns the number of clients closed.
+#[tauri::command]
+async fn client_kill(
+    connection_id: String,
+    target: ClientKillTarget,
+    node: Option<String>,
+    confirm_token: Option<String>,
+    state: State<'_, ConnectionManager>,
+) -> Result<u64, CommandError> {
+    state.ensure_writable(&connection_id, "CLIENT KILL").await?;
+    let (filter, value) = match target {
+        ClientKillTarget::Id(id) => ("ID", id.to_string()),
+        ClientKillTarget::Addr(addr) => ("ADDR", addr),
+        ClientKillTarget::User(user) => ("USER", user),
+    };
+    let argv = [
+        b"CLIENT".to_vec(),
+        b"KILL".to_vec(),
+        filter.as_bytes().to_vec(),
+        value.as_bytes().to_vec(),
+    ];
+    state
+        .confirm(&connection_id, &argv, confirm_token.as_deref())
+        .await?;
+
+    let mut killed = 0;
+    for (_, mut con) in state
+        .get_node_connections(&connection_id, 0)
+        .await?
+        .into_iter()
+        .filter(|(addr, _)| node.is_none() || *addr == node)
+    {
+        let count: u64 = redis::cmd("CLIENT")
+            .arg("KILL")
+            .arg(filter)
+            .arg(&value)
+            .query_async(&mut con)
+            .await
+            .map_err(|e| CommandError::redis("CLIENT KILL error", e))?;
+        killed += count;
+    }
+    Ok(killed)
+}
+
+/// CLIENT PAUSE on every node for `timeout_ms`. `write_only` (Redis 6.2+)
+/// keeps reads going.
+#[tauri::command]
+async fn client_pause(
+    connection_id: String,
+    timeout_ms: u64,
+    write_only: Option<bool>,
+    confirm_token: Option<String>,
+    state: State<'_, ConnectionManager>,
+) -> Result<(), CommandError> {
+    state
+        .ensure_writable(&connection_id, "CLIENT PAUSE")
+        .await?;
+    let mode = if write_only.unwrap_or(false) {
+        "WRITE"
+    } else {
+        "ALL"
+    };
+    let argv = [
+        b"CLIENT".to_vec(),
+        b"PAUSE".to_vec(),
+        timeout_ms.to_string().into_bytes(),
+        mode.as_bytes().to_vec(),
+    ];
+    state
+        .confirm(&connection_id, &argv, confirm_token.as_deref())
+        .await?;
+
+    for (_, mut con) in state.get_node_connections(&connection_id, 0).await? {
+        let mut cmd = redis::cmd("CLIENT");
+        cmd.arg("PAUSE").arg(timeout_ms);
+        // Older servers only know the implicit ALL
+        if mode == "WRITE" {
+            cmd.arg(mode);
+        }
+        cmd.exec_async(&mut con)
+            .await
+            .map_err(|e| CommandError::redis("CLIENT PAUSE error", e))?;
+    }
+    Ok(())
+}
+
+/// Ends a CLIENT PAUSE early on every node (Redis 6.2+).
+#[tauri::command]
+async fn client_unpause(
+    connection_id: String,
+    state: State<'_, ConnectionManager>,
+) -> Result<(), CommandError> {
+    state
+        .ensure_writable(&connection_id, "CLIENT UNPAUSE")
+        .await?;
+    for (_, mut con) in state.get_node_connections(&connection_id, 0).await? {
+        redis::cmd("CLIENT")
+            .arg("UNPAUSE")
+            .exec_async(&mut con)
+            .await
+            .map_err(|e| CommandError::redis("CLIENT UNPAUSE error", e))?;
+    }
+    Ok(())
+}
+
+/// CLIENT SETNAME for the session's own connections.
+#[tauri::command]
+async fn set_client_name(
+    connection_id: String,
+    name: String,
+    state: State<'_, ConnectionManager>,
+) -> Result<(), CommandError> {
+    state.set_client_name(&connection_id, &name).await
+}
+
+/// CLIENT NO-EVICT for the session's own connections (Redis 7.0+).
+#[tauri::command]
+async fn set_client_no_evict(
+    connection_id: String,
+    enabled: bool,
+    state: State<'_, ConnectionManager>,
+) -> Result<(), CommandError> {
+    state
+        .ensure_writable(&connection_id, "CLIENT NO-EVICT")
+        .await?;
+    state.set_no_evict(&connection_id, enabled).await
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    // redis-rs pulls in rustls without a default crypto provider
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_store::Builder::default().build())
+        .plugin(tauri_plugin_dialog::init())
         .manage(ConnectionManager::default())
+        .manage(Jobs::default())
+        .manage(Tasks::default())
         .invoke_handler(tauri::generate_handler![
-            connect_redis,
+            open_connection,
+            open_rdb,
+            close_connection,
+            list_connections,
             get_redis_version,
+            get_sentinel_status,
             list_keys,
             get_key_value,
             get_batch_key_values,
             get_db_sizes,
             set_key_value,
+            create_key,
             hash_scan,
             hash_get_field,
             hash_set_field,
@@ -701,8 +2746,52 @@ pub fn run() {
             hash_rename_field,
             hash_delete_field,
             hash_set_field_ttl,
+            list_range,
+            set_scan,
+            zset_range,
+            stream_range,
+            list_push,
+            list_insert,
+            list_set,
+            list_remove,
+            list_trim,
+            set_add_members,
+            set_remove_members,
+            set_move_members,
+            zset_add_members,
+            zset_update_score,
+            zset_increment_score,
+            zset_remove_members,
+            zset_remove_by_score,
+            decode_value,
+            encode_value,
             delete_keys,
             set_key_ttl,
+            execute_command,
+            cancel_job,
+            export_keys,
+            import_keys,
+            copy_keys,
+            analyze_memory,
+            delete_by_pattern,
+            get_server_info,
+            start_info_sampler,
+            stop_task,
+            get_slowlog,
+            reset_slowlog,
+            set_slowlog_config,
+            start_monitor,
+            stop_monitor,
+            subscribe,
+            unsubscribe,
+            publish,
+            get_pubsub_info,
+            client_list,
+            client_kill,
+            client_pause,
+            client_unpause,
+            set_client_name,
+            set_client_no_evict,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,349 @@
+//! Memory analysis: which keys and key prefixes take the most memory.
+//!
+//! Keys are scanned like an export and sized with MEMORY USAGE, so the
+//! numbers are what the server itself accounts for each key.
+
+use crate::connection::RedisConnection;
+use crate::error::CommandError;
+use crate::jobs::JobGuard;
+use crate::keyspace::{KeyBatches, KeyDelimiter};
+use crate::{length_command, RedisBytes};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+
+/// Prefix levels counted per key: `a:b:c:d` adds to `a`, `a:b` and `a:b:c`.
+const MAX_PREFIX_DEPTH: usize = 3;
+
+/// Distinct prefixes tracked. Keys with unique segments (ids) near the
+/// top would otherwise grow the map without bound.
+const MAX_PREFIXES: usize = 50_000;
+
+/// Prefixes included in a report, the biggest first.
+const REPORTED_PREFIXES: usize = 200;
+
+/// Partial reports are sent at most this often.
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Upper bounds (seconds) of the TTL distribution buckets; the last
+/// bucket holds everything longer.
+const TTL_BUCKETS: [u64; 4] = [60, 3600, 86400, 7 * 86400];
+
+pub struct MemoryOptions {
+    /// Separator of the key prefix levels
+    pub delimiter: KeyDelimiter,
+    /// Stop after this many keys, `None` for the whole keyspace
+    pub sample_limit: Option<u64>,
+    /// Biggest keys kept per type
+    pub top: usize,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BigKey {
+    pub key: RedisBytes,
+    pub memory: u64,
+    pub elements: u64,
+    /// Seconds left to live, -1 for persistent keys
+    pub ttl: i64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TypeStats {
+    pub key_type: String,
+    pub keys: u64,
+    pub memory: u64,
+    /// Elements (or bytes for strings) over all keys of the type
+    pub elements: u64,
+    pub biggest: Vec<BigKey>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PrefixStats {
+    pub prefix: RedisBytes,
+    pub depth: usize,
+    pub keys: u64,
+    pub memory: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct TtlBucket {
+    /// Upper bound in seconds, `None` for the open-ended last bucket
+    pub up_to: Option<u64>,
+    pub keys: u64,
+    pub memory: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MemoryReport {
+    pub scanned: u64,
+    /// Keys in the database when known (pattern `*`)
+    pub total: Option<u64>,
+    pub memory: u64,
+    pub types: Vec<TypeStats>,
+    pub prefixes: Vec<PrefixStats>,
+    /// Some prefixes were not tracked, see `MAX_PREFIXES`
+    pub prefixes_truncated: bool,
+    pub ttl: Vec<TtlBucket>,
+    pub no_ttl_keys: u64,
+    pub no_ttl_memory: u64,
+    /// The scan stopped at the sample limit
+    pub sampled: bool,
+    pub cancelled: bool,
+}
+
+struct KeyStats {
+    key: Vec<u8>,
+    key_type: String,
+    memory: u64,
+    elements: u64,
+    pttl: i64,
+}
+
+struct PrefixTotals {
+    depth: usize,
+    keys: u64,
+    memory: u64,
+}
+
+#[derive(Default)]
+struct TypeTotals {
+    keys: u64,
+    memory: u64,
+    elements: u64,
+    biggest: Vec<BigKey>,
+}
+
+struct Analysis {
+    delimiter: KeyDelimiter,
+    top: usize,
+    scanned: u64,
+    memory: u64,
+    types: HashMap<String, TypeTotals>,
+    prefixes: HashMap<Vec<u8>, PrefixTotals>,
+    prefixes_truncated: bool,
+    /// (keys, memory) per TTL bucket, the last one open-ended
+    ttl: [(u64, u64); TTL_BUCKETS.len() + 1],
+    no_ttl: (u64, u64),
+}
+
+impl Analysis {
+    fn add(&mut self, stats: KeyStats) {
+        self.scanned += 1;
+        self.memory += stats.memory;
+
+        if stats.pttl < 0 {
+            self.no_ttl.0 += 1;
+            self.no_ttl.1 += stats.memory;
+        } else {
+            let seconds = stats.pttl as u64 / 1000;
+            let bucket = TTL_BUCKETS
+                .iter()
+                .position(|&limit| seconds < limit)
+                .unwrap_or(TTL_BUCKETS.len());
+            self.ttl[bucket].0 += 1;
+            self.ttl[bucket].1 += stats.memory;
+        }
+
+        let ends = self
+            .delimiter
+            .prefix_ends(&stats.key)
+            .take(MAX_PREFIX_DEPTH);
+        for (depth, end) in ends.enumerate() {
+            let prefix = &stats.key[..end];
+            if let Some(entry) = self.prefixes.get_mut(prefix) {
+                entry.keys += 1;
+                entry.memory += stats.memory;
+            } else if self.prefixes.len() < MAX_PREFIXES {
+                self.prefixes.insert(
+                    prefix.to_vec(),
+                    PrefixTotals {
+                        depth: depth + 1,
+                        keys: 1,
+                        memory: stats.memory,
+                    },
+                );
+            } else {
+                self.prefixes_truncated = true;
+            }
+        }
+
+        let totals = self.types.entry(stats.key_type).or_default();
+        totals.keys += 1;
+        totals.memory += stats.memory;
+        totals.elements += stats.elements;
+        totals.biggest.push(BigKey {
+            key: RedisBytes::from(stats.key),
+            memory: stats.memory,
+            elements: stats.elements,
+            ttl: if stats.pttl < 0 {
+                -1
+            } else {
+                (stats.pttl + 500) / 1000
+            },
+        });
+        // Trimmed in bulk rather than on every key
+        if totals.biggest.len() >= self.top * 2 {
+            totals.biggest.sort_by_key(|k| Reverse(k.memory));
+            totals.biggest.truncate(self.top);
+        }
+    }
+
+    fn report(&self, total: Option<u64>, sampled: bool, cancelled: bool) -> MemoryReport {
+        let mut types: Vec<TypeStats> = self
+            .types
+            .iter()
+            .map(|(key_type, totals)| {
+                let mut biggest = totals.biggest.clone();
+                biggest.sort_by_key(|k| Reverse(k.memory));
+                biggest.truncate(self.top);
+                TypeStats {
+                    key_type: key_type.clone(),
+                    keys: totals.keys,
+                    memory: totals.memory,
+                    elements: totals.elements,
+                    biggest,
+                }
+            })
+            .collect();
+        types.sort_by_key(|k| Reverse(k.memory));
+
+        let mut prefixes: Vec<_> = self.prefixes.iter().collect();
+        prefixes.sort_by_key(|(_, totals)| Reverse(totals.memory));
+        let prefixes = prefixes
+            .into_iter()
+            .take(REPORTED_PREFIXES)
+            .map(|(prefix, totals)| PrefixStats {
+                prefix: RedisBytes::from(prefix.clone()),
+                depth: totals.depth,
+                keys: totals.keys,
+                memory: totals.memory,
+            })
+            .collect();
+
+        let ttl = self
+            .ttl
+            .iter()
+            .enumerate()
+            .map(|(i, &(keys, memory))| TtlBucket {
+                up_to: TTL_BUCKETS.get(i).copied(),
+                keys,
+                memory,
+            })
+            .collect();
+
+        MemoryReport {
+            scanned: self.scanned,
+            total,
+            memory: self.memory,
+            types,
+            prefixes,
+            prefixes_truncated: self.prefixes_truncated,
+            ttl,
+            no_ttl_keys: self.no_ttl.0,
+            no_ttl_memory: self.no_ttl.1,
+            sampled,
+            cancelled,
+        }
+    }
+}
+
+/// TYPE, MEMORY USAGE and PTTL of every key, then its element count.
+/// Keys that vanished in between are left out.
+async fn measure(
+    con: &mut RedisConnection,
+    keys: Vec<Vec<u8>>,
+) -> Result<Vec<KeyStats>, CommandError> {
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.cmd("TYPE").arg(key);
+        pipe.cmd("MEMORY").arg("USAGE").arg(key);
+        pipe.cmd("PTTL").arg(key);
+    }
+    let meta: Vec<(String, Option<u64>, i64)> = con
+        .query_pipeline(&pipe)
+        .await
+        .map_err(|e| CommandError::redis("Pipeline MEMORY USAGE error", e))?;
+
+    let mut pipe = redis::pipe();
+    for (key, (key_type, _, _)) in keys.iter().zip(&meta) {
+        // EXISTS keeps alignment for module types
+        pipe.cmd(length_command(key_type).unwrap_or("EXISTS"))
+            .arg(key);
+    }
+    let lengths: Vec<u64> = con
+        .query_pipeline(&pipe)
+        .await
+        .map_err(|e| CommandError::redis("Pipeline length error", e))?;
+
+    Ok(keys
+        .into_iter()
+        .zip(meta)
+        .zip(lengths)
+        .filter_map(|((key, (key_type, memory, pttl)), elements)| {
+            let memory = memory.filter(|_| key_type != "none")?;
+            Some(KeyStats {
+                key,
+                key_type,
+                memory,
+                elements,
+                pttl,
+            })
+        })
+        .collect())
+}
+
+pub async fn analyze_memory(
+    mut batches: KeyBatches,
+    options: MemoryOptions,
+    job: &JobGuard,
+    on_progress: &Channel<MemoryReport>,
+) -> Result<MemoryReport, CommandError> {
+    let mut analysis = Analysis {
+        delimiter: options.delimiter,
+        top: options.top.max(1),
+        scanned: 0,
+        memory: 0,
+        types: HashMap::new(),
+        prefixes: HashMap::new(),
+        prefixes_truncated: false,
+        ttl: Default::default(),
+        no_ttl: (0, 0),
+    };
+    let limit = options.sample_limit.unwrap_or(u64::MAX);
+
+    let mut last_report = Instant::now();
+    let mut sampled = false;
+    let mut cancelled = false;
+    while let Some((mut con, mut keys)) = batches.next().await? {
+        if job.is_cancelled() {
+            cancelled = true;
+            break;
+        }
+        let room = limit - analysis.scanned;
+        if keys.len() as u64 >= room {
+            keys.truncate(room as usize);
+            sampled = true;
+        }
+
+        for stats in measure(&mut con, keys).await? {
+            analysis.add(stats);
+        }
+
+        if sampled {
+            break;
+        }
+        if last_report.elapsed() >= REPORT_INTERVAL {
+            last_report = Instant::now();
+            // The UI may have gone away, the analysis still completes
+            let _ = on_progress.send(analysis.report(batches.total, false, false));
+        }
+    }
+
+    Ok(analysis.report(batches.total, sampled, cancelled))
+}
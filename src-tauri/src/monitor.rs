@@ -0,0 +1,220 @@
+//! MONITOR sessions: every command the server executes, filtered here and
+//! streamed to the UI in small batches.
+//!
+//! MONITOR costs the server noticeably, so a session always ends after a
+//! time and a line limit, whatever the caller asks for.
+
+use crate::keyspace::glob_match;
+use crate::RedisBytes;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::ipc::Channel;
+
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(60);
+pub const MAX_DURATION: Duration = Duration::from_secs(600);
+pub const DEFAULT_LINES: u64 = 10_000;
+pub const MAX_LINES: u64 = 100_000;
+
+/// Matched lines are sent at least this often...
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+/// ...or once this many have piled up.
+const FLUSH_LINES: usize = 500;
+
+/// Lines to keep; empty criteria match everything.
+#[derive(Deserialize, Default)]
+pub struct MonitorFilter {
+    /// Command names, any case
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// Glob matched against every argument after the command name
+    pub key_pattern: Option<String>,
+    /// Substring of the client address (`10.0.0.5:`, `lua`, ...)
+    pub client: Option<String>,
+}
+
+impl MonitorFilter {
+    fn matches(&self, line: &MonitorLine, argv: &[Vec<u8>]) -> bool {
+        if !self.commands.is_empty()
+            && !self
+                .commands
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(&line.command))
+        {
+            return false;
+        }
+        if let Some(pattern) = self.key_pattern.as_deref().filter(|p| !p.is_empty()) {
+            if !argv
+                .iter()
+                .skip(1)
+                .any(|arg| glob_match(pattern.as_bytes(), arg))
+            {
+                return false;
+            }
+        }
+        match self.client.as_deref().filter(|c| !c.is_empty()) {
+            Some(client) => line.client.contains(client),
+            None => true,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct MonitorLine {
+    /// Unix seconds with microseconds
+    pub timestamp: f64,
+    pub db: i64,
+    /// `ip:port`, `unix:/path` or `lua`
+    pub client: String,
+    /// Upper-case command name
+    pub command: String,
+    /// Full command line, name included
+    pub argv: Vec<RedisBytes>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    TimeLimit,
+    LineLimit,
+    /// The server closed the connection
+    Disconnected,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MonitorEvent {
+    Lines {
+        lines: Vec<MonitorLine>,
+        /// Lines received so far, before filtering
+        seen: u64,
+        matched: u64,
+    },
+    Stopped {
+        reason: StopReason,
+        seen: u64,
+        matched: u64,
+    },
+}
+
+/// Arguments of a MONITOR line: `"set" "k" "a\"b\x00"`.
+fn unquote_args(text: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut args = Vec::new();
+    let mut i = 0;
+    while i < text.len() {
+        if text[i] == b' ' {
+            i += 1;
+            continue;
+        }
+        if text[i] != b'"' {
+            return None;
+        }
+        i += 1;
+
+        let mut arg = Vec::new();
+        loop {
+            match *text.get(i)? {
+                b'"' => break,
+                b'\\' => {
+                    i += 1;
+                    match *text.get(i)? {
+                        b'n' => arg.push(b'\n'),
+                        b'r' => arg.push(b'\r'),
+                        b't' => arg.push(b'\t'),
+                        b'a' => arg.push(0x07),
+                        b'b' => arg.push(0x08),
+                        b'x' => {
+                            let hex = std::str::from_utf8(text.get(i + 1..i + 3)?).ok()?;
+                            arg.push(u8::from_str_radix(hex, 16).ok()?);
+                            i += 2;
+                        }
+                        other => arg.push(other),
+                    }
+                }
+                b => arg.push(b),
+            }
+            i += 1;
+        }
+        args.push(arg);
+        i += 1;
+    }
+    Some(args)
+}
+
+/// `1339518083.107412 [0 127.0.0.1:60866] "keys" "*"`
+fn parse_line(line: &str) -> Option<(MonitorLine, Vec<Vec<u8>>)> {
+    let (timestamp, rest) = line.split_once(' ')?;
+    let (source, args) = rest.strip_prefix('[')?.split_once("] ")?;
+    let (db, client) = source.split_once(' ')?;
+    let argv = unquote_args(args.as_bytes())?;
+    let command = String::from_utf8_lossy(argv.first()?).to_ascii_uppercase();
+
+    let line = MonitorLine {
+        timestamp: timestamp.parse().ok()?,
+        db: db.parse().ok()?,
+        client: client.to_string(),
+        command,
+        argv: argv.iter().cloned().map(RedisBytes::from).collect(),
+    };
+    Some((line, argv))
+}
+
+/// Streams matching lines until a limit is hit or the task is aborted.
+pub async fn run(
+    monitor: redis::aio::Monitor,
+    filter: MonitorFilter,
+    duration: Duration,
+    max_lines: u64,
+    on_event: Channel<MonitorEvent>,
+) {
+    let mut stream = monitor.into_on_message::<String>();
+    let deadline = tokio::time::sleep(duration);
+    tokio::pin!(deadline);
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+
+    let (mut seen, mut matched) = (0, 0);
+    let mut pending = Vec::new();
+    let send = |pending: &mut Vec<MonitorLine>, seen, matched| {
+        if !pending.is_empty() {
+            let _ = on_event.send(MonitorEvent::Lines {
+                lines: std::mem::take(pending),
+                seen,
+                matched,
+            });
+        }
+    };
+
+    let reason = loop {
+        tokio::select! {
+            line = stream.next() => {
+                let Some(line) = line else {
+                    break StopReason::Disconnected;
+                };
+                seen += 1;
+                let Some((line, argv)) = parse_line(&line) else {
+                    continue;
+                };
+                if !filter.matches(&line, &argv) {
+                    continue;
+                }
+                matched += 1;
+                pending.push(line);
+                if matched >= max_lines {
+                    break StopReason::LineLimit;
+                }
+                if pending.len() >= FLUSH_LINES {
+                    send(&mut pending, seen, matched);
+                }
+            }
+            _ = flush.tick() => send(&mut pending, seen, matched),
+            _ = &mut deadline => break StopReason::TimeLimit,
+        }
+    };
+
+    send(&mut pending, seen, matched);
+    let _ = on_event.send(MonitorEvent::Stopped {
+        reason,
+        seen,
+        matched,
+    });
+}
@@ -0,0 +1,243 @@
+//! Pub/Sub subscriptions on their own connection, streamed to the UI in
+//! small batches until stopped.
+
+use crate::error::CommandError;
+use crate::RedisBytes;
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+use redis::cluster::ClusterClientBuilder;
+use redis::{Msg, PushInfo, PushKind};
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::ipc::Channel;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Messages are sent at least this often...
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+/// ...or once this many have piled up.
+const FLUSH_MESSAGES: usize = 500;
+
+#[derive(Serialize, Clone)]
+pub struct PubSubMessage {
+    pub channel: RedisBytes,
+    /// Set for PSUBSCRIBE matches
+    pub pattern: Option<RedisBytes>,
+    /// Received through SSUBSCRIBE
+    pub shard: bool,
+    pub payload: RedisBytes,
+    /// When the message was received, in Unix milliseconds
+    pub time_ms: u64,
+}
+
+impl PubSubMessage {
+    fn new(msg: &Msg, shard: bool) -> Self {
+        PubSubMessage {
+            channel: RedisBytes::from(msg.get_channel::<Vec<u8>>().unwrap_or_default()),
+            pattern: msg
+                .from_pattern()
+                .then(|| msg.get_pattern::<Vec<u8>>().ok())
+                .flatten()
+                .map(RedisBytes::from),
+            shard,
+            payload: RedisBytes::from(msg.get_payload_bytes().to_vec()),
+            time_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PubSubEvent {
+    Messages {
+        messages: Vec<PubSubMessage>,
+        /// Messages received so far
+        received: u64,
+    },
+    /// The server closed the connection
+    Closed { received: u64 },
+}
+
+/// What to subscribe to; at least one list must be non-empty.
+pub struct Subscriptions {
+    pub channels: Vec<String>,
+    pub patterns: Vec<String>,
+    /// Shard channels, Redis 7.0+
+    pub shard_channels: Vec<String>,
+}
+
+/// Opens the connection and subscribes, so that errors reach the caller
+/// before anything is spawned.
+pub async fn open(
+    client: redis::Client,
+    subscriptions: Subscriptions,
+) -> Result<BoxStream<'static, PubSubMessage>, CommandError> {
+    let Subscriptions {
+        channels,
+        patterns,
+        shard_channels,
+    } = subscriptions;
+
+    if shard_channels.is_empty() {
+        let mut pubsub = client
+            .get_async_pubsub()
+            .await
+            .map_err(|e| CommandError::redis("Pub/Sub connection failed", e))?;
+        if !channels.is_empty() {
+            pubsub
+                .subscribe(&channels)
+                .await
+                .map_err(|e| CommandError::redis("SUBSCRIBE error", e))?;
+        }
+        if !patterns.is_empty() {
+            pubsub
+                .psubscribe(&patterns)
+                .await
+                .map_err(|e| CommandError::redis("PSUBSCRIBE error", e))?;
+        }
+        return Ok(pubsub
+            .into_on_message()
+            .map(|msg| PubSubMessage::new(&msg, false))
+            .boxed());
+    }
+
+    // aio::PubSub has no SSUBSCRIBE: subscribe on a RESP3 connection and
+    // read the pushes instead. Shard channels need 7.0, which speaks RESP3.
+    let info = client.get_connection_info().clone();
+    let settings = info
+        .redis_settings()
+        .clone()
+        .set_protocol(redis::ProtocolVersion::RESP3);
+    let client = redis::Client::open(info.set_redis_settings(settings))
+        .map_err(|e| CommandError::redis("Failed to create Redis client", e))?;
+
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    let config = redis::AsyncConnectionConfig::new().set_push_sender(sender);
+    let mut con = client
+        .get_multiplexed_async_connection_with_config(&config)
+        .await
+        .map_err(|e| CommandError::redis("Pub/Sub connection failed", e))?;
+    for (command, names) in [
+        ("SUBSCRIBE", &channels),
+        ("PSUBSCRIBE", &patterns),
+        ("SSUBSCRIBE", &shard_channels),
+    ] {
+        if !names.is_empty() {
+            redis::cmd(command)
+                .arg(names)
+                .exec_async(&mut con)
+                .await
+                .map_err(|e| CommandError::redis(&format!("{} error", command), e))?;
+        }
+    }
+
+    Ok(push_messages(receiver, con, true))
+}
+
+/// `open` for a cluster. The cluster connection sends each subscription
+/// to a node owning its slot and resubscribes after reconnecting, which
+/// is what shard channels need. Needs RESP3 (Redis 6.0+).
+pub async fn open_cluster(
+    builder: ClusterClientBuilder,
+    subscriptions: Subscriptions,
+) -> Result<BoxStream<'static, PubSubMessage>, CommandError> {
+    let Subscriptions {
+        channels,
+        patterns,
+        shard_channels,
+    } = subscriptions;
+
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    let client = builder
+        .use_protocol(redis::ProtocolVersion::RESP3)
+        .push_sender(sender)
+        .build()
+        .map_err(|e| CommandError::redis("Failed to create cluster client", e))?;
+    let mut con = client
+        .get_async_connection()
+        .await
+        .map_err(|e| CommandError::redis("Pub/Sub connection failed", e))?;
+
+    // Plain messages reach every node, any one will do
+    if !channels.is_empty() {
+        con.subscribe(&channels)
+            .await
+            .map_err(|e| CommandError::redis("SUBSCRIBE error", e))?;
+    }
+    if !patterns.is_empty() {
+        con.psubscribe(&patterns)
+            .await
+            .map_err(|e| CommandError::redis("PSUBSCRIBE error", e))?;
+    }
+    // One at a time, an SSUBSCRIBE cannot span slots
+    for channel in &shard_channels {
+        con.ssubscribe(channel)
+            .await
+            .map_err(|e| CommandError::redis("SSUBSCRIBE error", e))?;
+    }
+
+    Ok(push_messages(receiver, con, false))
+}
+
+/// Turns the pushes of a RESP3 connection into messages. The connection
+/// lives as long as the stream. It ends on a disconnection unless the
+/// connection resubscribes by itself.
+fn push_messages<C: Send + 'static>(
+    receiver: UnboundedReceiver<PushInfo>,
+    con: C,
+    end_on_disconnect: bool,
+) -> BoxStream<'static, PubSubMessage> {
+    stream::unfold((receiver, con), move |(mut receiver, con)| async move {
+        loop {
+            let push = receiver.recv().await?;
+            if push.kind == PushKind::Disconnection {
+                if end_on_disconnect {
+                    return None;
+                }
+                continue;
+            }
+            let shard = push.kind == PushKind::SMessage;
+            // Subscribe confirmations and the like
+            if let Some(msg) = Msg::from_push_info(push) {
+                return Some((PubSubMessage::new(&msg, shard), (receiver, con)));
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Forwards messages until the connection closes or the task is aborted.
+pub async fn run(mut messages: BoxStream<'static, PubSubMessage>, on_event: Channel<PubSubEvent>) {
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+    let mut received = 0;
+    let mut pending = Vec::new();
+    let send = |pending: &mut Vec<PubSubMessage>, received| {
+        if !pending.is_empty() {
+            let _ = on_event.send(PubSubEvent::Messages {
+                messages: std::mem::take(pending),
+                received,
+            });
+        }
+    };
+
+    loop {
+        tokio::select! {
+            message = messages.next() => {
+                let Some(message) = message else {
+                    break;
+                };
+                received += 1;
+                pending.push(message);
+                if pending.len() >= FLUSH_MESSAGES {
+                    send(&mut pending, received);
+                }
+            }
+            _ = flush.tick() => send(&mut pending, received),
+        }
+    }
+
+    send(&mut pending, received);
+    let _ = on_event.send(PubSubEvent::Closed { received });
+}
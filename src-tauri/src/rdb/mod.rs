@@ -0,0 +1,874 @@
+//! Offline browsing of RDB snapshots.
+//!
+//! Opening a file indexes every key (name, type, expiry and where its value
+//! starts); a value is only parsed when the key is looked at. The views
+//! below return the same shapes as the commands against a live server, so
+//! the explorer browses a snapshot like a read-only connection.
+
+mod packed;
+mod reader;
+
+use crate::error::{CommandError, ErrorKind};
+use crate::keyspace::glob_match;
+use crate::{
+    format_redis_bytes_list, format_redis_string, split_stream_page, RedisBytes, RedisKeyData,
+    RedisKeyInfo, RedisValue, StreamConsumerGroup, StreamEntry, StreamInfo, StreamPage,
+    StreamValue, ZSetRangeBy, STREAM_PAGE_SIZE, VALUE_PAGE_SIZE,
+};
+use reader::{
+    parse_f64, Reader, Stream, StreamId, Value, TYPE_HASH, TYPE_HASH_LISTPACK,
+    TYPE_HASH_LISTPACK_EX, TYPE_HASH_LISTPACK_EX_PRE_GA, TYPE_HASH_METADATA,
+    TYPE_HASH_METADATA_PRE_GA, TYPE_HASH_ZIPLIST, TYPE_HASH_ZIPMAP, TYPE_LIST, TYPE_LIST_QUICKLIST,
+    TYPE_LIST_QUICKLIST_2, TYPE_LIST_ZIPLIST, TYPE_MODULE, TYPE_MODULE_2, TYPE_SET,
+    TYPE_SET_INTSET, TYPE_SET_LISTPACK, TYPE_STREAM_LISTPACKS, TYPE_STREAM_LISTPACKS_2,
+    TYPE_STREAM_LISTPACKS_3, TYPE_STRING, TYPE_ZSET, TYPE_ZSET_2, TYPE_ZSET_LISTPACK,
+    TYPE_ZSET_ZIPLIST,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const OPCODE_SLOT_INFO: u8 = 0xf4;
+const OPCODE_FUNCTION_2: u8 = 0xf5;
+const OPCODE_FUNCTION_PRE_GA: u8 = 0xf6;
+const OPCODE_MODULE_AUX: u8 = 0xf7;
+const OPCODE_IDLE: u8 = 0xf8;
+const OPCODE_FREQ: u8 = 0xf9;
+const OPCODE_AUX: u8 = 0xfa;
+const OPCODE_RESIZEDB: u8 = 0xfb;
+const OPCODE_EXPIRETIME_MS: u8 = 0xfc;
+const OPCODE_EXPIRETIME: u8 = 0xfd;
+const OPCODE_SELECTDB: u8 = 0xfe;
+const OPCODE_EOF: u8 = 0xff;
+
+/// Newest format we understand (Redis 7.4).
+const MAX_VERSION: u32 = 12;
+
+/// Keys examined per `list_keys` call, like the COUNT of a SCAN.
+const SCAN_BATCH: usize = 1000;
+
+/// Module type ids pack a 9 character name in this alphabet.
+const MODULE_NAME_CHARSET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+struct RdbKey {
+    name: Vec<u8>,
+    kind: u8,
+    /// Position of the value in the file, and its size on disk
+    offset: u64,
+    size: u64,
+    expire_ms: Option<i64>,
+}
+
+/// What the UI shows after opening a snapshot.
+#[derive(Serialize)]
+pub struct RdbSummary {
+    pub connection_id: String,
+    pub rdb_version: u32,
+    pub redis_version: Option<String>,
+    pub keys: u64,
+    /// Keys that could not be loaded, e.g. module types
+    pub warnings: Vec<String>,
+}
+
+pub struct RdbFile {
+    path: PathBuf,
+    version: u32,
+    redis_version: Option<String>,
+    /// When the snapshot was taken; TTLs are shown relative to it
+    ctime_ms: i64,
+    /// Keys of every database, sorted by name
+    dbs: BTreeMap<i64, Vec<RdbKey>>,
+    /// Skipped module keys per module name
+    skipped_modules: BTreeMap<String, u64>,
+    /// Skipped Redis 7.0 release candidate functions
+    skipped_functions: u64,
+    /// Key whose old-style module value ended indexing, and its module
+    stopped_at: Option<(Vec<u8>, String)>,
+    /// The last value parsed, so paging through a big key reads it once
+    last_value: Mutex<Option<LastValue>>,
+}
+
+struct LastValue {
+    db: i64,
+    name: Vec<u8>,
+    value: Arc<Value>,
+}
+
+/// Runs `f`, which reads the snapshot file, off the async runtime.
+pub async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, CommandError> + Send + 'static,
+) -> Result<T, CommandError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| CommandError::new(ErrorKind::Internal, format!("RDB task failed: {}", e)))?
+}
+
+fn wrong_type() -> CommandError {
+    CommandError::new(
+        ErrorKind::WrongType,
+        "Operation against a key holding the wrong kind of value",
+    )
+}
+
+fn module_name(id: u64) -> String {
+    // 9 characters of 6 bits, above a 10 bit encoding version
+    (0..9)
+        .rev()
+        .map(|i| char::from(MODULE_NAME_CHARSET[((id >> (10 + 6 * i)) & 63) as usize]))
+        .collect()
+}
+
+fn type_name(kind: u8) -> &'static str {
+    match kind {
+        TYPE_STRING => "string",
+        TYPE_LIST | TYPE_LIST_ZIPLIST | TYPE_LIST_QUICKLIST | TYPE_LIST_QUICKLIST_2 => "list",
+        TYPE_SET | TYPE_SET_INTSET | TYPE_SET_LISTPACK => "set",
+        TYPE_ZSET | TYPE_ZSET_2 | TYPE_ZSET_ZIPLIST | TYPE_ZSET_LISTPACK => "zset",
+        TYPE_STREAM_LISTPACKS | TYPE_STREAM_LISTPACKS_2 | TYPE_STREAM_LISTPACKS_3 => "stream",
+        _ => "hash",
+    }
+}
+
+/// The OBJECT ENCODING the value had when it was saved.
+fn encoding_name(kind: u8, value: &Value) -> &'static str {
+    match (kind, value) {
+        (TYPE_STRING, Value::String(s)) => {
+            let is_int =
+                s.len() <= 20 && std::str::from_utf8(s).is_ok_and(|s| s.parse::<i64>().is_ok());
+            if is_int {
+                "int"
+            } else if s.len() <= 44 {
+                "embstr"
+            } else {
+                "raw"
+            }
+        }
+        (TYPE_LIST, _) => "linkedlist",
+        (TYPE_SET | TYPE_HASH | TYPE_HASH_METADATA_PRE_GA | TYPE_HASH_METADATA, _) => "hashtable",
+        (TYPE_ZSET | TYPE_ZSET_2, _) => "skiplist",
+        (TYPE_HASH_ZIPMAP, _) => "zipmap",
+        (TYPE_LIST_ZIPLIST | TYPE_ZSET_ZIPLIST | TYPE_HASH_ZIPLIST, _) => "ziplist",
+        (TYPE_SET_INTSET, _) => "intset",
+        (TYPE_LIST_QUICKLIST | TYPE_LIST_QUICKLIST_2, _) => "quicklist",
+        (TYPE_HASH_LISTPACK | TYPE_ZSET_LISTPACK | TYPE_SET_LISTPACK, _) => "listpack",
+        (TYPE_HASH_LISTPACK_EX_PRE_GA | TYPE_HASH_LISTPACK_EX, _) => "listpackex",
+        _ => "stream",
+    }
+}
+
+/// LRANGE style inclusive range with negative indexes from the end.
+fn index_range(len: usize, start: i64, stop: i64) -> std::ops::Range<usize> {
+    let len = len as i64;
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+    if start > stop {
+        0..0
+    } else {
+        start as usize..stop as usize + 1
+    }
+}
+
+fn parse_stream_id(id: &str) -> Result<StreamId, CommandError> {
+    let (ms, seq) = id.split_once('-').unwrap_or((id, "0"));
+    ms.parse()
+        .ok()
+        .zip(seq.parse().ok())
+        .map(|(ms, seq)| StreamId { ms, seq })
+        .ok_or_else(|| CommandError::invalid(format!("Invalid stream ID: {}", id)))
+}
+
+fn stream_entry(id: &StreamId, fields: &[(Vec<u8>, Vec<u8>)]) -> StreamEntry {
+    StreamEntry {
+        id: id.to_string(),
+        fields: fields
+            .iter()
+            .map(|(f, v)| (RedisBytes::from(f.clone()), RedisBytes::from(v.clone())))
+            .collect(),
+    }
+}
+
+/// Score range bound, `(` makes it exclusive.
+fn score_bound(bound: Option<&str>, default: f64) -> Result<(f64, bool), CommandError> {
+    let Some(bound) = bound else {
+        return Ok((default, false));
+    };
+    let (exclusive, number) = match bound.strip_prefix('(') {
+        Some(number) => (true, number),
+        None => (false, bound),
+    };
+    parse_f64(number)
+        .map(|score| (score, exclusive))
+        .ok_or_else(|| CommandError::invalid("min or max is not a float"))
+}
+
+enum LexBound {
+    Min,
+    Max,
+    Inclusive(Vec<u8>),
+    Exclusive(Vec<u8>),
+}
+
+fn lex_bound(bound: Option<&str>, default: LexBound) -> Result<LexBound, CommandError> {
+    let Some(bound) = bound else {
+        return Ok(default);
+    };
+    match bound.as_bytes() {
+        b"-" => Ok(LexBound::Min),
+        b"+" => Ok(LexBound::Max),
+        [b'[', rest @ ..] => Ok(LexBound::Inclusive(rest.to_vec())),
+        [b'(', rest @ ..] => Ok(LexBound::Exclusive(rest.to_vec())),
+        _ => Err(CommandError::invalid(
+            "min or max not valid string range item",
+        )),
+    }
+}
+
+impl RdbFile {
+    /// Indexes the snapshot at `path`.
+    pub fn open(path: &str) -> Result<RdbFile, CommandError> {
+        let file = File::open(path)
+            .map_err(|e| CommandError::invalid(format!("Cannot open {}: {}", path, e)))?;
+
+        let mut rdb = RdbFile::new(PathBuf::from(path));
+        rdb.index(&mut Reader::new(BufReader::new(file)))
+            .map_err(|e| CommandError::new(ErrorKind::Parse, format!("Invalid RDB file: {}", e)))?;
+        Ok(rdb)
+    }
+
+    fn new(path: PathBuf) -> RdbFile {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        RdbFile {
+            path,
+            version: 0,
+            redis_version: None,
+            ctime_ms: now_ms,
+            dbs: BTreeMap::new(),
+            skipped_modules: BTreeMap::new(),
+            skipped_functions: 0,
+            stopped_at: None,
+            last_value: Mutex::new(None),
+        }
+    }
+
+    fn index<R: Read>(&mut self, r: &mut Reader<R>) -> reader::Result<()> {
+        let magic = r.array::<9>()?;
+        if &magic[..5] != b"REDIS" {
+            return Err("not an RDB file".to_string());
+        }
+        self.version = std::str::from_utf8(&magic[5..])
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .ok_or("invalid RDB version")?;
+        if self.version > MAX_VERSION {
+            return Err(format!(
+                "RDB version {} is newer than supported ({})",
+                self.version, MAX_VERSION
+            ));
+        }
+
+        let mut db = 0;
+        let mut expire_ms = None;
+        loop {
+            match r.u8()? {
+                OPCODE_EOF => break,
+                OPCODE_SELECTDB => db = r.len()? as i64,
+                OPCODE_EXPIRETIME => {
+                    expire_ms = Some(i64::from(u32::from_le_bytes(r.array()?)) * 1000)
+                }
+                OPCODE_EXPIRETIME_MS => expire_ms = Some(r.millis()?),
+                OPCODE_RESIZEDB => {
+                    r.len()?;
+                    r.len()?;
+                }
+                OPCODE_AUX => {
+                    let field = r.string()?;
+                    let value = String::from_utf8_lossy(&r.string()?).into_owned();
+                    match field.as_slice() {
+                        b"redis-ver" => self.redis_version = Some(value),
+                        b"ctime" => {
+                            if let Ok(seconds) = value.parse::<i64>() {
+                                self.ctime_ms = seconds * 1000;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                // LFU / LRU hints of the next key
+                OPCODE_FREQ => {
+                    r.u8()?;
+                }
+                OPCODE_IDLE => {
+                    r.len()?;
+                }
+                OPCODE_MODULE_AUX => r.skip_module_aux()?,
+                OPCODE_FUNCTION_2 => r.skip_string()?,
+                OPCODE_FUNCTION_PRE_GA => {
+                    r.skip_function_pre_ga()?;
+                    self.skipped_functions += 1;
+                }
+                OPCODE_SLOT_INFO => {
+                    r.len()?;
+                    r.len()?;
+                    r.len()?;
+                }
+                kind => {
+                    let name = r.string()?;
+                    let offset = r.pos();
+                    let expire_ms = expire_ms.take();
+                    match kind {
+                        TYPE_MODULE_2 => {
+                            let id = r.skip_module_value()?;
+                            *self.skipped_modules.entry(module_name(id)).or_default() += 1;
+                            continue;
+                        }
+                        // Only the module itself knows where these end, so
+                        // keep what was indexed so far and stop
+                        TYPE_MODULE => {
+                            self.stopped_at = Some((name, module_name(r.len()?)));
+                            break;
+                        }
+                        _ => r.skip_value(kind)?,
+                    }
+                    // Redis drops keys that expired before the save when loading
+                    if expire_ms.is_some_and(|at| at <= self.ctime_ms) {
+                        continue;
+                    }
+                    self.dbs.entry(db).or_default().push(RdbKey {
+                        name,
+                        kind,
+                        offset,
+                        size: r.pos() - offset,
+                        expire_ms,
+                    });
+                }
+            }
+        }
+
+        for keys in self.dbs.values_mut() {
+            keys.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        Ok(())
+    }
+
+    pub fn summary(&self, connection_id: String) -> RdbSummary {
+        RdbSummary {
+            connection_id,
+            rdb_version: self.version,
+            redis_version: self.redis_version.clone(),
+            keys: self.dbs.values().map(|keys| keys.len() as u64).sum(),
+            warnings: self.warnings(),
+        }
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings: Vec<String> = self
+            .skipped_modules
+            .iter()
+            .map(|(module, count)| format!("Skipped {} keys of module type {}", count, module))
+            .collect();
+        if self.skipped_functions > 0 {
+            warnings.push(format!(
+                "Skipped {} functions saved by a Redis 7.0 release candidate",
+                self.skipped_functions
+            ));
+        }
+        if let Some((key, module)) = &self.stopped_at {
+            warnings.push(format!(
+                "Stopped at key {}: values of module type {} from RDB version 8 and older \
+                 cannot be skipped, the keys after it were not loaded",
+                String::from_utf8_lossy(key),
+                module
+            ));
+        }
+        warnings
+    }
+
+    pub fn redis_version(&self) -> Result<String, CommandError> {
+        self.redis_version.clone().ok_or_else(|| {
+            CommandError::new(
+                ErrorKind::Parse,
+                "The RDB file does not record a Redis version",
+            )
+        })
+    }
+
+    pub fn db_sizes(&self) -> Vec<i64> {
+        let count = self.dbs.keys().last().map_or(0, |db| db + 1).max(16);
+        (0..count)
+            .map(|db| self.dbs.get(&db).map_or(0, |keys| keys.len() as i64))
+            .collect()
+    }
+
+    /// SCAN over the sorted index; the cursor is the position in it.
+    pub fn list_keys(
+        &self,
+        db: i64,
+        cursor: &str,
+        pattern: &str,
+    ) -> Result<(String, Vec<RedisKeyInfo>), CommandError> {
+        let start: usize = cursor
+            .parse()
+            .map_err(|_| CommandError::invalid(format!("Invalid SCAN cursor: {}", cursor)))?;
+        let keys = self.dbs.get(&db).map(Vec::as_slice).unwrap_or_default();
+        let end = start.saturating_add(SCAN_BATCH).min(keys.len());
+        let pattern = if pattern.is_empty() { "*" } else { pattern };
+
+        let found = keys
+            .get(start..end)
+            .unwrap_or_default()
+            .iter()
+            .filter(|key| glob_match(pattern.as_bytes(), &key.name))
+            .map(|key| RedisKeyInfo {
+                name: RedisBytes::from(key.name.clone()),
+                key_type: type_name(key.kind).to_string(),
+            })
+            .collect();
+        let next_cursor = if end >= keys.len() {
+            "0".to_string()
+        } else {
+            end.to_string()
+        };
+        Ok((next_cursor, found))
+    }
+
+    fn key(&self, db: i64, name: &[u8]) -> Option<&RdbKey> {
+        let keys = self.dbs.get(&db)?;
+        keys.binary_search_by(|key| key.name.as_slice().cmp(name))
+            .ok()
+            .map(|i| &keys[i])
+    }
+
+    fn read_value(&self, key: &RdbKey) -> reader::Result<Value> {
+        let mut file = File::open(&self.path).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::Start(key.offset))
+            .map_err(|e| e.to_string())?;
+        let mut value = Reader::new(BufReader::new(file.take(key.size))).value(key.kind)?;
+        if let Value::ZSet(members) = &mut value {
+            members.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        }
+        Ok(value)
+    }
+
+    fn load(&self, db: i64, name: &[u8]) -> Result<Option<(&RdbKey, Arc<Value>)>, CommandError> {
+        let Some(key) = self.key(db, name) else {
+            return Ok(None);
+        };
+
+        let mut last = self.last_value.lock().unwrap();
+        if let Some(last) = last.as_ref().filter(|l| l.db == db && l.name == name) {
+            return Ok(Some((key, last.value.clone())));
+        }
+        let value = Arc::new(self.read_value(key).map_err(|e| {
+            CommandError::new(
+                ErrorKind::Parse,
+                format!("Cannot read {}: {}", String::from_utf8_lossy(name), e),
+            )
+        })?);
+        *last = Some(LastValue {
+            db,
+            name: name.to_vec(),
+            value: value.clone(),
+        });
+        Ok(Some((key, value)))
+    }
+
+    fn ttl(&self, key: &RdbKey) -> i64 {
+        match key.expire_ms {
+            // Rounded like the TTL command
+            Some(at) => (at - self.ctime_ms + 500) / 1000,
+            None => -1,
+        }
+    }
+
+    fn stream_value(stream: &Stream) -> StreamValue {
+        let StreamPage { entries, next_id } = split_stream_page(
+            stream
+                .entries
+                .iter()
+                .take(STREAM_PAGE_SIZE + 1)
+                .map(|(id, fields)| stream_entry(id, fields))
+                .collect(),
+            STREAM_PAGE_SIZE,
+        );
+        let info = StreamInfo {
+            length: stream.length as i64,
+            radix_tree_keys: stream.nodes as i64,
+            // Not stored in the file
+            radix_tree_nodes: 0,
+            last_generated_id: stream.last_id.to_string(),
+            first_entry_id: stream.entries.first().map(|(id, _)| id.to_string()),
+            last_entry_id: stream.entries.last().map(|(id, _)| id.to_string()),
+            groups: stream.groups.len() as i64,
+        };
+        let groups = stream
+            .groups
+            .iter()
+            .map(|group| StreamConsumerGroup {
+                name: String::from_utf8_lossy(&group.name).into_owned(),
+                consumers: group.consumers as i64,
+                pending: group.pending as i64,
+                last_delivered_id: group.last_id.to_string(),
+                lag: None,
+            })
+            .collect();
+        StreamValue {
+            entries,
+            next_id,
+            info: Some(info),
+            groups,
+        }
+    }
+
+    /// `get_key_value` for a snapshot key: the first page of the value.
+    pub fn key_data(&self, db: i64, key: &[u8]) -> Result<RedisKeyData, CommandError> {
+        let Some((meta, value)) = self.load(db, key)? else {
+            return Ok(RedisKeyData {
+                key_type: "none".to_string(),
+                value: RedisValue::None,
+                length: 0,
+                ttl: -2,
+                memory: 0,
+                encoding: "none".to_string(),
+            });
+        };
+
+        let page = |items: &[Vec<u8>]| {
+            format_redis_bytes_list(items.iter().take(VALUE_PAGE_SIZE).cloned().collect())
+        };
+        let (length, redis_value) = match &*value {
+            Value::String(s) => (s.len() as u64, format_redis_string(s.clone())),
+            Value::List(items) => (items.len() as u64, RedisValue::List(page(items))),
+            Value::Set(items) => (items.len() as u64, RedisValue::Set(page(items))),
+            Value::ZSet(members) => (
+                members.len() as u64,
+                RedisValue::ZSet(
+                    members
+                        .iter()
+                        .take(VALUE_PAGE_SIZE)
+                        .map(|(m, s)| (RedisBytes::from(m.clone()), *s))
+                        .collect(),
+                ),
+            ),
+            Value::Hash(pairs) => (
+                pairs.len() as u64,
+                RedisValue::Hash(
+                    pairs
+                        .iter()
+                        .take(VALUE_PAGE_SIZE)
+                        .map(|(f, v)| (RedisBytes::from(f.clone()), RedisBytes::from(v.clone())))
+                        .collect(),
+                ),
+            ),
+            Value::Stream(stream) => (
+                stream.length,
+                RedisValue::Stream(Self::stream_value(stream)),
+            ),
+        };
+
+        Ok(RedisKeyData {
+            key_type: type_name(meta.kind).to_string(),
+            value: redis_value,
+            length: length as i64,
+            ttl: self.ttl(meta),
+            memory: meta.size as i64,
+            encoding: encoding_name(meta.kind, &value).to_string(),
+        })
+    }
+
+    pub fn batch_values(&self, db: i64, keys: &[Vec<u8>]) -> Result<Vec<RedisValue>, CommandError> {
+        keys.iter()
+            .map(|key| self.key_data(db, key).map(|data| data.value))
+            .collect()
+    }
+
+    pub fn list_range(
+        &self,
+        db: i64,
+        key: &[u8],
+        start: i64,
+        stop: i64,
+    ) -> Result<Vec<RedisBytes>, CommandError> {
+        match self.load(db, key)?.as_ref().map(|(_, v)| &**v) {
+            None => Ok(Vec::new()),
+            Some(Value::List(items)) => Ok(format_redis_bytes_list(
+                items[index_range(items.len(), start, stop)].to_vec(),
+            )),
+            Some(_) => Err(wrong_type()),
+        }
+    }
+
+    /// SSCAN with the position in the member list as cursor.
+    pub fn set_scan(
+        &self,
+        db: i64,
+        key: &[u8],
+        cursor: u64,
+        count: usize,
+        pattern: Option<&str>,
+    ) -> Result<(u64, Vec<RedisBytes>), CommandError> {
+        let loaded = self.load(db, key)?;
+        let members = match loaded.as_ref().map(|(_, v)| &**v) {
+            None => return Ok((0, Vec::new())),
+            Some(Value::Set(members)) => members,
+            Some(_) => return Err(wrong_type()),
+        };
+        let start = (cursor as usize).min(members.len());
+        let end = start.saturating_add(count.max(1)).min(members.len());
+        let pattern = pattern.filter(|p| !p.is_empty()).unwrap_or("*");
+
+        let found = members[start..end]
+            .iter()
+            .filter(|m| glob_match(pattern.as_bytes(), m))
+            .cloned()
+            .collect();
+        let next = if end >= members.len() { 0 } else { end as u64 };
+        Ok((next, format_redis_bytes_list(found)))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn zset_range(
+        &self,
+        db: i64,
+        key: &[u8],
+        by: ZSetRangeBy,
+        min: Option<&str>,
+        max: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(RedisBytes, f64)>, CommandError> {
+        let loaded = self.load(db, key)?;
+        let members = match loaded.as_ref().map(|(_, v)| &**v) {
+            None => return Ok(Vec::new()),
+            Some(Value::ZSet(members)) => members,
+            Some(_) => return Err(wrong_type()),
+        };
+
+        type Member = (Vec<u8>, f64);
+        let in_range: Box<dyn Fn(&Member) -> bool> = match by {
+            ZSetRangeBy::Rank => Box::new(|_| true),
+            ZSetRangeBy::Score => {
+                let (low, low_exclusive) = score_bound(min, f64::NEG_INFINITY)?;
+                let (high, high_exclusive) = score_bound(max, f64::INFINITY)?;
+                Box::new(move |(_, score)| {
+                    (if low_exclusive {
+                        *score > low
+                    } else {
+                        *score >= low
+                    }) && (if high_exclusive {
+                        *score < high
+                    } else {
+                        *score <= high
+                    })
+                })
+            }
+            ZSetRangeBy::Lex => {
+                let low = lex_bound(min, LexBound::Min)?;
+                let high = lex_bound(max, LexBound::Max)?;
+                Box::new(move |(member, _)| {
+                    let above = match &low {
+                        LexBound::Min => true,
+                        LexBound::Max => false,
+                        LexBound::Inclusive(b) => member >= b,
+                        LexBound::Exclusive(b) => member > b,
+                    };
+                    let below = match &high {
+                        LexBound::Min => false,
+                        LexBound::Max => true,
+                        LexBound::Inclusive(b) => member <= b,
+                        LexBound::Exclusive(b) => member < b,
+                    };
+                    above && below
+                })
+            }
+        };
+
+        Ok(members
+            .iter()
+            .filter(|m| in_range(m))
+            .skip(offset)
+            .take(limit)
+            .map(|(m, s)| (RedisBytes::from(m.clone()), *s))
+            .collect())
+    }
+
+    pub fn stream_range(
+        &self,
+        db: i64,
+        key: &[u8],
+        start: Option<&str>,
+        count: usize,
+    ) -> Result<StreamPage, CommandError> {
+        let loaded = self.load(db, key)?;
+        let stream = match loaded.as_ref().map(|(_, v)| &**v) {
+            None => {
+                return Ok(StreamPage {
+                    entries: Vec::new(),
+                    next_id: None,
+                })
+            }
+            Some(Value::Stream(stream)) => stream,
+            Some(_) => return Err(wrong_type()),
+        };
+        let from = match start.filter(|s| *s != "-") {
+            Some(id) => {
+                let id = parse_stream_id(id)?;
+                stream.entries.partition_point(|(entry, _)| *entry < id)
+            }
+            None => 0,
+        };
+
+        Ok(split_stream_page(
+            stream.entries[from..]
+                .iter()
+                .take(count + 1)
+                .map(|(id, fields)| stream_entry(id, fields))
+                .collect(),
+            count,
+        ))
+    }
+
+    /// HSCAN returning field names, with the field position as cursor.
+    pub fn hash_scan(
+        &self,
+        db: i64,
+        key: &[u8],
+        cursor: u64,
+        count: usize,
+    ) -> Result<(u64, Vec<RedisBytes>), CommandError> {
+        let loaded = self.load(db, key)?;
+        let pairs = match loaded.as_ref().map(|(_, v)| &**v) {
+            None => return Ok((0, Vec::new())),
+            Some(Value::Hash(pairs)) => pairs,
+            Some(_) => return Err(wrong_type()),
+        };
+        let start = (cursor as usize).min(pairs.len());
+        let end = start.saturating_add(count.max(1)).min(pairs.len());
+
+        let fields = pairs[start..end]
+            .iter()
+            .map(|(f, _)| RedisBytes::from(f.clone()))
+            .collect();
+        let next = if end >= pairs.len() { 0 } else { end as u64 };
+        Ok((next, fields))
+    }
+
+    pub fn hash_get_field(
+        &self,
+        db: i64,
+        key: &[u8],
+        field: &[u8],
+    ) -> Result<Option<RedisBytes>, CommandError> {
+        match self.load(db, key)?.as_ref().map(|(_, v)| &**v) {
+            None => Ok(None),
+            Some(Value::Hash(pairs)) => Ok(pairs
+                .iter()
+                .find(|(f, _)| f == field)
+                .map(|(_, v)| RedisBytes::from(v.clone()))),
+            Some(_) => Err(wrong_type()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(data: &[u8]) -> RdbFile {
+        let mut rdb = RdbFile::new(PathBuf::from("test.rdb"));
+        rdb.index(&mut Reader::new(data)).unwrap();
+        rdb
+    }
+
+    fn key_names(rdb: &RdbFile) -> Vec<String> {
+        let (_, keys) = rdb.list_keys(0, "0", "*").unwrap();
+        keys.iter()
+            .map(|key| match &key.name {
+                RedisBytes::Utf8(name) => name.clone(),
+                RedisBytes::Base64(name) => panic!("binary name {}", name),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn skips_pre_ga_functions() {
+        #[rustfmt::skip]
+        let data = [
+            b"REDIS0010".as_slice(),
+            &[OPCODE_FUNCTION_PRE_GA, 1, b'f', 3, b'L', b'U', b'A'],
+            &[1, 1, b'd'], // description
+            &[8, b'r', b'e', b't', b'u', b'r', b'n', b' ', b'1'],
+            &[TYPE_STRING, 1, b'k', 1, b'v'],
+            &[OPCODE_EOF],
+        ]
+        .concat();
+        let rdb = index(&data);
+        assert_eq!(key_names(&rdb), ["k"]);
+        assert_eq!(rdb.warnings().len(), 1);
+    }
+
+    #[test]
+    fn stops_at_old_module_values() {
+        #[rustfmt::skip]
+        let data = [
+            b"REDIS0008".as_slice(),
+            &[TYPE_STRING, 1, b'a', 1, b'1'],
+            &[TYPE_MODULE, 1, b'm', 0x81, 0, 0, 0, 0, 0, 0, 4, 1],
+            // Opaque module data, then more keys
+            &[0x12, 0x34, TYPE_STRING, 1, b'b', 1, b'2'],
+        ]
+        .concat();
+        let rdb = index(&data);
+        assert_eq!(key_names(&rdb), ["a"]);
+        let warnings = rdb.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("Stopped at key m"));
+    }
+
+    #[test]
+    fn lists_binary_key_names() {
+        let data = [
+            b"REDIS0011".as_slice(),
+            &[TYPE_STRING, 2, 0xff, 0xfe, 1, b'v'],
+            &[TYPE_STRING, 3, b'k', b'e', b'y', 1, b'v'],
+            &[OPCODE_EOF],
+        ]
+        .concat();
+        let (_, keys) = index(&data).list_keys(0, "0", "*").unwrap();
+        assert!(matches!(&keys[0].name, RedisBytes::Utf8(name) if name == "key"));
+        assert!(matches!(&keys[1].name, RedisBytes::Base64(name) if name == "//4="));
+    }
+
+    #[test]
+    fn drops_keys_expired_before_the_save() {
+        let data = [
+            b"REDIS0011".as_slice(),
+            &[OPCODE_AUX, 5, b'c', b't', b'i', b'm', b'e', 0xc0, 100],
+            &[OPCODE_EXPIRETIME_MS],
+            &50_000i64.to_le_bytes(),
+            &[TYPE_STRING, 3, b'o', b'l', b'd', 1, b'v'],
+            &[OPCODE_EXPIRETIME_MS],
+            &200_000i64.to_le_bytes(),
+            &[TYPE_STRING, 3, b'n', b'e', b'w', 1, b'v'],
+            &[OPCODE_EOF],
+        ]
+        .concat();
+        let rdb = index(&data);
+        assert_eq!(key_names(&rdb), ["new"]);
+        assert_eq!(rdb.ttl(rdb.key(0, b"new").unwrap()), 100);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let mut rdb = RdbFile::new(PathBuf::from("test.rdb"));
+        assert!(rdb.index(&mut Reader::new(&b"NOTRDB001"[..])).is_err());
+        assert!(rdb.index(&mut Reader::new(&b"REDIS0099"[..])).is_err());
+    }
+}
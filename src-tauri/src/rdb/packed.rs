@@ -0,0 +1,207 @@
+//! The compact encodings small collections are stored in: ziplist,
+//! listpack, intset and the pre-2.6 zipmap. Integers come back as their
+//! decimal text, the way Redis returns them.
+
+/// A listpack or ziplist entry.
+pub enum Entry<'a> {
+    Bytes(&'a [u8]),
+    Int(i64),
+}
+
+impl Entry<'_> {
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Entry::Bytes(b) => b.to_vec(),
+            Entry::Int(n) => n.to_string().into_bytes(),
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Entry::Int(n) => Some(*n),
+            Entry::Bytes(b) => std::str::from_utf8(b).ok()?.parse().ok(),
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Entry::Int(n) => Some(*n as f64),
+            Entry::Bytes(b) => crate::rdb::reader::parse_f64(std::str::from_utf8(b).ok()?),
+        }
+    }
+}
+
+fn slice(data: &[u8], pos: usize, len: usize) -> Result<&[u8], String> {
+    pos.checked_add(len)
+        .and_then(|end| data.get(pos..end))
+        .ok_or_else(|| "truncated compact encoding".to_string())
+}
+
+fn int_le(bytes: &[u8]) -> i64 {
+    // Sign-extend from the top byte
+    let fill = if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+        0xff
+    } else {
+        0
+    };
+    let mut buf = [fill; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    i64::from_le_bytes(buf)
+}
+
+pub fn listpack(data: &[u8]) -> Result<Vec<Entry<'_>>, String> {
+    // total bytes (4) + element count (2)
+    let mut pos = 6;
+    let mut entries = Vec::new();
+
+    loop {
+        let b = *data.get(pos).ok_or("listpack without terminator")?;
+        if b == 0xff {
+            return Ok(entries);
+        }
+
+        let (entry, len) = match b {
+            0x00..=0x7f => (Entry::Int(i64::from(b)), 1),
+            0x80..=0xbf => {
+                let n = usize::from(b & 0x3f);
+                (Entry::Bytes(slice(data, pos + 1, n)?), 1 + n)
+            }
+            0xc0..=0xdf => {
+                let raw = (u16::from(b & 0x1f) << 8)
+                    | u16::from(*data.get(pos + 1).ok_or("truncated listpack")?);
+                // 13-bit two's complement
+                let n = (i64::from(raw) << 51) >> 51;
+                (Entry::Int(n), 2)
+            }
+            0xe0..=0xef => {
+                let n = (usize::from(b & 0x0f) << 8)
+                    | usize::from(*data.get(pos + 1).ok_or("truncated listpack")?);
+                (Entry::Bytes(slice(data, pos + 2, n)?), 2 + n)
+            }
+            0xf0 => {
+                let n = u32::from_le_bytes(slice(data, pos + 1, 4)?.try_into().unwrap()) as usize;
+                (Entry::Bytes(slice(data, pos + 5, n)?), 5 + n)
+            }
+            0xf1..=0xf4 => {
+                let width = match b {
+                    0xf1 => 2,
+                    0xf2 => 3,
+                    0xf3 => 4,
+                    _ => 8,
+                };
+                (Entry::Int(int_le(slice(data, pos + 1, width)?)), 1 + width)
+            }
+            other => return Err(format!("invalid listpack encoding 0x{:02x}", other)),
+        };
+
+        // The entry is followed by its own length, for walking backwards
+        let backlen = match len {
+            0..=127 => 1,
+            128..=16382 => 2,
+            16383..=2097150 => 3,
+            2097151..=268435454 => 4,
+            _ => 5,
+        };
+        entries.push(entry);
+        pos += len + backlen;
+    }
+}
+
+pub fn ziplist(data: &[u8]) -> Result<Vec<Entry<'_>>, String> {
+    // zlbytes (4) + zltail (4) + zllen (2)
+    let mut pos = 10;
+    let mut entries = Vec::new();
+
+    loop {
+        let first = *data.get(pos).ok_or("ziplist without terminator")?;
+        if first == 0xff {
+            return Ok(entries);
+        }
+        // Length of the previous entry, 1 or 5 bytes
+        pos += if first == 0xfe { 5 } else { 1 };
+
+        let b = *data.get(pos).ok_or("truncated ziplist")?;
+        let (entry, len) = match b >> 6 {
+            0 => {
+                let n = usize::from(b & 0x3f);
+                (Entry::Bytes(slice(data, pos + 1, n)?), 1 + n)
+            }
+            1 => {
+                let n = (usize::from(b & 0x3f) << 8)
+                    | usize::from(*data.get(pos + 1).ok_or("truncated ziplist")?);
+                (Entry::Bytes(slice(data, pos + 2, n)?), 2 + n)
+            }
+            2 => {
+                let n = u32::from_be_bytes(slice(data, pos + 1, 4)?.try_into().unwrap()) as usize;
+                (Entry::Bytes(slice(data, pos + 5, n)?), 5 + n)
+            }
+            _ => {
+                let width = match b {
+                    0xc0 => 2,
+                    0xd0 => 4,
+                    0xe0 => 8,
+                    0xf0 => 3,
+                    0xfe => 1,
+                    0xf1..=0xfd => 0,
+                    other => return Err(format!("invalid ziplist encoding 0x{:02x}", other)),
+                };
+                let n = if width == 0 {
+                    // 4-bit immediate, stored as value + 1
+                    i64::from(b & 0x0f) - 1
+                } else {
+                    int_le(slice(data, pos + 1, width)?)
+                };
+                (Entry::Int(n), 1 + width)
+            }
+        };
+        entries.push(entry);
+        pos += len;
+    }
+}
+
+pub fn intset(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let width = u32::from_le_bytes(slice(data, 0, 4)?.try_into().unwrap()) as usize;
+    let count = u32::from_le_bytes(slice(data, 4, 4)?.try_into().unwrap()) as usize;
+    if !matches!(width, 2 | 4 | 8) {
+        return Err(format!("invalid intset encoding {}", width));
+    }
+    let values = slice(data, 8, count.checked_mul(width).ok_or("intset too large")?)?;
+    Ok(values
+        .chunks(width)
+        .map(|chunk| int_le(chunk).to_string().into_bytes())
+        .collect())
+}
+
+/// Flat `[field, value, field, value, ...]` of a zipmap.
+pub fn zipmap(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    // zmlen (1)
+    let mut pos = 1;
+    let mut items = Vec::new();
+
+    loop {
+        if *data.get(pos).ok_or("zipmap without terminator")? == 0xff {
+            return Ok(items);
+        }
+        let len = zipmap_len(data, &mut pos)?;
+        items.push(slice(data, pos, len)?.to_vec());
+        pos += len;
+
+        // Values carry the number of unused bytes left after them
+        let len = zipmap_len(data, &mut pos)?;
+        let free = usize::from(*data.get(pos).ok_or("truncated zipmap")?);
+        items.push(slice(data, pos + 1, len)?.to_vec());
+        pos += 1 + len + free;
+    }
+}
+
+fn zipmap_len(data: &[u8], pos: &mut usize) -> Result<usize, String> {
+    let b = *data.get(*pos).ok_or("truncated zipmap")?;
+    if b < 254 {
+        *pos += 1;
+        Ok(usize::from(b))
+    } else {
+        let len = u32::from_le_bytes(slice(data, *pos + 1, 4)?.try_into().unwrap()) as usize;
+        *pos += 5;
+        Ok(len)
+    }
+}
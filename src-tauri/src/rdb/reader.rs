@@ -0,0 +1,909 @@
+//! The byte level of the format: lengths, strings (plain, integer or LZF
+//! compressed), and the value layout of every key type.
+
+use super::packed::{self, Entry};
+use std::fmt;
+use std::io::{self, Read};
+
+pub const TYPE_STRING: u8 = 0;
+pub const TYPE_LIST: u8 = 1;
+pub const TYPE_SET: u8 = 2;
+pub const TYPE_ZSET: u8 = 3;
+pub const TYPE_HASH: u8 = 4;
+pub const TYPE_ZSET_2: u8 = 5;
+pub const TYPE_MODULE: u8 = 6;
+pub const TYPE_MODULE_2: u8 = 7;
+pub const TYPE_HASH_ZIPMAP: u8 = 9;
+pub const TYPE_LIST_ZIPLIST: u8 = 10;
+pub const TYPE_SET_INTSET: u8 = 11;
+pub const TYPE_ZSET_ZIPLIST: u8 = 12;
+pub const TYPE_HASH_ZIPLIST: u8 = 13;
+pub const TYPE_LIST_QUICKLIST: u8 = 14;
+pub const TYPE_STREAM_LISTPACKS: u8 = 15;
+pub const TYPE_HASH_LISTPACK: u8 = 16;
+pub const TYPE_ZSET_LISTPACK: u8 = 17;
+pub const TYPE_LIST_QUICKLIST_2: u8 = 18;
+pub const TYPE_STREAM_LISTPACKS_2: u8 = 19;
+pub const TYPE_SET_LISTPACK: u8 = 20;
+pub const TYPE_STREAM_LISTPACKS_3: u8 = 21;
+pub const TYPE_HASH_METADATA_PRE_GA: u8 = 22;
+pub const TYPE_HASH_LISTPACK_EX_PRE_GA: u8 = 23;
+pub const TYPE_HASH_METADATA: u8 = 24;
+pub const TYPE_HASH_LISTPACK_EX: u8 = 25;
+
+/// Module values end with this opcode; the others say what comes next.
+const MODULE_OPCODE_EOF: u64 = 0;
+const MODULE_OPCODE_SINT: u64 = 1;
+const MODULE_OPCODE_UINT: u64 = 2;
+const MODULE_OPCODE_FLOAT: u64 = 3;
+const MODULE_OPCODE_DOUBLE: u64 = 4;
+const MODULE_OPCODE_STRING: u64 = 5;
+
+/// Quicklist 2 node containers
+const QUICKLIST_NODE_PLAIN: u64 = 1;
+const QUICKLIST_NODE_PACKED: u64 = 2;
+
+/// Stream entry flags
+const STREAM_ITEM_DELETED: i64 = 1;
+const STREAM_ITEM_SAME_FIELDS: i64 = 2;
+
+pub type Result<T> = std::result::Result<T, String>;
+
+/// Field/value pairs of a stream entry.
+pub type Fields = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// A fully loaded value.
+pub enum Value {
+    String(Vec<u8>),
+    List(Vec<Vec<u8>>),
+    Set(Vec<Vec<u8>>),
+    ZSet(Vec<(Vec<u8>, f64)>),
+    Hash(Vec<(Vec<u8>, Vec<u8>)>),
+    Stream(Stream),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+pub struct Stream {
+    pub entries: Vec<(StreamId, Fields)>,
+    pub length: u64,
+    pub last_id: StreamId,
+    /// Number of listpack nodes, the radix tree keys of XINFO
+    pub nodes: u64,
+    pub groups: Vec<ConsumerGroup>,
+}
+
+pub struct ConsumerGroup {
+    pub name: Vec<u8>,
+    pub last_id: StreamId,
+    pub consumers: u64,
+    pub pending: u64,
+}
+
+enum Length {
+    Len(u64),
+    /// Integer or LZF encoded string, with the encoding number
+    Special(u8),
+}
+
+/// Parses a score the way Redis prints them, including `inf`/`-inf`.
+pub fn parse_f64(s: &str) -> Option<f64> {
+    match s {
+        "inf" | "+inf" => Some(f64::INFINITY),
+        "-inf" => Some(f64::NEG_INFINITY),
+        _ => s.parse().ok(),
+    }
+}
+
+fn lzf_decompress(input: &[u8], out_len: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(out_len.min(input.len().saturating_mul(4)));
+    let mut i = 0;
+    let truncated = || "truncated LZF data".to_string();
+
+    while i < input.len() {
+        let ctrl = usize::from(input[i]);
+        i += 1;
+        if ctrl < 32 {
+            // Literal run
+            let run = input.get(i..i + ctrl + 1).ok_or_else(truncated)?;
+            out.extend_from_slice(run);
+            i += ctrl + 1;
+        } else {
+            // Back reference
+            let mut len = ctrl >> 5;
+            if len == 7 {
+                len += usize::from(*input.get(i).ok_or_else(truncated)?);
+                i += 1;
+            }
+            let back = ((ctrl & 0x1f) << 8) + usize::from(*input.get(i).ok_or_else(truncated)?) + 1;
+            i += 1;
+            let start = out
+                .len()
+                .checked_sub(back)
+                .ok_or("invalid LZF back reference")?;
+            for k in 0..len + 2 {
+                out.push(out[start + k]);
+            }
+        }
+        if out.len() > out_len {
+            return Err("LZF data longer than announced".to_string());
+        }
+    }
+
+    if out.len() != out_len {
+        return Err("LZF data shorter than announced".to_string());
+    }
+    Ok(out)
+}
+
+fn entries_to_bytes(entries: Vec<Entry<'_>>) -> Vec<Vec<u8>> {
+    entries.into_iter().map(Entry::into_bytes).collect()
+}
+
+fn entries_to_pairs(entries: Vec<Entry<'_>>) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut pairs = Vec::with_capacity(entries.len() / 2);
+    let mut iter = entries.into_iter();
+    while let (Some(f), Some(v)) = (iter.next(), iter.next()) {
+        pairs.push((f.into_bytes(), v.into_bytes()));
+    }
+    pairs
+}
+
+fn entries_to_zset(entries: Vec<Entry<'_>>) -> Result<Vec<(Vec<u8>, f64)>> {
+    let mut members = Vec::with_capacity(entries.len() / 2);
+    let mut iter = entries.into_iter();
+    while let (Some(member), Some(score)) = (iter.next(), iter.next()) {
+        let score = score.as_f64().ok_or("invalid score in packed zset")?;
+        members.push((member.into_bytes(), score));
+    }
+    Ok(members)
+}
+
+/// Field/value pairs of a hash with field TTLs, packed as triplets.
+fn entries_to_hash_ex(entries: Vec<Entry<'_>>) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut pairs = Vec::with_capacity(entries.len() / 3);
+    let mut iter = entries.into_iter();
+    while let (Some(f), Some(v), Some(_ttl)) = (iter.next(), iter.next(), iter.next()) {
+        pairs.push((f.into_bytes(), v.into_bytes()));
+    }
+    pairs
+}
+
+/// Big-endian ID a stream listpack node is keyed by.
+fn node_key(raw: &[u8]) -> Result<StreamId> {
+    if raw.len() != 16 {
+        return Err("invalid stream node key".to_string());
+    }
+    Ok(StreamId {
+        ms: u64::from_be_bytes(raw[..8].try_into().unwrap()),
+        seq: u64::from_be_bytes(raw[8..].try_into().unwrap()),
+    })
+}
+
+/// Entries of one stream listpack node. The node starts with a master
+/// entry listing the fields most entries share, followed by entries with
+/// IDs relative to the node key.
+fn stream_node(master: StreamId, data: &[u8], entries: &mut Vec<(StreamId, Fields)>) -> Result<()> {
+    let mut iter = packed::listpack(data)?.into_iter();
+    let int = |iter: &mut std::vec::IntoIter<Entry<'_>>| -> Result<i64> {
+        iter.next()
+            .and_then(|e| e.as_int())
+            .ok_or_else(|| "invalid stream listpack".to_string())
+    };
+
+    let count = int(&mut iter)?;
+    let deleted = int(&mut iter)?;
+    let master_fields_count = int(&mut iter)?;
+    let master_fields: Vec<Vec<u8>> = (0..master_fields_count)
+        .map(|_| iter.next().map(Entry::into_bytes))
+        .collect::<Option<_>>()
+        .ok_or("invalid stream master entry")?;
+    // Terminator of the master entry
+    int(&mut iter)?;
+
+    for _ in 0..count + deleted {
+        let flags = int(&mut iter)?;
+        let id = StreamId {
+            ms: master.ms.wrapping_add(int(&mut iter)? as u64),
+            seq: master.seq.wrapping_add(int(&mut iter)? as u64),
+        };
+
+        let mut fields = Vec::new();
+        if flags & STREAM_ITEM_SAME_FIELDS != 0 {
+            for field in &master_fields {
+                let value = iter.next().ok_or("truncated stream entry")?;
+                fields.push((field.clone(), value.into_bytes()));
+            }
+        } else {
+            let n = int(&mut iter)?;
+            for _ in 0..n {
+                let (Some(field), Some(value)) = (iter.next(), iter.next()) else {
+                    return Err("truncated stream entry".to_string());
+                };
+                fields.push((field.into_bytes(), value.into_bytes()));
+            }
+        }
+        // Number of listpack items of the entry, for walking backwards
+        int(&mut iter)?;
+
+        if flags & STREAM_ITEM_DELETED == 0 {
+            entries.push((id, fields));
+        }
+    }
+    Ok(())
+}
+
+pub struct Reader<R> {
+    inner: R,
+    pos: u64,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Reader { inner, pos: 0 }
+    }
+
+    /// Bytes consumed so far.
+    pub fn pos(&self) -> u64 {
+        self.pos
+    }
+
+    pub fn bytes(&mut self, n: u64) -> Result<Vec<u8>> {
+        // Grows as data arrives, a corrupt length can't allocate up front
+        let mut buf = Vec::new();
+        (&mut self.inner)
+            .take(n)
+            .read_to_end(&mut buf)
+            .map_err(|e| e.to_string())?;
+        if buf.len() as u64 != n {
+            return Err("unexpected end of file".to_string());
+        }
+        self.pos += n;
+        Ok(buf)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0; N];
+        self.inner
+            .read_exact(&mut buf)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => "unexpected end of file".to_string(),
+                _ => e.to_string(),
+            })?;
+        self.pos += N as u64;
+        Ok(buf)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn skip(&mut self, n: u64) -> Result<()> {
+        let skipped =
+            io::copy(&mut (&mut self.inner).take(n), &mut io::sink()).map_err(|e| e.to_string())?;
+        if skipped != n {
+            return Err("unexpected end of file".to_string());
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    /// 8-byte little-endian timestamp in milliseconds.
+    pub fn millis(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    fn length(&mut self) -> Result<Length> {
+        let b = self.u8()?;
+        Ok(match b >> 6 {
+            0 => Length::Len(u64::from(b & 0x3f)),
+            1 => Length::Len((u64::from(b & 0x3f) << 8) | u64::from(self.u8()?)),
+            2 => match b {
+                0x80 => Length::Len(u64::from(u32::from_be_bytes(self.array()?))),
+                0x81 => Length::Len(u64::from_be_bytes(self.array()?)),
+                _ => return Err(format!("invalid length encoding 0x{:02x}", b)),
+            },
+            _ => Length::Special(b & 0x3f),
+        })
+    }
+
+    pub fn len(&mut self) -> Result<u64> {
+        match self.length()? {
+            Length::Len(n) => Ok(n),
+            Length::Special(_) => Err("expected a length, found an encoded string".to_string()),
+        }
+    }
+
+    pub fn string(&mut self) -> Result<Vec<u8>> {
+        match self.length()? {
+            Length::Len(n) => self.bytes(n),
+            Length::Special(0) => Ok((self.u8()? as i8).to_string().into_bytes()),
+            Length::Special(1) => Ok(i16::from_le_bytes(self.array()?).to_string().into_bytes()),
+            Length::Special(2) => Ok(i32::from_le_bytes(self.array()?).to_string().into_bytes()),
+            Length::Special(3) => {
+                let compressed_len = self.len()?;
+                let len = usize::try_from(self.len()?).map_err(|_| "LZF string too large")?;
+                let compressed = self.bytes(compressed_len)?;
+                lzf_decompress(&compressed, len)
+            }
+            Length::Special(n) => Err(format!("invalid string encoding {}", n)),
+        }
+    }
+
+    pub fn skip_string(&mut self) -> Result<()> {
+        match self.length()? {
+            Length::Len(n) => self.skip(n),
+            Length::Special(0) => self.skip(1),
+            Length::Special(1) => self.skip(2),
+            Length::Special(2) => self.skip(4),
+            Length::Special(3) => {
+                let compressed_len = self.len()?;
+                self.len()?;
+                self.skip(compressed_len)
+            }
+            Length::Special(n) => Err(format!("invalid string encoding {}", n)),
+        }
+    }
+
+    /// Score of the original zset type, a length-prefixed string.
+    fn text_f64(&mut self) -> Result<f64> {
+        match self.u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            n => {
+                let text = self.bytes(u64::from(n))?;
+                std::str::from_utf8(&text)
+                    .ok()
+                    .and_then(parse_f64)
+                    .ok_or_else(|| "invalid zset score".to_string())
+            }
+        }
+    }
+
+    fn skip_text_f64(&mut self) -> Result<()> {
+        match self.u8()? {
+            253..=255 => Ok(()),
+            n => self.skip(u64::from(n)),
+        }
+    }
+
+    fn stream_id(&mut self) -> Result<StreamId> {
+        Ok(StreamId {
+            ms: self.len()?,
+            seq: self.len()?,
+        })
+    }
+
+    /// Module data is a sequence of typed opcodes up to an EOF opcode.
+    fn skip_module_data(&mut self) -> Result<()> {
+        loop {
+            match self.len()? {
+                MODULE_OPCODE_EOF => return Ok(()),
+                MODULE_OPCODE_SINT | MODULE_OPCODE_UINT => {
+                    self.len()?;
+                }
+                MODULE_OPCODE_FLOAT => self.skip(4)?,
+                MODULE_OPCODE_DOUBLE => self.skip(8)?,
+                MODULE_OPCODE_STRING => self.skip_string()?,
+                op => return Err(format!("invalid module opcode {}", op)),
+            }
+        }
+    }
+
+    /// Skips a module value (type 7) and returns the 64-bit module type id.
+    pub fn skip_module_value(&mut self) -> Result<u64> {
+        let id = self.len()?;
+        self.skip_module_data()?;
+        Ok(id)
+    }
+
+    /// Skips an auxiliary module field (the MODULE_AUX opcode).
+    pub fn skip_module_aux(&mut self) -> Result<()> {
+        // Module id, then when the aux data was written
+        self.len()?;
+        self.len()?;
+        self.len()?;
+        self.skip_module_data()
+    }
+
+    /// Skips a function of the Redis 7.0 release candidates (the
+    /// FUNCTION_PRE_GA opcode): name, engine, optional description, code.
+    pub fn skip_function_pre_ga(&mut self) -> Result<()> {
+        self.skip_string()?;
+        self.skip_string()?;
+        if self.len()? != 0 {
+            self.skip_string()?;
+        }
+        self.skip_string()
+    }
+
+    fn read_stream(&mut self, kind: u8) -> Result<Stream> {
+        let nodes = self.len()?;
+        let mut entries = Vec::new();
+        for _ in 0..nodes {
+            let master = node_key(&self.string()?)?;
+            stream_node(master, &self.string()?, &mut entries)?;
+        }
+
+        let length = self.len()?;
+        let last_id = self.stream_id()?;
+        if kind >= TYPE_STREAM_LISTPACKS_2 {
+            // First ID, max deleted ID, entries added
+            self.stream_id()?;
+            self.stream_id()?;
+            self.len()?;
+        }
+
+        let mut groups = Vec::new();
+        for _ in 0..self.len()? {
+            let name = self.string()?;
+            let last_id = self.stream_id()?;
+            if kind >= TYPE_STREAM_LISTPACKS_2 {
+                // entries-read
+                self.len()?;
+            }
+            let pending = self.len()?;
+            for _ in 0..pending {
+                // ID, delivery time, delivery count
+                self.skip(16 + 8)?;
+                self.len()?;
+            }
+            let consumers = self.len()?;
+            for _ in 0..consumers {
+                self.skip_consumer(kind)?;
+            }
+            groups.push(ConsumerGroup {
+                name,
+                last_id,
+                consumers,
+                pending,
+            });
+        }
+
+        Ok(Stream {
+            entries,
+            length,
+            last_id,
+            nodes,
+            groups,
+        })
+    }
+
+    fn skip_consumer(&mut self, kind: u8) -> Result<()> {
+        self.skip_string()?;
+        // Seen time, and active time since stream type 3
+        self.skip(if kind >= TYPE_STREAM_LISTPACKS_3 {
+            16
+        } else {
+            8
+        })?;
+        let pending = self.len()?;
+        self.skip(pending.checked_mul(16).ok_or("invalid consumer PEL size")?)
+    }
+
+    fn skip_stream(&mut self, kind: u8) -> Result<()> {
+        for _ in 0..self.len()? {
+            self.skip_string()?;
+            self.skip_string()?;
+        }
+        self.len()?;
+        self.stream_id()?;
+        if kind >= TYPE_STREAM_LISTPACKS_2 {
+            self.stream_id()?;
+            self.stream_id()?;
+            self.len()?;
+        }
+        for _ in 0..self.len()? {
+            self.skip_string()?;
+            self.stream_id()?;
+            if kind >= TYPE_STREAM_LISTPACKS_2 {
+                self.len()?;
+            }
+            for _ in 0..self.len()? {
+                self.skip(16 + 8)?;
+                self.len()?;
+            }
+            for _ in 0..self.len()? {
+                self.skip_consumer(kind)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the value of a key of type `kind`.
+    pub fn value(&mut self, kind: u8) -> Result<Value> {
+        Ok(match kind {
+            TYPE_STRING => Value::String(self.string()?),
+            TYPE_LIST | TYPE_SET => {
+                let mut items = Vec::new();
+                for _ in 0..self.len()? {
+                    items.push(self.string()?);
+                }
+                if kind == TYPE_LIST {
+                    Value::List(items)
+                } else {
+                    Value::Set(items)
+                }
+            }
+            TYPE_ZSET | TYPE_ZSET_2 => {
+                let mut members = Vec::new();
+                for _ in 0..self.len()? {
+                    let member = self.string()?;
+                    let score = if kind == TYPE_ZSET {
+                        self.text_f64()?
+                    } else {
+                        f64::from_le_bytes(self.array()?)
+                    };
+                    members.push((member, score));
+                }
+                Value::ZSet(members)
+            }
+            TYPE_HASH => {
+                let mut pairs = Vec::new();
+                for _ in 0..self.len()? {
+                    pairs.push((self.string()?, self.string()?));
+                }
+                Value::Hash(pairs)
+            }
+            TYPE_HASH_METADATA_PRE_GA | TYPE_HASH_METADATA => {
+                if kind == TYPE_HASH_METADATA {
+                    // Smallest field expiry, the field TTLs are relative to it
+                    self.millis()?;
+                }
+                let mut pairs = Vec::new();
+                for _ in 0..self.len()? {
+                    self.len()?;
+                    pairs.push((self.string()?, self.string()?));
+                }
+                Value::Hash(pairs)
+            }
+            TYPE_HASH_ZIPMAP => {
+                let flat = packed::zipmap(&self.string()?)?;
+                let mut iter = flat.into_iter();
+                let mut pairs = Vec::new();
+                while let (Some(f), Some(v)) = (iter.next(), iter.next()) {
+                    pairs.push((f, v));
+                }
+                Value::Hash(pairs)
+            }
+            TYPE_LIST_ZIPLIST => Value::List(entries_to_bytes(packed::ziplist(&self.string()?)?)),
+            TYPE_SET_INTSET => Value::Set(packed::intset(&self.string()?)?),
+            TYPE_ZSET_ZIPLIST => Value::ZSet(entries_to_zset(packed::ziplist(&self.string()?)?)?),
+            TYPE_HASH_ZIPLIST => Value::Hash(entries_to_pairs(packed::ziplist(&self.string()?)?)),
+            TYPE_HASH_LISTPACK => Value::Hash(entries_to_pairs(packed::listpack(&self.string()?)?)),
+            TYPE_ZSET_LISTPACK => Value::ZSet(entries_to_zset(packed::listpack(&self.string()?)?)?),
+            TYPE_SET_LISTPACK => Value::Set(entries_to_bytes(packed::listpack(&self.string()?)?)),
+            TYPE_HASH_LISTPACK_EX_PRE_GA | TYPE_HASH_LISTPACK_EX => {
+                if kind == TYPE_HASH_LISTPACK_EX {
+                    self.millis()?;
+                }
+                Value::Hash(entries_to_hash_ex(packed::listpack(&self.string()?)?))
+            }
+            TYPE_LIST_QUICKLIST => {
+                let mut items = Vec::new();
+                for _ in 0..self.len()? {
+                    items.extend(entries_to_bytes(packed::ziplist(&self.string()?)?));
+                }
+                Value::List(items)
+            }
+            TYPE_LIST_QUICKLIST_2 => {
+                let mut items = Vec::new();
+                for _ in 0..self.len()? {
+                    match self.len()? {
+                        QUICKLIST_NODE_PLAIN => items.push(self.string()?),
+                        QUICKLIST_NODE_PACKED => {
+                            items.extend(entries_to_bytes(packed::listpack(&self.string()?)?))
+                        }
+                        n => return Err(format!("invalid quicklist container {}", n)),
+                    }
+                }
+                Value::List(items)
+            }
+            TYPE_STREAM_LISTPACKS | TYPE_STREAM_LISTPACKS_2 | TYPE_STREAM_LISTPACKS_3 => {
+                Value::Stream(self.read_stream(kind)?)
+            }
+            _ => return Err(format!("unsupported value type {}", kind)),
+        })
+    }
+
+    /// Moves past a value without keeping it, for indexing.
+    pub fn skip_value(&mut self, kind: u8) -> Result<()> {
+        match kind {
+            TYPE_STRING
+            | TYPE_HASH_ZIPMAP
+            | TYPE_LIST_ZIPLIST
+            | TYPE_SET_INTSET
+            | TYPE_ZSET_ZIPLIST
+            | TYPE_HASH_ZIPLIST
+            | TYPE_HASH_LISTPACK
+            | TYPE_ZSET_LISTPACK
+            | TYPE_SET_LISTPACK
+            | TYPE_HASH_LISTPACK_EX_PRE_GA => self.skip_string(),
+            TYPE_HASH_LISTPACK_EX => {
+                self.skip(8)?;
+                self.skip_string()
+            }
+            TYPE_LIST | TYPE_SET | TYPE_LIST_QUICKLIST => {
+                for _ in 0..self.len()? {
+                    self.skip_string()?;
+                }
+                Ok(())
+            }
+            TYPE_ZSET => {
+                for _ in 0..self.len()? {
+                    self.skip_string()?;
+                    self.skip_text_f64()?;
+                }
+                Ok(())
+            }
+            TYPE_ZSET_2 => {
+                for _ in 0..self.len()? {
+                    self.skip_string()?;
+                    self.skip(8)?;
+                }
+                Ok(())
+            }
+            TYPE_HASH => {
+                for _ in 0..self.len()? {
+                    self.skip_string()?;
+                    self.skip_string()?;
+                }
+                Ok(())
+            }
+            TYPE_HASH_METADATA_PRE_GA | TYPE_HASH_METADATA => {
+                if kind == TYPE_HASH_METADATA {
+                    self.skip(8)?;
+                }
+                for _ in 0..self.len()? {
+                    self.len()?;
+                    self.skip_string()?;
+                    self.skip_string()?;
+                }
+                Ok(())
+            }
+            TYPE_LIST_QUICKLIST_2 => {
+                for _ in 0..self.len()? {
+                    self.len()?;
+                    self.skip_string()?;
+                }
+                Ok(())
+            }
+            TYPE_STREAM_LISTPACKS | TYPE_STREAM_LISTPACKS_2 | TYPE_STREAM_LISTPACKS_3 => {
+                self.skip_stream(kind)
+            }
+            _ => Err(format!("unsupported value type {}", kind)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the value, checking that skipping it consumes the same bytes.
+    fn read(kind: u8, data: &[u8]) -> Result<Value> {
+        let mut skipper = Reader::new(data);
+        skipper.skip_value(kind)?;
+        assert_eq!(
+            skipper.pos(),
+            data.len() as u64,
+            "skip_value of type {}",
+            kind
+        );
+        let mut reader = Reader::new(data);
+        let value = reader.value(kind)?;
+        assert_eq!(reader.pos(), data.len() as u64, "value of type {}", kind);
+        Ok(value)
+    }
+
+    fn strings(items: &[Vec<u8>]) -> Vec<&str> {
+        items
+            .iter()
+            .map(|item| std::str::from_utf8(item).unwrap())
+            .collect()
+    }
+
+    fn pairs(items: &[(Vec<u8>, Vec<u8>)]) -> Vec<(&str, &str)> {
+        items
+            .iter()
+            .map(|(f, v)| {
+                (
+                    std::str::from_utf8(f).unwrap(),
+                    std::str::from_utf8(v).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_lzf_strings() {
+        // "a" as a literal, then a back reference repeating it 9 times
+        let data = [0xc3, 0x05, 0x0a, 0x00, b'a', 0xe0, 0x00, 0x00];
+        let Value::String(s) = read(TYPE_STRING, &data).unwrap() else {
+            panic!("not a string");
+        };
+        assert_eq!(s, b"aaaaaaaaaa");
+
+        // Announces 11 bytes
+        let short = [0xc3, 0x05, 0x0b, 0x00, b'a', 0xe0, 0x00, 0x00];
+        assert!(Reader::new(&short[..]).value(TYPE_STRING).is_err());
+        // Back reference before the start
+        let bad = [0xc3, 0x03, 0x0a, 0xe0, 0x00, 0x05];
+        assert!(Reader::new(&bad[..]).value(TYPE_STRING).is_err());
+    }
+
+    #[test]
+    fn reads_integer_strings() {
+        let Value::String(s) = read(TYPE_STRING, &[0xc1, 0x2c, 0x01]).unwrap() else {
+            panic!("not a string");
+        };
+        assert_eq!(s, b"300");
+    }
+
+    #[test]
+    fn reads_ziplists() {
+        let data = [
+            20, // string length
+            20, 0, 0, 0, 15, 0, 0, 0, 3, 0, // zlbytes, zltail, zllen
+            0x00, 0x01, b'a', // "a"
+            0x03, 0xf6, // 4-bit immediate 5
+            0x02, 0xc0, 0x2c, 0x01, // int16 300
+            0xff,
+        ];
+        let Value::List(items) = read(TYPE_LIST_ZIPLIST, &data).unwrap() else {
+            panic!("not a list");
+        };
+        assert_eq!(strings(&items), ["a", "5", "300"]);
+
+        // Without the terminator
+        let mut truncated = data[..data.len() - 1].to_vec();
+        truncated[0] -= 1;
+        assert!(read(TYPE_LIST_ZIPLIST, &truncated).is_err());
+    }
+
+    #[test]
+    fn reads_listpacks() {
+        let data = [
+            18, // string length
+            18, 0, 0, 0, 4, 0, // total bytes, element count
+            0x81, b'f', 0x02, // "f"
+            0x81, b'v', 0x02, // "v"
+            0x81, b'n', 0x02, // "n"
+            0x07, 0x01, // 7-bit uint 7
+            0xff,
+        ];
+        let Value::Hash(items) = read(TYPE_HASH_LISTPACK, &data).unwrap() else {
+            panic!("not a hash");
+        };
+        assert_eq!(pairs(&items), [("f", "v"), ("n", "7")]);
+
+        let zset = [
+            13, 13, 0, 0, 0, 2, 0, // header
+            0x81, b'm', 0x02, // "m"
+            0xdf, 0xff, 0x01, // 13-bit int -1
+            0xff,
+        ];
+        let Value::ZSet(members) = read(TYPE_ZSET_LISTPACK, &zset[..]).unwrap() else {
+            panic!("not a zset");
+        };
+        assert_eq!(members, [(b"m".to_vec(), -1.0)]);
+
+        // String entry running past the end
+        let bad = [8, 8, 0, 0, 0, 1, 0, 0x85, b'x'];
+        assert!(read(TYPE_SET_LISTPACK, &bad).is_err());
+    }
+
+    #[test]
+    fn reads_intsets() {
+        let data = [
+            14, // string length
+            2, 0, 0, 0, 3, 0, 0, 0, // encoding, length
+            0x01, 0x00, 0xfe, 0xff, 0x2c, 0x01,
+        ];
+        let Value::Set(items) = read(TYPE_SET_INTSET, &data).unwrap() else {
+            panic!("not a set");
+        };
+        assert_eq!(strings(&items), ["1", "-2", "300"]);
+
+        let bad_width = [8, 3, 0, 0, 0, 0, 0, 0, 0];
+        assert!(read(TYPE_SET_INTSET, &bad_width).is_err());
+        let truncated = [10, 2, 0, 0, 0, 2, 0, 0, 0, 0x01, 0x00];
+        assert!(read(TYPE_SET_INTSET, &truncated).is_err());
+    }
+
+    #[test]
+    fn reads_zipmaps() {
+        let data = [
+            14, // string length
+            2,  // zmlen
+            1, b'f', 1, 0, b'v', // f => v
+            1, b'k', 2, 1, b'a', b'b', 0x00, // k => ab, one free byte
+            0xff,
+        ];
+        let Value::Hash(items) = read(TYPE_HASH_ZIPMAP, &data).unwrap() else {
+            panic!("not a hash");
+        };
+        assert_eq!(pairs(&items), [("f", "v"), ("k", "ab")]);
+
+        let truncated = [5, 1, 1, b'f', 3, 0];
+        assert!(read(TYPE_HASH_ZIPMAP, &truncated).is_err());
+    }
+
+    #[test]
+    fn reads_quicklists() {
+        let data = [
+            2, // nodes
+            1, 5, b'p', b'l', b'a', b'i', b'n', // plain node
+            2, 12, 12, 0, 0, 0, 2, 0, 0x81, b'x', 0x02, 0x01, 0x01, 0xff, // packed node
+        ];
+        let Value::List(items) = read(TYPE_LIST_QUICKLIST_2, &data).unwrap() else {
+            panic!("not a list");
+        };
+        assert_eq!(strings(&items), ["plain", "x", "1"]);
+
+        let bad_container = [1, 3, 1, b'x'];
+        assert!(Reader::new(&bad_container[..])
+            .value(TYPE_LIST_QUICKLIST_2)
+            .is_err());
+    }
+
+    #[test]
+    fn reads_stream_listpacks() {
+        #[rustfmt::skip]
+        let data = [
+            1, // nodes
+            16, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, // node key 1-0
+            45, // listpack length
+            45, 0, 0, 0, 17, 0,
+            0x02, 0x01, 0x00, 0x01, // 2 entries, none deleted
+            0x01, 0x01, 0x81, b'f', 0x02, 0x00, 0x01, // master fields [f]
+            0x02, 0x01, 0x00, 0x01, 0x00, 0x01, // same fields, 1-0
+            0x81, b'a', 0x02, 0x04, 0x01, // f=a
+            0x00, 0x01, 0x01, 0x01, 0x00, 0x01, // own fields, 2-0
+            0x01, 0x01, 0x81, b'g', 0x02, 0x81, b'b', 0x02, 0x06, 0x01, // g=b
+            0xff,
+            2, 2, 0, // length, last id
+            1, // groups
+            3, b'g', b'r', b'p', 1, 0, // name, last delivered id
+            1, // pending
+            0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, // id
+            0, 0, 0, 0, 0, 0, 0, 0, 1, // delivery time, count
+            1, // consumers
+            1, b'c', 0, 0, 0, 0, 0, 0, 0, 0, // name, seen time
+            1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, // pending id
+        ];
+        let Value::Stream(stream) = read(TYPE_STREAM_LISTPACKS, &data).unwrap() else {
+            panic!("not a stream");
+        };
+        let ids: Vec<String> = stream
+            .entries
+            .iter()
+            .map(|(id, _)| id.to_string())
+            .collect();
+        assert_eq!(ids, ["1-0", "2-0"]);
+        assert_eq!(pairs(&stream.entries[0].1), [("f", "a")]);
+        assert_eq!(pairs(&stream.entries[1].1), [("g", "b")]);
+        assert_eq!(stream.length, 2);
+        assert_eq!(stream.last_id.to_string(), "2-0");
+        assert_eq!(stream.groups.len(), 1);
+        assert_eq!(stream.groups[0].name, b"grp");
+        assert_eq!(stream.groups[0].pending, 1);
+        assert_eq!(stream.groups[0].consumers, 1);
+
+        // Node key of the wrong size
+        let mut bad = data.to_vec();
+        bad[1] = 15;
+        assert!(Reader::new(&bad[..]).value(TYPE_STREAM_LISTPACKS).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use crate::connection::RedisConnection;
+use crate::error::{CommandError, ErrorKind};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+
+/// DEL/UNLINK only count as dangerous above this many keys.
+pub const MANY_KEYS_THRESHOLD: usize = 100;
+const CONFIRM_TOKEN_TTL: Duration = Duration::from_secs(60);
+
+/// COMMAND INFO flags that make a command unavailable in read-only mode.
+/// `admin` is not one of them, it also covers introspection such as
+/// CONFIG GET, SLOWLOG GET or CLIENT LIST.
+const MUTATING_FLAGS: &[&str] = &["write", "may_replicate"];
+
+/// Admin commands that change the server without a `write` flag, as
+/// `name` for every subcommand or `name|subcommand`.
+const DENIED_ADMIN_COMMANDS: &[&str] = &[
+    "acl|deluser",
+    "acl|dryrun",
+    "acl|load",
+    "acl|save",
+    "acl|setuser",
+    "bgrewriteaof",
+    "bgsave",
+    "client|kill",
+    "client|pause",
+    "cluster|addslots",
+    "cluster|addslotsrange",
+    "cluster|delslots",
+    "cluster|delslotsrange",
+    "cluster|failover",
+    "cluster|flushslots",
+    "cluster|forget",
+    "cluster|meet",
+    "cluster|replicate",
+    "cluster|reset",
+    "cluster|setslot",
+    "config|resetstat",
+    "config|rewrite",
+    "config|set",
+    "debug|change-repl-id",
+    "debug|crash-and-recover",
+    "debug|loadaof",
+    "debug|populate",
+    "debug|reload",
+    "debug|restart",
+    "debug|segfault",
+    "debug|set-active-expire",
+    "debug|sleep",
+    "failover",
+    "flushall",
+    "latency|reset",
+    "module|load",
+    "module|loadex",
+    "module|unload",
+    "replicaof",
+    "save",
+    "script|flush",
+    "script|kill",
+    "shutdown",
+    "slaveof",
+    "slowlog|reset",
+];
+
+pub fn read_only_error(name: &str) -> CommandError {
+    CommandError::new(
+        ErrorKind::ReadOnly,
+        format!(
+            "{} is not allowed: the connection is read-only",
+            name.to_ascii_uppercase()
+        ),
+    )
+}
+
+/// Describes why `argv` needs confirmation under the profile's
+/// `confirm_commands` list, or `None` if it can run right away.
+pub fn confirmation_reason(confirm_commands: &[String], argv: &[Vec<u8>]) -> Option<String> {
+    let (name, args) = argv.split_first()?;
+    let name = String::from_utf8_lossy(name).to_ascii_uppercase();
+    if !confirm_commands
+        .iter()
+        .any(|c| c.eq_ignore_ascii_case(&name))
+    {
+        return None;
+    }
+
+    match name.as_str() {
+        "DEL" | "UNLINK" if args.len() <= MANY_KEYS_THRESHOLD => None,
+        "DEL" | "UNLINK" => Some(format!("{} of {} keys", name, args.len())),
+        _ => Some(name),
+    }
+}
+
+/// `confirmation_reason` for deleting every key matching `pattern`. The
+/// number of keys is unknown up front, so listing DEL or UNLINK is enough.
+pub fn pattern_delete_reason(confirm_commands: &[String], pattern: &str) -> Option<String> {
+    confirm_commands
+        .iter()
+        .any(|c| c.eq_ignore_ascii_case("DEL") || c.eq_ignore_ascii_case("UNLINK"))
+        .then(|| format!("Deleting every key matching \"{}\"", pattern))
+}
+
+/// Lowercase COMMAND INFO names of `argv`: the command and, when there is
+/// an argument, the `name|subcommand` form Redis 7 knows subcommands by.
+pub fn command_names(argv: &[Vec<u8>]) -> Option<(String, Option<String>)> {
+    let name = String::from_utf8_lossy(argv.first()?).to_ascii_lowercase();
+    let sub = argv.get(1).map(|sub| {
+        format!(
+            "{}|{}",
+            name,
+            String::from_utf8_lossy(sub).to_ascii_lowercase()
+        )
+    });
+    Some((name, sub))
+}
+
+/// Whether `DENIED_ADMIN_COMMANDS` lists the command, independent of what
+/// the server reports (pre-7 servers know no subcommands).
+pub fn is_denied_admin_command(name: &str, sub: Option<&str>) -> bool {
+    DENIED_ADMIN_COMMANDS.contains(&name)
+        || sub.is_some_and(|sub| DENIED_ADMIN_COMMANDS.contains(&sub))
+}
+
+/// What read-only mode and the slowlog need to know about a command.
+#[derive(Clone, Copy)]
+pub struct CommandInfo {
+    /// Has one of the `MUTATING_FLAGS`
+    pub mutating: bool,
+    /// A container such as CONFIG whose subcommands have their own flags
+    pub has_subcommands: bool,
+    /// Argument position of the first key, 0 for commands without keys
+    /// (or with keys after a count, like EVAL)
+    pub first_key: usize,
+}
+
+/// COMMAND INFO of one command or `name|subcommand`, `None` when the
+/// server does not know it.
+pub async fn command_info(
+    con: &mut RedisConnection,
+    name: &str,
+) -> Result<Option<CommandInfo>, CommandError> {
+    let info: Vec<redis::Value> = redis::cmd("COMMAND")
+        .arg("INFO")
+        .arg(name)
+        .query_async(con)
+        .await
+        .map_err(|e| CommandError::redis("COMMAND INFO error", e))?;
+
+    let Some(redis::Value::Array(entry)) = info.into_iter().next() else {
+        return Ok(None);
+    };
+    let flags: Vec<String> = entry
+        .get(2)
+        .cloned()
+        .and_then(|v| redis::from_redis_value(v).ok())
+        .unwrap_or_default();
+    Ok(Some(CommandInfo {
+        mutating: flags
+            .iter()
+            .any(|f| MUTATING_FLAGS.contains(&f.to_ascii_lowercase().as_str())),
+        // Subcommands are the tenth field, Redis 7+
+        has_subcommands: matches!(entry.get(9), Some(redis::Value::Array(subs)) if !subs.is_empty()),
+        first_key: entry
+            .get(3)
+            .cloned()
+            .and_then(|v| redis::from_redis_value::<i64>(v).ok())
+            .and_then(|position| usize::try_from(position).ok())
+            .unwrap_or(0),
+    }))
+}
+
+/// One-shot confirmation tokens handed out for dangerous commands. A token
+/// is bound to the exact command it was issued for and expires after a
+/// minute, so a confirmed FLUSHDB cannot be replayed later.
+#[derive(Default)]
+pub struct Confirmations {
+    pending: HashMap<String, (u64, Instant)>,
+    hasher: RandomState,
+    issued: u64,
+}
+
+impl Confirmations {
+    /// Consumes a matching `token`, or issues a new one and returns a
+    /// `confirmation_required` error carrying it.
+    pub fn check(
+        &mut self,
+        reason: &str,
+        argv: &[Vec<u8>],
+        token: Option<&str>,
+    ) -> Result<(), CommandError> {
+        self.pending
+            .retain(|_, (_, issued_at)| issued_at.elapsed() < CONFIRM_TOKEN_TTL);
+
+        let fingerprint = self.hasher.hash_one(argv);
+        if let Some(token) = token {
+            if self
+                .pending
+                .get(token)
+                .is_some_and(|(f, _)| *f == fingerprint)
+            {
+                self.pending.remove(token);
+                return Ok(());
+            }
+        }
+
+        self.issued += 1;
+        let new_token = format!(
+            "{:016x}",
+            self.hasher
+                .hash_one((self.issued, fingerprint, Instant::now()))
+        );
+        self.pending
+            .insert(new_token.clone(), (fingerprint, Instant::now()));
+
+        let mut error = CommandError::new(
+            ErrorKind::ConfirmationRequired,
+            format!("{} needs confirmation", reason),
+        );
+        error.confirm_token = Some(new_token);
+        Err(error)
+    }
+}
@@ -0,0 +1,200 @@
+//! Live server metrics: polls INFO at a fixed interval and turns the
+//! cumulative counters into per-second rates for the dashboard charts.
+
+use crate::connection::{ConnectionManager, RedisConnection};
+use crate::error::{CommandError, ErrorKind};
+use crate::info::{self, ServerInfo};
+use serde::Serialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Manager};
+
+/// Polling faster than this mostly measures the sampler itself.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The sections the charts read. Several at once needs Redis 7.0, older
+/// servers get the default sections.
+const SECTIONS: &[&str] = &["clients", "memory", "stats", "cpu", "keyspace"];
+
+/// One point of the live charts. Rates are averages over the time since
+/// the previous sample and missing on the first one.
+#[derive(Serialize, Clone)]
+pub struct InfoSample {
+    /// Unix milliseconds
+    pub time_ms: u64,
+    pub ops_per_sec: Option<f64>,
+    /// Share of key lookups that found the key, `None` without lookups
+    pub hit_ratio: Option<f64>,
+    pub net_input_per_sec: Option<f64>,
+    pub net_output_per_sec: Option<f64>,
+    /// Server CPU time (user + sys) per wall-clock time, 100 = one core
+    pub cpu_percent: Option<f64>,
+    pub expired_per_sec: Option<f64>,
+    pub evicted_per_sec: Option<f64>,
+    pub used_memory: Option<u64>,
+    pub used_memory_rss: Option<u64>,
+    pub connected_clients: Option<u64>,
+    pub blocked_clients: Option<u64>,
+    pub keys: u64,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SamplerEvent {
+    Sample(InfoSample),
+    Error {
+        message: String,
+        /// The sampler stopped, otherwise it retries on the next tick
+        fatal: bool,
+    },
+}
+
+/// Cumulative counters of one INFO reply.
+struct Counters {
+    at: Instant,
+    commands: Option<u64>,
+    hits: Option<u64>,
+    misses: Option<u64>,
+    net_input: Option<u64>,
+    net_output: Option<u64>,
+    cpu: Option<f64>,
+    expired: Option<u64>,
+    evicted: Option<u64>,
+}
+
+impl Counters {
+    fn new(info: &ServerInfo) -> Self {
+        let cpu = match (info.cpu.used_cpu_user, info.cpu.used_cpu_sys) {
+            (Some(user), Some(sys)) => Some(user + sys),
+            _ => None,
+        };
+        Counters {
+            at: Instant::now(),
+            commands: info.stats.total_commands_processed,
+            hits: info.stats.keyspace_hits,
+            misses: info.stats.keyspace_misses,
+            net_input: info.stats.total_net_input_bytes,
+            net_output: info.stats.total_net_output_bytes,
+            cpu,
+            expired: info.stats.expired_keys,
+            evicted: info.stats.evicted_keys,
+        }
+    }
+}
+
+/// Growth of a counter. One that went down (CONFIG RESETSTAT, a restart)
+/// gives nothing.
+fn delta(now: Option<u64>, before: Option<u64>) -> Option<u64> {
+    now?.checked_sub(before?)
+}
+
+fn rate(now: Option<u64>, before: Option<u64>, seconds: f64) -> Option<f64> {
+    delta(now, before).map(|d| d as f64 / seconds)
+}
+
+fn sample(info: &ServerInfo, now: &Counters, before: Option<&Counters>) -> InfoSample {
+    let mut sample = InfoSample {
+        time_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        ops_per_sec: None,
+        hit_ratio: None,
+        net_input_per_sec: None,
+        net_output_per_sec: None,
+        cpu_percent: None,
+        expired_per_sec: None,
+        evicted_per_sec: None,
+        used_memory: info.memory.used_memory,
+        used_memory_rss: info.memory.used_memory_rss,
+        connected_clients: info.clients.connected_clients,
+        blocked_clients: info.clients.blocked_clients,
+        keys: info.keyspace.iter().map(|db| db.keys).sum(),
+    };
+    let Some(before) = before else {
+        return sample;
+    };
+
+    let seconds = now.at.duration_since(before.at).as_secs_f64();
+    if seconds <= 0.0 {
+        return sample;
+    }
+    sample.ops_per_sec = rate(now.commands, before.commands, seconds);
+    sample.net_input_per_sec = rate(now.net_input, before.net_input, seconds);
+    sample.net_output_per_sec = rate(now.net_output, before.net_output, seconds);
+    sample.expired_per_sec = rate(now.expired, before.expired, seconds);
+    sample.evicted_per_sec = rate(now.evicted, before.evicted, seconds);
+    sample.cpu_percent = match (now.cpu, before.cpu) {
+        (Some(now), Some(before)) if now >= before => Some((now - before) / seconds * 100.0),
+        _ => None,
+    };
+
+    let hits = delta(now.hits, before.hits);
+    let misses = delta(now.misses, before.misses);
+    if let (Some(hits), Some(misses)) = (hits, misses) {
+        if hits + misses > 0 {
+            sample.hit_ratio = Some(hits as f64 / (hits + misses) as f64);
+        }
+    }
+    sample
+}
+
+/// INFO with `SECTIONS`, or the default sections once the server has
+/// refused several at once.
+async fn fetch(
+    con: &mut RedisConnection,
+    multi_section: &mut bool,
+) -> Result<ServerInfo, CommandError> {
+    if *multi_section {
+        match info::fetch_sections(con, SECTIONS).await {
+            Err(e) if e.code.as_deref() == Some("ERR") => *multi_section = false,
+            result => return result,
+        }
+    }
+    info::fetch_sections(con, &[]).await
+}
+
+/// Sends an `InfoSample` every `interval` until aborted or the connection
+/// is closed.
+pub async fn run(
+    app: AppHandle,
+    connection_id: String,
+    interval: Duration,
+    on_event: Channel<SamplerEvent>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut previous: Option<Counters> = None;
+    let mut multi_section = true;
+
+    loop {
+        ticker.tick().await;
+        let state = app.state::<ConnectionManager>();
+        let result = match state.get_connection(&connection_id, 0).await {
+            Ok(mut con) => fetch(&mut con, &mut multi_section).await,
+            Err(e) => Err(e),
+        };
+
+        // A closed channel means the page listening is gone
+        match result {
+            Ok(info) => {
+                let counters = Counters::new(&info);
+                let event = SamplerEvent::Sample(sample(&info, &counters, previous.as_ref()));
+                if on_event.send(event).is_err() {
+                    return;
+                }
+                previous = Some(counters);
+            }
+            Err(e) => {
+                let fatal = e.kind == ErrorKind::UnknownConnection;
+                let sent = on_event.send(SamplerEvent::Error {
+                    message: e.message,
+                    fatal,
+                });
+                if fatal || sent.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
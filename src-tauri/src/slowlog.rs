@@ -0,0 +1,208 @@
+//! SLOWLOG of every node, grouped by command and key pattern so that one
+//! slow access pattern stands out from many similar entries.
+
+use crate::connection::{ConnectionManager, RedisConnection};
+use crate::error::CommandError;
+use crate::keyspace::KeyDelimiter;
+use crate::RedisBytes;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// One SLOWLOG entry. Long arguments and argument lists come back
+/// shortened by the server.
+#[derive(Serialize)]
+pub struct SlowlogEntry {
+    pub id: i64,
+    /// Unix seconds
+    pub timestamp: i64,
+    pub duration_us: i64,
+    /// Upper-case command name
+    pub command: String,
+    /// See `SlowlogGroup::key_pattern`
+    pub key_pattern: Option<String>,
+    pub args: Vec<RedisBytes>,
+    /// Redis 4.0+
+    pub client_addr: Option<String>,
+    pub client_name: Option<String>,
+    /// Cluster node that logged the entry
+    pub node: Option<String>,
+}
+
+/// Slow entries of one command on keys of one pattern.
+#[derive(Serialize)]
+pub struct SlowlogGroup {
+    pub command: String,
+    /// Key with id-like segments replaced by `*`, `None` for keyless commands
+    pub key_pattern: Option<String>,
+    pub count: u64,
+    pub total_us: i64,
+    pub max_us: i64,
+}
+
+#[derive(Serialize)]
+pub struct SlowlogReport {
+    /// Newest first
+    pub entries: Vec<SlowlogEntry>,
+    /// Slowest total first
+    pub groups: Vec<SlowlogGroup>,
+    /// `slowlog-log-slower-than`, unknown when CONFIG is not allowed
+    pub threshold_us: Option<i64>,
+    /// `slowlog-max-len`
+    pub max_len: Option<i64>,
+}
+
+/// Replies of a command cluster connections send to every node (a node ->
+/// reply map), or the single reply of a standalone server.
+fn node_replies(value: redis::Value) -> Vec<(Option<String>, redis::Value)> {
+    match value {
+        redis::Value::Map(nodes) => nodes
+            .into_iter()
+            .map(|(node, reply)| (redis::from_redis_value::<String>(node).ok(), reply))
+            .collect(),
+        v => vec![(None, v)],
+    }
+}
+
+/// A segment that differs per key: numbers, hex ids and UUIDs.
+fn is_id_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && (segment.bytes().all(|b| b.is_ascii_digit())
+            || (segment.len() >= 8 && segment.bytes().all(|b| b.is_ascii_hexdigit()))
+            || (segment.len() == 36 && segment.bytes().all(|b| b.is_ascii_hexdigit() || b == b'-')))
+}
+
+/// `user:1234:profile` -> `user:*:profile`
+fn key_pattern(key: &[u8], delimiter: &KeyDelimiter) -> String {
+    let delimiter = delimiter.as_str();
+    String::from_utf8_lossy(key)
+        .split(delimiter)
+        .map(|segment| if is_id_segment(segment) { "*" } else { segment })
+        .collect::<Vec<_>>()
+        .join(delimiter)
+}
+
+/// An entry as SLOWLOG GET returns it, the key pattern still unset.
+fn parse_entry(value: redis::Value, node: Option<&String>) -> Option<(SlowlogEntry, Vec<Vec<u8>>)> {
+    let redis::Value::Array(fields) = value else {
+        return None;
+    };
+    let mut fields = fields.into_iter();
+    let id: i64 = redis::from_redis_value(fields.next()?).ok()?;
+    let timestamp: i64 = redis::from_redis_value(fields.next()?).ok()?;
+    let duration_us: i64 = redis::from_redis_value(fields.next()?).ok()?;
+    let args: Vec<Vec<u8>> = redis::from_redis_value(fields.next()?).ok()?;
+    let mut text = || {
+        fields
+            .next()
+            .and_then(|v| redis::from_redis_value::<String>(v).ok())
+            .filter(|s| !s.is_empty())
+    };
+    let client_addr = text();
+    let client_name = text();
+
+    let command = args
+        .first()
+        .map(|c| String::from_utf8_lossy(c).to_ascii_uppercase())
+        .unwrap_or_default();
+    let entry = SlowlogEntry {
+        id,
+        timestamp,
+        duration_us,
+        command,
+        key_pattern: None,
+        args: args.iter().cloned().map(RedisBytes::from).collect(),
+        client_addr,
+        client_name,
+        node: node.cloned(),
+    };
+    Some((entry, args))
+}
+
+fn group(entries: &[SlowlogEntry]) -> Vec<SlowlogGroup> {
+    let mut groups: HashMap<(&str, Option<&str>), SlowlogGroup> = HashMap::new();
+    for entry in entries {
+        let group = groups
+            .entry((&entry.command, entry.key_pattern.as_deref()))
+            .or_insert_with(|| SlowlogGroup {
+                command: entry.command.clone(),
+                key_pattern: entry.key_pattern.clone(),
+                count: 0,
+                total_us: 0,
+                max_us: 0,
+            });
+        group.count += 1;
+        group.total_us += entry.duration_us;
+        group.max_us = group.max_us.max(entry.duration_us);
+    }
+    let mut groups: Vec<SlowlogGroup> = groups.into_values().collect();
+    groups.sort_by_key(|g| Reverse(g.total_us));
+    groups
+}
+
+/// The last `count` entries of every node (128 by default, -1 for all on
+/// Redis 7) with their groups. Which argument is the key comes from the
+/// server's COMMAND INFO.
+pub async fn report(
+    state: &ConnectionManager,
+    connection_id: &str,
+    count: Option<i64>,
+    delimiter: &KeyDelimiter,
+) -> Result<SlowlogReport, CommandError> {
+    let mut con = state.get_connection(connection_id, 0).await?;
+
+    let reply: redis::Value = redis::cmd("SLOWLOG")
+        .arg("GET")
+        .arg(count.unwrap_or(128))
+        .query_async(&mut con)
+        .await
+        .map_err(|e| CommandError::redis("SLOWLOG GET error", e))?;
+
+    let mut entries = Vec::new();
+    for (node, reply) in node_replies(reply) {
+        let redis::Value::Array(items) = reply else {
+            continue;
+        };
+        for item in items {
+            let Some((mut entry, args)) = parse_entry(item, node.as_ref()) else {
+                continue;
+            };
+            // ACLs may deny COMMAND, the entries are still useful
+            let key = state
+                .first_key_index(connection_id, &mut con, &args)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|index| args.get(index));
+            entry.key_pattern = key.map(|key| key_pattern(key, delimiter));
+            entries.push(entry);
+        }
+    }
+    entries.sort_by_key(|e| Reverse((e.timestamp, e.id)));
+    let groups = group(&entries);
+
+    let config = slowlog_config(&mut con).await;
+    Ok(SlowlogReport {
+        entries,
+        groups,
+        threshold_us: config
+            .as_ref()
+            .and_then(|c| c.get("slowlog-log-slower-than").copied()),
+        max_len: config
+            .as_ref()
+            .and_then(|c| c.get("slowlog-max-len").copied()),
+    })
+}
+
+/// CONFIG GET slowlog-*. CONFIG may be renamed or denied by ACL, the log
+/// is still useful without it.
+async fn slowlog_config(con: &mut RedisConnection) -> Option<HashMap<String, i64>> {
+    redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("slowlog-*")
+        .query_async::<redis::Value>(con)
+        .await
+        .ok()
+        .and_then(|v| node_replies(v).into_iter().next())
+        .and_then(|(_, v)| redis::from_redis_value(v).ok())
+}
@@ -0,0 +1,482 @@
+use polling::{Event, Events, Poller};
+use serde::{Deserialize, Serialize};
+use ssh2::{BlockDirections, Channel, CheckResult, ErrorCode, KnownHostFileKind, Session};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SSH_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const SSH_KEEPALIVE_SECS: u32 = 30;
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;
+/// libssh2 errors meaning the connection to the bastion is gone
+/// (SOCKET_SEND, SOCKET_DISCONNECT, SOCKET_TIMEOUT, SOCKET_RECV).
+const LIBSSH2_SOCKET_ERRORS: &[i32] = &[-7, -13, -30, -43];
+const BUFFER_SIZE: usize = 32 * 1024;
+
+fn default_ssh_port() -> String {
+    "22".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SshConfig {
+    pub host: String,
+    #[serde(default = "default_ssh_port")]
+    pub port: String,
+    pub username: String,
+    pub password: Option<String>,
+    pub private_key_path: Option<String>,
+    pub passphrase: Option<String>,
+    /// Refuse bastions whose host key is unknown or changed
+    #[serde(default = "default_true")]
+    pub strict_host_key_checking: bool,
+    /// Defaults to `~/.ssh/known_hosts`
+    pub known_hosts_path: Option<String>,
+}
+
+/// A local port forward `127.0.0.1:<local_port>` -> bastion -> Redis.
+/// Dropping it stops the forwarding thread and closes the SSH session.
+pub struct SshTunnel {
+    pub local_port: u16,
+    control: Arc<Control>,
+}
+
+/// Shared between a tunnel and its forwarding thread.
+struct Control {
+    shutdown: AtomicBool,
+    /// Set by the thread when the bastion session is gone
+    disconnected: AtomicBool,
+    /// The thread waits on it, `notify` wakes it up for the shutdown
+    poller: Poller,
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        self.control.shutdown.store(true, Ordering::Relaxed);
+        let _ = self.control.poller.notify();
+    }
+}
+
+impl SshTunnel {
+    /// Connects and authenticates to the bastion, then starts forwarding.
+    /// Blocking, call it from `spawn_blocking`.
+    pub fn open(ssh: &SshConfig, remote_host: &str, remote_port: u16) -> Result<SshTunnel, String> {
+        let (session, socket) = connect_session(ssh)?;
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .map_err(|e| format!("Failed to bind local tunnel port: {}", e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure tunnel listener: {}", e))?;
+        let local_port = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read tunnel port: {}", e))?
+            .port();
+
+        let control = Arc::new(Control {
+            shutdown: AtomicBool::new(false),
+            disconnected: AtomicBool::new(false),
+            poller: Poller::new().map_err(|e| format!("Failed to create tunnel poller: {}", e))?,
+        });
+        let thread_control = control.clone();
+        let target = remote_host.to_string();
+        thread::Builder::new()
+            .name(format!("ssh-tunnel-{}", local_port))
+            .spawn(move || {
+                let tunnel = Tunnel {
+                    session,
+                    socket,
+                    listener,
+                    remote_host: target,
+                    remote_port,
+                };
+                tunnel.run(&thread_control);
+                thread_control.disconnected.store(true, Ordering::Relaxed);
+            })
+            .map_err(|e| format!("Failed to start tunnel thread: {}", e))?;
+
+        Ok(SshTunnel {
+            local_port,
+            control,
+        })
+    }
+
+    /// False once the bastion session has dropped. The tunnel then no longer
+    /// forwards anything and has to be opened again.
+    pub fn is_alive(&self) -> bool {
+        !self.control.disconnected.load(Ordering::Relaxed)
+    }
+}
+
+/// Returns the authenticated session and a handle on its TCP socket, used to
+/// wait for it to become ready.
+fn connect_session(ssh: &SshConfig) -> Result<(Session, TcpStream), String> {
+    let port: u16 = ssh
+        .port
+        .parse()
+        .map_err(|_| format!("Invalid SSH port: {}", ssh.port))?;
+
+    let addr = (ssh.host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve SSH host {}: {}", ssh.host, e))?
+        .next()
+        .ok_or_else(|| format!("Failed to resolve SSH host {}", ssh.host))?;
+    let tcp = TcpStream::connect_timeout(&addr, SSH_CONNECT_TIMEOUT)
+        .map_err(|e| format!("SSH connection to {}:{} failed: {}", ssh.host, port, e))?;
+
+    let socket = tcp
+        .try_clone()
+        .map_err(|e| format!("SSH socket error: {}", e))?;
+    let mut session = Session::new().map_err(|e| format!("SSH session error: {}", e))?;
+    session.set_tcp_stream(tcp);
+    session
+        .handshake()
+        .map_err(|e| format!("SSH handshake failed: {}", e))?;
+
+    if ssh.strict_host_key_checking {
+        verify_host_key(&session, ssh, port)?;
+    }
+
+    let auth = match (&ssh.private_key_path, &ssh.password) {
+        (Some(key), _) if !key.is_empty() => session.userauth_pubkey_file(
+            &ssh.username,
+            None,
+            &expand_home(key),
+            ssh.passphrase.as_deref().filter(|p| !p.is_empty()),
+        ),
+        (_, Some(password)) if !password.is_empty() => {
+            session.userauth_password(&ssh.username, password)
+        }
+        _ => session.userauth_agent(&ssh.username),
+    };
+    auth.map_err(|e| format!("SSH authentication failed: {}", e))?;
+
+    if !session.authenticated() {
+        return Err("SSH authentication failed".to_string());
+    }
+
+    session.set_keepalive(true, SSH_KEEPALIVE_SECS);
+    Ok((session, socket))
+}
+
+fn verify_host_key(session: &Session, ssh: &SshConfig, port: u16) -> Result<(), String> {
+    let path = match &ssh.known_hosts_path {
+        Some(p) if !p.is_empty() => expand_home(p),
+        _ => default_known_hosts().ok_or_else(|| "Cannot locate ~/.ssh/known_hosts".to_string())?,
+    };
+
+    let mut known_hosts = session
+        .known_hosts()
+        .map_err(|e| format!("known_hosts error: {}", e))?;
+    known_hosts
+        .read_file(&path, KnownHostFileKind::OpenSSH)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| "SSH server sent no host key".to_string())?;
+
+    match known_hosts.check_port(&ssh.host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => Err(format!(
+            "Host key for {} is not in {}",
+            ssh.host,
+            path.display()
+        )),
+        CheckResult::Mismatch => Err(format!(
+            "Host key for {} does not match {}, possible man-in-the-middle attack",
+            ssh.host,
+            path.display()
+        )),
+        CheckResult::Failure => Err(format!("Failed to check host key for {}", ssh.host)),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+fn default_known_hosts() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".ssh").join("known_hosts"))
+}
+
+/// Expands a leading `~/` the way a shell would.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn would_block(e: &std::io::Error) -> bool {
+    e.kind() == ErrorKind::WouldBlock
+}
+
+fn is_socket_error(e: &ssh2::Error) -> bool {
+    matches!(e.code(), ErrorCode::Session(code) if LIBSSH2_SOCKET_ERRORS.contains(&code))
+}
+
+/// Poller keys. Forwards count up from `FIRST_FORWARD`.
+const LISTENER: usize = 0;
+const SESSION: usize = 1;
+const FIRST_FORWARD: usize = 2;
+
+/// One accepted local socket and its SSH channel.
+struct Forward {
+    key: usize,
+    socket: TcpStream,
+    channel: Channel,
+    to_remote: Vec<u8>,
+    to_local: Vec<u8>,
+    closed: bool,
+}
+
+impl Forward {
+    /// Moves whatever is ready in both directions. Returns true if any
+    /// bytes were transferred.
+    fn pump(&mut self, buf: &mut [u8]) -> bool {
+        let mut progressed = false;
+
+        if self.to_remote.is_empty() {
+            match self.socket.read(buf) {
+                Ok(0) => self.closed = true,
+                Ok(n) => self.to_remote.extend_from_slice(&buf[..n]),
+                Err(e) if would_block(&e) => {}
+                Err(_) => self.closed = true,
+            }
+        }
+        if !self.to_remote.is_empty() {
+            match self.channel.write(&self.to_remote) {
+                Ok(n) => {
+                    self.to_remote.drain(..n);
+                    progressed = true;
+                }
+                Err(e) if would_block(&e) => {}
+                Err(_) => self.closed = true,
+            }
+        }
+
+        if self.to_local.is_empty() {
+            match self.channel.read(buf) {
+                Ok(0) if self.channel.eof() => self.closed = true,
+                Ok(0) => {}
+                Ok(n) => self.to_local.extend_from_slice(&buf[..n]),
+                Err(e) if would_block(&e) => {}
+                Err(_) => self.closed = true,
+            }
+        }
+        if !self.to_local.is_empty() {
+            match self.socket.write(&self.to_local) {
+                Ok(n) => {
+                    self.to_local.drain(..n);
+                    progressed = true;
+                }
+                Err(e) if would_block(&e) => {}
+                Err(_) => self.closed = true,
+            }
+        }
+
+        progressed
+    }
+
+    /// Whether the channel reads from the SSH socket on the next `pump`.
+    fn reads_session(&self) -> bool {
+        self.to_local.is_empty() || !self.to_remote.is_empty()
+    }
+
+    /// Data or EOF libssh2 already took off the SSH socket while reading
+    /// another channel. Waiting on the socket would not see it.
+    fn has_buffered(&self) -> bool {
+        self.to_local.is_empty() && (self.channel.read_window().available > 0 || self.channel.eof())
+    }
+
+    fn interest(&self) -> Event {
+        Event::new(
+            self.key,
+            self.to_remote.is_empty(),
+            !self.to_local.is_empty(),
+        )
+    }
+}
+
+/// The forwarding thread's end of a tunnel.
+struct Tunnel {
+    session: Session,
+    /// Clone of the session's TCP stream, only waited on
+    socket: TcpStream,
+    listener: TcpListener,
+    remote_host: String,
+    remote_port: u16,
+}
+
+impl Tunnel {
+    /// Forwards until shut down or the bastion session drops. libssh2
+    /// sessions are not safe to drive from several threads, so the session
+    /// is switched to non-blocking mode and every socket/channel pair is
+    /// served here, waiting on the poller whenever a round moved nothing.
+    fn run(self, control: &Control) {
+        let poller = &control.poller;
+        self.session.set_blocking(false);
+        if self.socket.set_nonblocking(true).is_err() {
+            return;
+        }
+        // SAFETY: both are deleted below before they are dropped
+        unsafe {
+            if poller.add(&self.listener, Event::none(LISTENER)).is_err() {
+                return;
+            }
+            if poller.add(&self.socket, Event::none(SESSION)).is_err() {
+                let _ = poller.delete(&self.listener);
+                return;
+            }
+        }
+
+        let mut forwards: Vec<Forward> = Vec::new();
+        self.serve(control, &mut forwards);
+
+        for forward in forwards.iter() {
+            let _ = poller.delete(&forward.socket);
+        }
+        let _ = poller.delete(&self.socket);
+        let _ = poller.delete(&self.listener);
+    }
+
+    fn serve(&self, control: &Control, forwards: &mut Vec<Forward>) {
+        let poller = &control.poller;
+        let keepalive = Duration::from_secs(SSH_KEEPALIVE_SECS as u64);
+        let mut pending: Vec<TcpStream> = Vec::new();
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        let mut events = Events::new();
+        let mut next_key = FIRST_FORWARD;
+        let mut last_keepalive = Instant::now();
+
+        while !control.shutdown.load(Ordering::Relaxed) {
+            let mut progressed = false;
+
+            match self.listener.accept() {
+                Ok((socket, _)) => {
+                    if socket.set_nonblocking(true).is_ok() {
+                        pending.push(socket);
+                    }
+                    progressed = true;
+                }
+                Err(e) if would_block(&e) => {}
+                Err(_) => return,
+            }
+
+            // Opening a channel takes several round trips in non-blocking mode
+            let mut still_pending = Vec::new();
+            for socket in pending.drain(..) {
+                match self
+                    .session
+                    .channel_direct_tcpip(&self.remote_host, self.remote_port, None)
+                {
+                    Ok(channel) => {
+                        let key = next_key;
+                        next_key += 1;
+                        // SAFETY: deleted before the forward is dropped
+                        if unsafe { poller.add(&socket, Event::none(key)) }.is_ok() {
+                            forwards.push(Forward {
+                                key,
+                                socket,
+                                channel,
+                                to_remote: Vec::new(),
+                                to_local: Vec::new(),
+                                closed: false,
+                            });
+                        }
+                        progressed = true;
+                    }
+                    Err(e) if e.code() == ErrorCode::Session(LIBSSH2_ERROR_EAGAIN) => {
+                        still_pending.push(socket)
+                    }
+                    Err(e) if is_socket_error(&e) => return,
+                    // Dropping the socket tells the Redis client the connect failed
+                    Err(_) => {}
+                }
+            }
+            pending = still_pending;
+
+            for forward in forwards.iter_mut() {
+                progressed |= forward.pump(&mut buf);
+            }
+            forwards.retain(|f| {
+                if f.closed {
+                    let _ = poller.delete(&f.socket);
+                }
+                !f.closed
+            });
+
+            if last_keepalive.elapsed() >= keepalive {
+                match self.session.keepalive_send() {
+                    Ok(_) => {}
+                    Err(e) if e.code() == ErrorCode::Session(LIBSSH2_ERROR_EAGAIN) => {}
+                    Err(_) => return,
+                }
+                last_keepalive = Instant::now();
+            }
+
+            if progressed || forwards.iter().any(Forward::has_buffered) {
+                continue;
+            }
+
+            // Interests are oneshot, so every wait re-arms them
+            let blocked = self.session.block_directions();
+            let read_session = !pending.is_empty()
+                || forwards.iter().any(Forward::reads_session)
+                || matches!(blocked, BlockDirections::Inbound | BlockDirections::Both);
+            let write_session =
+                matches!(blocked, BlockDirections::Outbound | BlockDirections::Both);
+            let armed = poller
+                .modify(&self.listener, Event::readable(LISTENER))
+                .and_then(|_| {
+                    poller.modify(
+                        &self.socket,
+                        Event::new(SESSION, read_session, write_session),
+                    )
+                })
+                .and_then(|_| {
+                    forwards
+                        .iter()
+                        .try_for_each(|f| poller.modify(&f.socket, f.interest()))
+                });
+            if armed.is_err() {
+                return;
+            }
+
+            events.clear();
+            let timeout = keepalive.saturating_sub(last_keepalive.elapsed());
+            match poller.wait(&mut events, Some(timeout)) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return,
+            }
+            if events
+                .iter()
+                .any(|event| event.key == SESSION && event.readable && self.bastion_closed())
+            {
+                return;
+            }
+        }
+    }
+
+    /// Whether the bastion closed the connection, checked when its socket
+    /// turns readable.
+    fn bastion_closed(&self) -> bool {
+        match self.socket.peek(&mut [0u8; 1]) {
+            Ok(0) => true,
+            Ok(_) => false,
+            Err(e) => !would_block(&e) && e.kind() != ErrorKind::Interrupted,
+        }
+    }
+}
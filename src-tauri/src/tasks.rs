@@ -0,0 +1,46 @@
+use crate::error::CommandError;
+use crate::jobs::Registry;
+use std::future::Future;
+use tokio::task::AbortHandle;
+
+/// Background tasks that keep running after the command starting them has
+/// returned (the INFO sampler, MONITOR, ...), pushing their results over a
+/// channel. As with jobs, the caller picks the task id.
+pub struct Tasks {
+    registry: Registry<AbortHandle>,
+}
+
+impl Default for Tasks {
+    fn default() -> Self {
+        Tasks {
+            registry: Registry::new("Task"),
+        }
+    }
+}
+
+impl Tasks {
+    pub fn spawn<F>(&self, id: &str, task: F) -> Result<(), CommandError>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.registry.insert_with(id, |registration| {
+            tokio::spawn(async move {
+                // Unregisters when the task ends on its own or is aborted
+                let _registration = registration;
+                task.await;
+            })
+            .abort_handle()
+        })
+    }
+
+    /// Aborts a task. Returns false when no such task is running.
+    pub fn stop(&self, id: &str) -> bool {
+        match self.registry.take(id) {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,53 @@
+import { Store } from "@tauri-apps/plugin-store";
+
+// Giới hạn số lệnh lưu cho mỗi server
+const MAX_HISTORY = 200;
+
+let store = null;
+
+/**
+ * Khởi tạo Tauri Store
+ * Store file: console-history.json
+ */
+async function initStore() {
+  if (!store) {
+    store = await Store.load("console-history.json");
+  }
+  return store;
+}
+
+/**
+ * Key lưu lịch sử của một server (không chứa mật khẩu)
+ * @param {{host: string, port: string}} config
+ * @returns {string}
+ */
+export function historyKey(config) {
+  return `${config.host}:${config.port}`;
+}
+
+/**
+ * Load lịch sử lệnh của một server
+ * @param {string} key
+ * @returns {Promise<string[]>} Lệnh cũ nhất trước
+ */
+export async function loadHistory(key) {
+  const s = await initStore();
+  const history = await s.get(key);
+  return history || [];
+}
+
+/**
+ * Thêm một lệnh vào lịch sử và lưu lại
+ * @param {string} key
+ * @param {string} command
+ * @returns {Promise<string[]>} Lịch sử mới
+ */
+export async function appendHistory(key, command) {
+  const s = await initStore();
+  const history = ((await s.get(key)) || []).filter((c) => c !== command);
+  history.push(command);
+  const trimmed = history.slice(-MAX_HISTORY);
+  await s.set(key, trimmed);
+  await s.save();
+  return trimmed;
+}
@@ -8,6 +8,9 @@ import {
 // Cấu hình Redis đang kết nối
 export const activeConfig = writable(null);
 
+// Id của session backend tương ứng, truyền vào mọi command
+export const activeConnectionId = writable(null);
+
 // Danh sách servers đã lưu
 function createSavedServersStore() {
   const { subscribe, set, update } = writable([]);
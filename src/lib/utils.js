@@ -1,3 +1,5 @@
+import { invoke } from "@tauri-apps/api/core";
+import { ask } from "@tauri-apps/plugin-dialog";
 import { getCurrentWindow, LogicalSize } from "@tauri-apps/api/window";
 
 /**
@@ -15,6 +17,86 @@ export async function resizeWindow(width, height) {
   }
 }
 
+/**
+ * Message of an error thrown by `invoke`. Backend commands reject with
+ * `{ kind, code, message, retryable }`; anything else is stringified.
+ * @param {any} error
+ * @returns {string}
+ */
+export function errorMessage(error) {
+  return error?.message ?? String(error);
+}
+
+/**
+ * Invoke a command that may be guarded by the profile's confirmation list.
+ * When the backend answers `confirmation_required`, ask the user and resend
+ * with the one-shot token it returned. Resolves to `null` if declined.
+ * @param {string} command
+ * @param {Record<string, any>} args
+ * @returns {Promise<any>}
+ */
+export async function invokeConfirmed(command, args) {
+  try {
+    return await invoke(command, args);
+  } catch (error) {
+    if (error?.kind !== "confirmation_required") throw error;
+    const confirmed = await ask(`${error.message}. Run it anyway?`, {
+      title: "Dangerous command",
+      kind: "warning",
+    });
+    if (!confirmed) return null;
+    return await invoke(command, { ...args, confirmToken: error.confirm_token });
+  }
+}
+
+export const BINARY_PREFIX = "[Binary/Base64]: ";
+
+/**
+ * Wrap text as the `{ encoding, data }` bytes that commands accept for
+ * elements, members, fields and values
+ * @param {string} data - Plain text, or Base64 when `binary` is set
+ * @param {boolean} [binary]
+ * @returns {{encoding: string, data: string}}
+ */
+export function toRedisBytes(data, binary = false) {
+  return { encoding: binary ? "base64" : "utf8", data };
+}
+
+/**
+ * Display text of `{ encoding, data }` bytes returned by the backend
+ * @param {{encoding: string, data: string}} bytes
+ * @returns {string}
+ */
+export function bytesLabel(bytes) {
+  if (!bytes) return "";
+  return bytes.encoding === "base64" ? BINARY_PREFIX + bytes.data : bytes.data;
+}
+
+/**
+ * The `{ encoding, data }` bytes of a key shown with `bytesLabel`, for
+ * commands that take key names
+ * @param {string} label
+ * @returns {{encoding: string, data: string}}
+ */
+export function keyBytes(label) {
+  return label.startsWith(BINARY_PREFIX)
+    ? toRedisBytes(label.slice(BINARY_PREFIX.length), true)
+    : toRedisBytes(label);
+}
+
+/**
+ * Human-readable size, e.g. "1.5 MB"
+ * @param {number} bytes
+ * @returns {string}
+ */
+export function formatBytes(bytes) {
+  if (bytes === 0) return "0 B";
+  const k = 1024;
+  const sizes = ["B", "KB", "MB", "GB", "TB"];
+  const i = Math.floor(Math.log(bytes) / Math.log(k));
+  return parseFloat((bytes / Math.pow(k, i)).toFixed(2)) + " " + sizes[i];
+}
+
 /**
  * Check if a Uint8Array is valid UTF-8 and contains printable characters
  * @param {Uint8Array} bytes
@@ -55,10 +137,9 @@ export function bytesToHex(bytes) {
  * @returns {string}
  */
 function formatRawValue(val, type = "String") {
+  // Kept whole so an edited value can be decoded back to the same bytes
   if (type === "Binary") {
-    return `[Binary/Base64]: ${val.slice(0, 100)}${
-      val.length > 100 ? "..." : ""
-    }`;
+    return BINARY_PREFIX + val;
   }
 
   // Truncate extremely large strings for UI stability (1MB limit)
@@ -76,9 +157,10 @@ function formatRawValue(val, type = "String") {
 /**
  * Format key value for display
  * @param {{type: string, value: any}} keyValue
+ * @param {number} [length] - Total element count reported by the backend
  * @returns {string}
  */
-export function formatKeyValue(keyValue) {
+export function formatKeyValue(keyValue, length) {
   if (!keyValue || (keyValue.type === "None" && !keyValue.value)) return "";
 
   const { type, value } = keyValue;
@@ -89,28 +171,45 @@ export function formatKeyValue(keyValue) {
       return formatRawValue(value, type);
     case "List":
     case "Set": {
-      const limitedValue = value.slice(0, 500);
+      const limitedValue = value.slice(0, 500).map(bytesLabel);
       let json = JSON.stringify(limitedValue, null, 2);
-      if (value.length > 500) {
-        json += `\n\n--- [Vượt quá 500 phần tử, chỉ hiển thị ${limitedValue.length} phần tử đầu tiên] ---`;
+      if ((length ?? value.length) > limitedValue.length) {
+        json += `\n\n--- [Tổng cộng ${length ?? value.length} phần tử, chỉ hiển thị ${limitedValue.length} phần tử đầu tiên] ---`;
       }
       return json;
     }
     case "ZSet": {
-      const limitedValue = value.slice(0, 500);
+      const limitedValue = value
+        .slice(0, 500)
+        .map(([member, score]) => [bytesLabel(member), score]);
       let json = JSON.stringify(limitedValue, null, 2);
-      if (value.length > 500) {
-        json += `\n\n--- [Vượt quá 500 phần tử, chỉ hiển thị ${limitedValue.length} phần tử đầu tiên] ---`;
+      if ((length ?? value.length) > limitedValue.length) {
+        json += `\n\n--- [Tổng cộng ${length ?? value.length} phần tử, chỉ hiển thị ${limitedValue.length} phần tử đầu tiên] ---`;
       }
       return json;
     }
     case "Hash": {
-      const entries = Object.entries(value);
+      const entries = value;
       const limitedEntries = entries.slice(0, 500);
-      const formattedHash = Object.fromEntries(limitedEntries);
+      const formattedHash = Object.fromEntries(
+        limitedEntries.map(([f, v]) => [bytesLabel(f), bytesLabel(v)])
+      );
       let json = JSON.stringify(formattedHash, null, 2);
-      if (entries.length > 500) {
-        json += `\n\n--- [Vượt quá 500 field, chỉ hiển thị ${limitedEntries.length} field đầu tiên] ---`;
+      if ((length ?? entries.length) > limitedEntries.length) {
+        json += `\n\n--- [Tổng cộng ${length ?? entries.length} field, chỉ hiển thị ${limitedEntries.length} field đầu tiên] ---`;
+      }
+      return json;
+    }
+    case "Stream": {
+      const entries = value.entries.map((entry) => ({
+        id: entry.id,
+        ...Object.fromEntries(
+          entry.fields.map(([f, v]) => [bytesLabel(f), bytesLabel(v)])
+        ),
+      }));
+      let json = JSON.stringify(entries, null, 2);
+      if (value.next_id) {
+        json += `\n\n--- [Stream còn nhiều entry, chỉ hiển thị ${entries.length} entry đầu tiên] ---`;
       }
       return json;
     }
@@ -118,6 +217,65 @@ export function formatKeyValue(keyValue) {
       return "No data";
   }
 }
+/**
+ * Format a console reply the way redis-cli prints it
+ * @param {{type: string, value: any}} reply
+ * @param {number} [indent]
+ * @returns {string}
+ */
+export function formatReply(reply, indent = 0) {
+  const { type, value } = reply;
+  const nested = (items, render) => {
+    if (items.length === 0) return "(empty array)";
+    const width = String(items.length).length;
+    return items
+      .map((item, i) => {
+        const prefix = `${String(i + 1).padStart(width)}) `;
+        const body = render(item, indent + prefix.length);
+        return (i === 0 ? "" : " ".repeat(indent)) + prefix + body;
+      })
+      .join("\n");
+  };
+
+  switch (type) {
+    case "nil":
+      return "(nil)";
+    case "int":
+      return `(integer) ${value}`;
+    case "string":
+      return JSON.stringify(value);
+    case "binary":
+      return BINARY_PREFIX + value;
+    case "status":
+      return value;
+    case "double":
+      return `(double) ${value}`;
+    case "boolean":
+      return `(${value ? "true" : "false"})`;
+    case "big_number":
+      return `(big number) ${value}`;
+    case "verbatim":
+      return value.text;
+    case "array":
+    case "set":
+      return nested(value, formatReply);
+    case "push":
+      return nested(value.data, formatReply);
+    case "map":
+      if (value.length === 0) return "(empty hash)";
+      return nested(value, ([k, v], inner) => {
+        const key = formatReply(k, inner);
+        return `${key} => ${formatReply(v, inner + key.length + 4)}`;
+      });
+    case "attribute":
+      return formatReply(value.data, indent);
+    case "error":
+      return `(error) ${value.code} ${value.message}`.trim();
+    default:
+      return String(value);
+  }
+}
+
 /**
  * Build a tree structure from a flat list of keys
  * @param {any[]} keys - Array of { name, key_type }